@@ -1,33 +1,123 @@
+use crate::app::builder::{AppBuilder, Context, Hooks, WindowRequest};
+use crate::gpu::gpu::GPUDevice;
+use crate::render::render::RenderSettings;
 use crate::window::window::WindowState;
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Instant;
 use winit::{
     application::ApplicationHandler,
     event::*,
-    event_loop::{ActiveEventLoop, EventLoop},
+    event_loop::ActiveEventLoop,
     keyboard::PhysicalKey,
-    window::Window,
+    window::{Window, WindowAttributes, WindowId},
 };
 
+struct ManagedWindow {
+    state: WindowState,
+    last_frame: Instant,
+}
+
+// Every window gets its own surface, camera and pipeline but they all share one GPUDevice.
+// Events are routed to the window they came from by WindowId.
 pub struct App {
-    state: Option<WindowState>,
+    windows: HashMap<WindowId, ManagedWindow>,
+    gpu: Option<Arc<GPUDevice>>,
+    attributes: WindowAttributes,
+    settings: RenderSettings,
+    hooks: Hooks,
+    requests: Vec<WindowRequest>,
+}
+
+impl Default for App {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl App {
     pub fn new() -> Self {
-        Self { state: None }
+        Self::with_config(Window::default_attributes(), RenderSettings::default(), Hooks::default())
+    }
+
+    pub fn with_config(attributes: WindowAttributes, settings: RenderSettings, hooks: Hooks) -> Self {
+        Self {
+            windows: HashMap::new(),
+            gpu: None,
+            attributes,
+            settings,
+            hooks,
+            requests: Vec::new(),
+        }
+    }
+
+    pub fn window_count(&self) -> usize {
+        self.windows.len()
+    }
+
+    fn insert_window(&mut self, state: WindowState) {
+        if self.gpu.is_none() {
+            self.gpu = Some(state.shared_gpu());
+        }
+        self.windows.insert(
+            state.window.id(),
+            ManagedWindow {
+                state,
+                last_frame: Instant::now(),
+            },
+        );
+    }
+
+    fn close_window(&mut self, event_loop: &ActiveEventLoop, id: WindowId) {
+        self.windows.remove(&id);
+
+        if self.windows.is_empty() {
+            event_loop.exit();
+        }
+    }
+
+    // Runs whatever the hooks asked for while they were holding a window
+    fn apply_requests(&mut self, event_loop: &ActiveEventLoop) {
+        for request in std::mem::take(&mut self.requests) {
+            match request {
+                WindowRequest::Open(window) => {
+                    // the first window normally shares its GPUDevice before any hook can ask for another one,
+                    // if none is there yet this window makes it the way resumed does and insert_window keeps it
+                    let state = match &self.gpu {
+                        Some(gpu) => WindowState::with_gpu(window, gpu.clone(), self.settings),
+                        None => pollster::block_on(WindowState::new(window, self.settings)),
+                    };
+                    match state {
+                        Ok(state) => self.insert_window(state),
+                        Err(e) => eprintln!("Unable to open window {}", e),
+                    }
+                }
+                WindowRequest::Close(id) => self.close_window(event_loop, id),
+            }
+        }
     }
 }
 
 impl ApplicationHandler<WindowState> for App {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
-        #[allow(unused_mut)]
-        let mut window_attributes = Window::default_attributes();
+        // resumed can fire more than once (mobile), the first window only gets made the first time
+        if !self.windows.is_empty() {
+            return;
+        }
 
-        let window = Arc::new(event_loop.create_window(window_attributes).unwrap());
+        let window = Arc::new(event_loop.create_window(self.attributes.clone()).unwrap());
 
-        {
-            self.state = Some(pollster::block_on(WindowState::new(window)).unwrap());
+        let mut state = match &self.gpu {
+            Some(gpu) => WindowState::with_gpu(window, gpu.clone(), self.settings).unwrap(),
+            None => pollster::block_on(WindowState::new(window, self.settings)).unwrap(),
+        };
+
+        if let Some(on_init) = self.hooks.on_init.as_mut() {
+            on_init(&mut Context::new(&mut state, event_loop, &mut self.requests));
         }
+
+        self.insert_window(state);
+        self.apply_requests(event_loop);
     }
     #[allow(unused_mut)]
     fn user_event(&mut self, _event_loop: &ActiveEventLoop, mut event: WindowState) {
@@ -38,25 +128,56 @@ impl ApplicationHandler<WindowState> for App {
                 event.window.inner_size().height,
             );
         }
-        self.state = Some(event);
+        self.insert_window(event);
     }
 
     fn window_event(
         &mut self,
         event_loop: &ActiveEventLoop,
-        _window_id: winit::window::WindowId,
+        window_id: WindowId,
         event: WindowEvent,
     ) {
-        let state = match &mut self.state {
+        let managed = match self.windows.get_mut(&window_id) {
             Some(canvas) => canvas,
             None => return,
         };
+        let state = &mut managed.state;
+
+        // the user gets first look at every event and can swallow it
+        if let Some(on_event) = self.hooks.on_event.as_mut()
+            && on_event(&mut Context::new(state, event_loop, &mut self.requests), &event)
+        {
+            self.apply_requests(event_loop);
+            return;
+        }
 
         match event {
-            WindowEvent::CloseRequested => event_loop.exit(),
+            WindowEvent::CloseRequested => self.close_window(event_loop, window_id),
             WindowEvent::Resized(size) => state.resize(size.width, size.height),
+            // the physical size might not change when the DPI does, the pixel camera still has to be rebuilt
+            WindowEvent::ScaleFactorChanged { .. } => {
+                let size = state.window.inner_size();
+                state.resize(size.width, size.height);
+            }
             WindowEvent::RedrawRequested => {
-                match state.render() {
+                let now = Instant::now();
+                let dt = now.duration_since(managed.last_frame).as_secs_f32();
+                managed.last_frame = now;
+
+                state.update(dt);
+
+                if let Some(on_update) = self.hooks.on_update.as_mut() {
+                    on_update(&mut Context::new(state, event_loop, &mut self.requests), dt);
+                }
+
+                let on_render = &mut self.hooks.on_render;
+                let result = state.render_with(|gpu, encoder, view| {
+                    if let Some(on_render) = on_render.as_mut() {
+                        on_render(gpu, encoder, view);
+                    }
+                });
+
+                match result {
                     Ok(_) => {}
                     // Reconfigure the surface if it's lost or outdated
                     Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
@@ -76,24 +197,30 @@ impl ApplicationHandler<WindowState> for App {
                         ..
                     },
                 ..
-            } => state.handle_key(event_loop, code, key_state.is_pressed()),
+            } => {
+                // Escape closes the window it was pressed in, not the whole app
+                let close = state.handle_key(code, key_state.is_pressed());
+                if close {
+                    self.close_window(event_loop, window_id);
+                }
+            }
+            WindowEvent::MouseInput { state: button_state, button, .. } => {
+                state.handle_mouse_button(button, button_state.is_pressed())
+            }
+            WindowEvent::CursorMoved { position, .. } => state.handle_cursor_moved(position.x, position.y),
+            WindowEvent::CursorLeft { .. } => state.handle_cursor_left(),
+            WindowEvent::MouseWheel { delta, .. } => state.handle_scroll(delta),
 
             _ => {}
         }
+
+        self.apply_requests(event_loop);
     }
 }
 
 pub fn run() -> Result<(), Box<dyn std::error::Error>> {
-    env_logger::init();
-
-    let event_loop = EventLoop::with_user_event().build()?;
-    let mut app = App::new();
-
-    event_loop.run_app(&mut app)?;
-
-    Ok(())
+    AppBuilder::new().run()
 }
 
-// THE run() FUNCTION STARTS THE event_loop and Window. event_loop.run_app() takes any type that impls ApplicationHandler trait.  
-
-    
+// THE run() FUNCTION STARTS THE event_loop and Window. event_loop.run_app() takes any type that impls ApplicationHandler trait.
+// AppBuilder::run() does the same thing but with the window attributes and hooks the user set.
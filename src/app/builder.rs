@@ -0,0 +1,222 @@
+use crate::app::app::App;
+use crate::gpu::gpu::GPUDevice;
+use crate::camera::camera::CameraMatrix;
+use crate::render::render::RenderSettings;
+use crate::window::window::WindowState;
+use std::sync::Arc;
+use winit::{
+    dpi::LogicalSize,
+    error::OsError,
+    event::WindowEvent,
+    event_loop::{ActiveEventLoop, EventLoop},
+    window::{Icon, Window, WindowAttributes, WindowId},
+};
+
+// Window changes asked for from inside a hook. App can't touch its window map while a hook is
+// holding one of the windows so they get queued and App applies them once the hook returns.
+pub(crate) enum WindowRequest {
+    Open(Arc<Window>),
+    Close(WindowId),
+}
+
+// What the user hooks get handed. It is just the window state plus the event loop so a hook can
+// reach anything the library can (gpu, camera, the window itself) without App being forked.
+// With more than one window open `state` is the window the event / frame belongs to.
+pub struct Context<'a> {
+    pub state: &'a mut WindowState,
+    event_loop: &'a ActiveEventLoop,
+    requests: &'a mut Vec<WindowRequest>,
+}
+
+impl<'a> Context<'a> {
+    pub(crate) fn new(
+        state: &'a mut WindowState,
+        event_loop: &'a ActiveEventLoop,
+        requests: &'a mut Vec<WindowRequest>,
+    ) -> Self {
+        Self { state, event_loop, requests }
+    }
+
+    pub fn window(&self) -> &Arc<Window> {
+        &self.state.window
+    }
+
+    pub fn window_id(&self) -> WindowId {
+        self.state.window.id()
+    }
+
+    // Opens another window on the same GPUDevice. The winit window exists right away so its id can be kept
+    // around (to tell the inspector apart from the preview etc.), its surface is set up once the hook returns.
+    pub fn create_window(&mut self, attributes: WindowAttributes) -> Result<WindowId, OsError> {
+        let window = Arc::new(self.event_loop.create_window(attributes)?);
+        let id = window.id();
+        self.requests.push(WindowRequest::Open(window));
+        Ok(id)
+    }
+
+    // The app exits when the last window is closed
+    pub fn close_window(&mut self, id: WindowId) {
+        self.requests.push(WindowRequest::Close(id));
+    }
+
+    pub fn gpu(&self) -> &GPUDevice {
+        self.state.gpu()
+    }
+
+    pub fn camera(&mut self) -> &mut CameraMatrix {
+        &mut self.state.camera
+    }
+
+    pub fn exit(&self) {
+        self.event_loop.exit();
+    }
+}
+
+// on_init only runs for the first window, the other hooks run for every window
+pub type InitHook = Box<dyn FnMut(&mut Context)>;
+// dt is the time since the last frame in seconds
+pub type UpdateHook = Box<dyn FnMut(&mut Context, f32)>;
+// return true if the event was handled, then App skips its own handling of it
+pub type EventHook = Box<dyn FnMut(&mut Context, &WindowEvent) -> bool>;
+// runs after the scene has been recorded, anything recorded here is drawn on top of it
+pub type RenderHook = Box<dyn FnMut(&GPUDevice, &mut wgpu::CommandEncoder, &wgpu::TextureView)>;
+
+#[derive(Default)]
+pub struct Hooks {
+    pub on_init: Option<InitHook>,
+    pub on_update: Option<UpdateHook>,
+    pub on_event: Option<EventHook>,
+    pub on_render: Option<RenderHook>,
+}
+
+// Sets up the window and the user hooks, then run() starts the event loop.
+//
+// AppBuilder::new()
+//     .title("Inspector")
+//     .size(1280, 720)
+//     .on_update(|ctx, dt| { ... })
+//     .run()
+pub struct AppBuilder {
+    attributes: WindowAttributes,
+    settings: RenderSettings,
+    hooks: Hooks,
+}
+
+impl Default for AppBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AppBuilder {
+    pub fn new() -> Self {
+        Self {
+            attributes: Window::default_attributes(),
+            settings: RenderSettings::default(),
+            hooks: Hooks::default(),
+        }
+    }
+
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.attributes = self.attributes.with_title(title);
+        self
+    }
+
+    // sizes are logical pixels, the window gets scaled by the monitor's scale factor
+    pub fn size(mut self, width: u32, height: u32) -> Self {
+        self.attributes = self.attributes.with_inner_size(LogicalSize::new(width, height));
+        self
+    }
+
+    pub fn min_size(mut self, width: u32, height: u32) -> Self {
+        self.attributes = self.attributes.with_min_inner_size(LogicalSize::new(width, height));
+        self
+    }
+
+    pub fn max_size(mut self, width: u32, height: u32) -> Self {
+        self.attributes = self.attributes.with_max_inner_size(LogicalSize::new(width, height));
+        self
+    }
+
+    pub fn resizable(mut self, resizable: bool) -> Self {
+        self.attributes = self.attributes.with_resizable(resizable);
+        self
+    }
+
+    pub fn decorations(mut self, decorations: bool) -> Self {
+        self.attributes = self.attributes.with_decorations(decorations);
+        self
+    }
+
+    pub fn transparent(mut self, transparent: bool) -> Self {
+        self.attributes = self.attributes.with_transparent(transparent);
+        self
+    }
+
+    pub fn icon(mut self, icon: Icon) -> Self {
+        self.attributes = self.attributes.with_window_icon(Some(icon));
+        self
+    }
+
+    // for anything winit supports that the builder doesn't have a method for
+    pub fn window_attributes(mut self, attributes: WindowAttributes) -> Self {
+        self.attributes = attributes;
+        self
+    }
+
+    // None turns the depth buffer off, Depth24PlusStencil8 gives a stencil as well
+    pub fn depth_format(mut self, format: Option<wgpu::TextureFormat>) -> Self {
+        self.settings.depth_format = format;
+        self
+    }
+
+    // MSAA, 1 / 2 / 4 / 8. Lowered to what the adapter supports when the window is made.
+    pub fn sample_count(mut self, sample_count: u32) -> Self {
+        self.settings.sample_count = sample_count;
+        self
+    }
+
+    pub fn render_settings(mut self, settings: RenderSettings) -> Self {
+        self.settings = settings;
+        self
+    }
+
+    pub fn on_init(mut self, hook: impl FnMut(&mut Context) + 'static) -> Self {
+        self.hooks.on_init = Some(Box::new(hook));
+        self
+    }
+
+    pub fn on_update(mut self, hook: impl FnMut(&mut Context, f32) + 'static) -> Self {
+        self.hooks.on_update = Some(Box::new(hook));
+        self
+    }
+
+    pub fn on_event(mut self, hook: impl FnMut(&mut Context, &WindowEvent) -> bool + 'static) -> Self {
+        self.hooks.on_event = Some(Box::new(hook));
+        self
+    }
+
+    pub fn on_render(
+        mut self,
+        hook: impl FnMut(&GPUDevice, &mut wgpu::CommandEncoder, &wgpu::TextureView) + 'static,
+    ) -> Self {
+        self.hooks.on_render = Some(Box::new(hook));
+        self
+    }
+
+    pub fn build(self) -> App {
+        App::with_config(self.attributes, self.settings, self.hooks)
+    }
+
+    pub fn run(self) -> Result<(), Box<dyn std::error::Error>> {
+        // try_init so run() can be called more than once / after the user set up their own logger
+        let _ = env_logger::try_init();
+
+        let event_loop = EventLoop::with_user_event().build()?;
+        let mut app = self.build();
+
+        event_loop.run_app(&mut app)?;
+
+        Ok(())
+    }
+}
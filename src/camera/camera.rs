@@ -1,9 +1,9 @@
-use nalgebra::Matrix4;
+use nalgebra::{Matrix4, Point3, Vector3};
 use nalgebra::geometry::Perspective3;
-use winit::keyboard::KeyCode;
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
 
 #[repr(C)]
-#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+#[derive(Clone, Copy, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct CameraUniform {
     pub cam_mat: [[f32; 4]; 4],
     pub clip_space: [[f32; 4]; 4], // mat4x4<f32>
@@ -34,14 +34,28 @@ impl From<CameraMatrix> for CameraUniform {
     }
 }
 
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Projection {
+    // the 3D camera, aspect ratio comes from the window
+    Perspective,
+    // 1 unit = 1 logical pixel, (0, 0) is the top left corner of the window and y goes down.
+    // scale_factor is the window's DPI scale so a 100px wide widget stays 100 logical px on a 2x screen.
+    Pixels { scale_factor: f32 },
+}
+
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
 pub struct CameraMatrix {
     pub cam: Matrix4<f32>,
     pub projection: Matrix4<f32>,
+    pub mode: Projection,
 }
 
 impl CameraMatrix {
+    pub const FOVY: f32 = std::f32::consts::FRAC_PI_4;
+    pub const ZNEAR: f32 = 0.1;
+    pub const ZFAR: f32 = 100.0;
+
     pub fn new(aspect_ratio: f32) -> Self {
         // view matrix (camera)
         let eye: nalgebra::OPoint<f32, nalgebra::Const<3>> = nalgebra::Point3::new(0.0, 0.0, 5.0); // Camera is 5 units back
@@ -51,28 +65,107 @@ impl CameraMatrix {
         
         // Create projection matrix
         let projection = Perspective3::new(
-            aspect_ratio, 
-            std::f32::consts::FRAC_PI_4, 
-            0.1, 
-            100.0
+            aspect_ratio,
+            Self::FOVY,
+            Self::ZNEAR,
+            Self::ZFAR
         ).to_homogeneous();
-        
+
         // Combine them: projection * view
         //let view_proj = projection * cam_matrix;
         Self {
             cam,
             projection,
+            mode: Projection::Perspective,
         }
     }
 
-    pub fn handle_key_press(&self, key: KeyCode) {
-        // ENTER KEY 
-        match key {
-            KeyCode::KeyW => {  },
-            _=> {}
+    // 2D camera for UI, width and height are the physical size of the window (what the surface is configured with)
+    pub fn pixel_space(width: u32, height: u32, scale_factor: f32) -> Self {
+        Self {
+            cam: Matrix4::identity(),
+            projection: pixel_projection(width, height, scale_factor),
+            mode: Projection::Pixels { scale_factor },
+        }
+    }
+
+    // rebuilds the view matrix, the camera controllers call this every frame
+    pub fn set_view(&mut self, eye: &Point3<f32>, target: &Point3<f32>, up: &Vector3<f32>) {
+        self.cam = Matrix4::look_at_rh(eye, target, up);
+    }
+
+    // the projection has to follow the window size or everything gets stretched on resize
+    pub fn set_aspect_ratio(&mut self, aspect_ratio: f32) {
+        self.projection = Perspective3::new(aspect_ratio, Self::FOVY, Self::ZNEAR, Self::ZFAR).to_homogeneous();
+    }
+
+    // Rebuilds the projection for whichever mode the camera is in. scale_factor only matters for Pixels
+    // and gets stored so it sticks when the window moves to a monitor with a different DPI.
+    pub fn resize(&mut self, width: u32, height: u32, scale_factor: f32) {
+        match self.mode {
+            Projection::Perspective => self.set_aspect_ratio(aspect_ratio(width, height)),
+            Projection::Pixels { .. } => {
+                self.projection = pixel_projection(width, height, scale_factor);
+                self.mode = Projection::Pixels { scale_factor };
+            }
         }
+    }
+}
 
+// Orthographic matrix from logical pixels to clip space.
+//   x: 0 .. width  ->  -1 .. 1
+//   y: 0 .. height ->   1 .. -1   (flipped so y goes down the screen)
+//   z: 1000 .. -1000 -> 0 .. 1    (bigger z is in front, z = 0 is the middle of the depth range)
+fn pixel_projection(width: u32, height: u32, scale_factor: f32) -> Matrix4<f32> {
+    let scale_factor = if scale_factor > 0.0 { scale_factor } else { 1.0 };
+    let logical_width = (width.max(1) as f32) / scale_factor;
+    let logical_height = (height.max(1) as f32) / scale_factor;
+
+    Matrix4::new(
+        2.0 / logical_width, 0.0, 0.0, -1.0,
+        0.0, -2.0 / logical_height, 0.0, 1.0,
+        0.0, 0.0, -1.0 / 2000.0, 0.5,
+        0.0, 0.0, 0.0, 1.0,
+    )
+}
+
+// width / height as a float, a zero sized (minimized) window gets 1.0 so the projection doesn't blow up
+pub fn aspect_ratio(width: u32, height: u32) -> f32 {
+    if width == 0 || height == 0 {
+        1.0
+    } else {
+        width as f32 / height as f32
+    }
+}
 
+// The GPU side of the camera. The buffer is COPY_DST so it can be rewritten with queue.write_buffer
+// whenever the CameraMatrix changes instead of being baked in once when the pipeline is made.
+pub struct CameraBuffer {
+    pub buffer: wgpu::Buffer,
+    uploaded: CameraUniform,
+}
+
+impl CameraBuffer {
+    pub fn new(device: &wgpu::Device, camera: &CameraMatrix) -> Self {
+        let uploaded = CameraUniform::from(*camera);
+        let buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("camera"),
+            contents: bytemuck::bytes_of(&uploaded),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        Self { buffer, uploaded }
+    }
+
+    // Only writes when the matrices are different from what the GPU already has. Returns true if it wrote.
+    pub fn update(&mut self, queue: &wgpu::Queue, camera: &CameraMatrix) -> bool {
+        let uniform = CameraUniform::from(*camera);
+        if uniform == self.uploaded {
+            return false;
+        }
 
+        queue.write_buffer(&self.buffer, 0, bytemuck::bytes_of(&uniform));
+        self.uploaded = uniform;
+        true
     }
 }
\ No newline at end of file
@@ -0,0 +1,400 @@
+use nalgebra::{Matrix4, Point3, Vector3};
+use winit::event::MouseButton;
+use winit::keyboard::KeyCode;
+use crate::camera::camera::CameraMatrix;
+
+// A controller owns the camera's position between frames. Input gets fed in as it arrives and
+// update() rebuilds the view matrix from that state once per frame, so movement follows dt and not
+// how many key repeat events the OS sends.
+pub trait CameraController {
+    // returns true if the key is one of the controller's bindings
+    fn process_key(&mut self, key: KeyCode, pressed: bool) -> bool;
+    fn process_mouse_button(&mut self, _button: MouseButton, _pressed: bool) {}
+    // cursor movement in physical pixels since the last call
+    fn process_mouse_motion(&mut self, _dx: f64, _dy: f64) {}
+    // scroll wheel, positive is away from the user (zoom in)
+    fn process_scroll(&mut self, _delta: f32) {}
+    // Only touches the camera when there was input to act on and returns whether it did, so a view
+    // set from code (camera().set_view in a hook) stays put until the user actually moves the camera.
+    fn update(&mut self, camera: &mut CameraMatrix, dt: f32) -> bool;
+    // Picks up where the camera is looking now. update() calls it by itself when the view isn't the one
+    // it set last, so the first input after a set_view moves on from that view instead of jumping back.
+    fn sync_from(&mut self, _camera: &CameraMatrix) {}
+}
+
+// eye and normalized forward of a view matrix, None if it can't be inverted
+fn eye_and_forward(camera: &CameraMatrix) -> Option<(Point3<f32>, Vector3<f32>)> {
+    let world = camera.cam.try_inverse()?;
+    let eye = world.transform_point(&Point3::origin());
+    let forward = world.transform_vector(&-Vector3::z()).try_normalize(f32::EPSILON)?;
+    Some((eye, forward))
+}
+
+// stop just short of straight up/down, look_at breaks when the view lines up with the up vector
+const PITCH_LIMIT: f32 = std::f32::consts::FRAC_PI_2 - 0.01;
+
+#[derive(Debug, Clone, Copy)]
+pub struct OrbitBindings {
+    pub rotate_button: MouseButton,
+    pub pan_button: MouseButton,
+    pub rotate_left: KeyCode,
+    pub rotate_right: KeyCode,
+    pub rotate_up: KeyCode,
+    pub rotate_down: KeyCode,
+    pub zoom_in: KeyCode,
+    pub zoom_out: KeyCode,
+}
+
+impl Default for OrbitBindings {
+    fn default() -> Self {
+        Self {
+            rotate_button: MouseButton::Left,
+            pan_button: MouseButton::Right,
+            rotate_left: KeyCode::ArrowLeft,
+            rotate_right: KeyCode::ArrowRight,
+            rotate_up: KeyCode::ArrowUp,
+            rotate_down: KeyCode::ArrowDown,
+            zoom_in: KeyCode::Equal,
+            zoom_out: KeyCode::Minus,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct OrbitSettings {
+    // radians per pixel of mouse drag
+    pub rotate_sensitivity: f32,
+    // world units per pixel of drag, scaled by the distance so panning feels the same zoomed in or out
+    pub pan_sensitivity: f32,
+    // fraction of the distance per scroll step
+    pub zoom_speed: f32,
+    // radians per second while an arrow key is held
+    pub key_rotate_speed: f32,
+    // scroll steps per second while a zoom key is held
+    pub key_zoom_speed: f32,
+    pub min_distance: f32,
+    pub max_distance: f32,
+    pub bindings: OrbitBindings,
+}
+
+impl Default for OrbitSettings {
+    fn default() -> Self {
+        Self {
+            rotate_sensitivity: 0.01,
+            pan_sensitivity: 0.002,
+            zoom_speed: 0.1,
+            key_rotate_speed: 1.5,
+            key_zoom_speed: 10.0,
+            min_distance: 0.5,
+            max_distance: 50.0,
+            bindings: OrbitBindings::default(),
+        }
+    }
+}
+
+// Arcball style camera that circles a target point. Drag to rotate, drag with the pan button to
+// move the target, scroll to zoom.
+#[derive(Debug, Clone)]
+pub struct OrbitController {
+    pub target: Point3<f32>,
+    pub distance: f32,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub settings: OrbitSettings,
+    rotating: bool,
+    panning: bool,
+    // (left, right, up, down, zoom in, zoom out)
+    keys: [bool; 6],
+    pending_rotate: (f32, f32),
+    pending_pan: (f32, f32),
+    pending_zoom: f32,
+    // the view update() set last, the camera having any other one means code moved it
+    view: Option<Matrix4<f32>>,
+}
+
+impl Default for OrbitController {
+    fn default() -> Self {
+        // same spot CameraMatrix::new puts the camera, 5 units back looking at the origin
+        Self::new(Point3::origin(), 5.0, OrbitSettings::default())
+    }
+}
+
+impl OrbitController {
+    pub fn new(target: Point3<f32>, distance: f32, settings: OrbitSettings) -> Self {
+        Self {
+            target,
+            distance,
+            yaw: 0.0,
+            pitch: 0.0,
+            settings,
+            rotating: false,
+            panning: false,
+            keys: [false; 6],
+            pending_rotate: (0.0, 0.0),
+            pending_pan: (0.0, 0.0),
+            pending_zoom: 0.0,
+            view: None,
+        }
+    }
+
+    pub fn eye(&self) -> Point3<f32> {
+        let offset = Vector3::new(
+            self.pitch.cos() * self.yaw.sin(),
+            self.pitch.sin(),
+            self.pitch.cos() * self.yaw.cos(),
+        );
+        self.target + offset * self.distance
+    }
+}
+
+impl CameraController for OrbitController {
+    fn process_key(&mut self, key: KeyCode, pressed: bool) -> bool {
+        let b = &self.settings.bindings;
+        let slot = match key {
+            k if k == b.rotate_left => 0,
+            k if k == b.rotate_right => 1,
+            k if k == b.rotate_up => 2,
+            k if k == b.rotate_down => 3,
+            k if k == b.zoom_in => 4,
+            k if k == b.zoom_out => 5,
+            _ => return false,
+        };
+        self.keys[slot] = pressed;
+        true
+    }
+
+    fn process_mouse_button(&mut self, button: MouseButton, pressed: bool) {
+        if button == self.settings.bindings.rotate_button {
+            self.rotating = pressed;
+        }
+        if button == self.settings.bindings.pan_button {
+            self.panning = pressed;
+        }
+    }
+
+    fn process_mouse_motion(&mut self, dx: f64, dy: f64) {
+        if self.rotating {
+            self.pending_rotate.0 += dx as f32;
+            self.pending_rotate.1 += dy as f32;
+        } else if self.panning {
+            self.pending_pan.0 += dx as f32;
+            self.pending_pan.1 += dy as f32;
+        }
+    }
+
+    fn process_scroll(&mut self, delta: f32) {
+        self.pending_zoom += delta;
+    }
+
+    fn update(&mut self, camera: &mut CameraMatrix, dt: f32) -> bool {
+        let idle = !self.keys.contains(&true)
+            && self.pending_rotate == (0.0, 0.0)
+            && self.pending_pan == (0.0, 0.0)
+            && self.pending_zoom == 0.0;
+        if idle {
+            return false;
+        }
+        if self.view != Some(camera.cam) {
+            self.sync_from(camera);
+        }
+        let s = self.settings;
+
+        // mouse rotation, dragging right spins the camera left around the target like grabbing the scene
+        let key_yaw = (self.keys[1] as i32 - self.keys[0] as i32) as f32 * s.key_rotate_speed * dt;
+        let key_pitch = (self.keys[2] as i32 - self.keys[3] as i32) as f32 * s.key_rotate_speed * dt;
+        self.yaw += key_yaw - self.pending_rotate.0 * s.rotate_sensitivity;
+        self.pitch += key_pitch + self.pending_rotate.1 * s.rotate_sensitivity;
+        self.pitch = self.pitch.clamp(-PITCH_LIMIT, PITCH_LIMIT);
+
+        // pan along the camera's right and up vectors
+        let forward = (self.target - self.eye()).normalize();
+        let right = forward.cross(&Vector3::y()).normalize();
+        let up = right.cross(&forward);
+        let pan_scale = s.pan_sensitivity * self.distance;
+        self.target += (-right * self.pending_pan.0 + up * self.pending_pan.1) * pan_scale;
+
+        let key_zoom = (self.keys[4] as i32 - self.keys[5] as i32) as f32 * s.key_zoom_speed * dt;
+        let zoom = self.pending_zoom + key_zoom;
+        self.distance = (self.distance * (1.0 - zoom * s.zoom_speed)).clamp(s.min_distance, s.max_distance);
+
+        self.pending_rotate = (0.0, 0.0);
+        self.pending_pan = (0.0, 0.0);
+        self.pending_zoom = 0.0;
+
+        camera.set_view(&self.eye(), &self.target, &Vector3::y());
+        self.view = Some(camera.cam);
+        true
+    }
+
+    // The view doesn't say how far away the target is, the distance stays and the target goes that far
+    // in front of the camera
+    fn sync_from(&mut self, camera: &CameraMatrix) {
+        let Some((eye, forward)) = eye_and_forward(camera) else {
+            return;
+        };
+        self.target = eye + forward * self.distance;
+        self.yaw = (-forward.x).atan2(-forward.z);
+        self.pitch = (-forward.y).asin().clamp(-PITCH_LIMIT, PITCH_LIMIT);
+        self.view = Some(camera.cam);
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct FlyBindings {
+    pub forward: KeyCode,
+    pub backward: KeyCode,
+    pub left: KeyCode,
+    pub right: KeyCode,
+    pub up: KeyCode,
+    pub down: KeyCode,
+    // mouse look only happens while this is held, None means always
+    pub look_button: Option<MouseButton>,
+}
+
+impl Default for FlyBindings {
+    fn default() -> Self {
+        Self {
+            forward: KeyCode::KeyW,
+            backward: KeyCode::KeyS,
+            left: KeyCode::KeyA,
+            right: KeyCode::KeyD,
+            up: KeyCode::Space,
+            down: KeyCode::ShiftLeft,
+            look_button: Some(MouseButton::Right),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct FlySettings {
+    // world units per second
+    pub speed: f32,
+    // radians per pixel of mouse movement
+    pub sensitivity: f32,
+    pub bindings: FlyBindings,
+}
+
+impl Default for FlySettings {
+    fn default() -> Self {
+        Self {
+            speed: 3.0,
+            sensitivity: 0.003,
+            bindings: FlyBindings::default(),
+        }
+    }
+}
+
+// First person camera, WASD to move and the mouse to look around
+#[derive(Debug, Clone)]
+pub struct FlyController {
+    pub position: Point3<f32>,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub settings: FlySettings,
+    looking: bool,
+    // (forward, backward, left, right, up, down)
+    keys: [bool; 6],
+    pending_look: (f32, f32),
+    // see OrbitController::view
+    view: Option<Matrix4<f32>>,
+}
+
+impl Default for FlyController {
+    fn default() -> Self {
+        // yaw of -PI/2 looks down -Z which is where CameraMatrix::new looks from (0, 0, 5)
+        Self::new(Point3::new(0.0, 0.0, 5.0), -std::f32::consts::FRAC_PI_2, 0.0, FlySettings::default())
+    }
+}
+
+impl FlyController {
+    pub fn new(position: Point3<f32>, yaw: f32, pitch: f32, settings: FlySettings) -> Self {
+        Self {
+            position,
+            yaw,
+            pitch,
+            settings,
+            looking: settings.bindings.look_button.is_none(),
+            keys: [false; 6],
+            pending_look: (0.0, 0.0),
+            view: None,
+        }
+    }
+
+    pub fn forward(&self) -> Vector3<f32> {
+        Vector3::new(
+            self.pitch.cos() * self.yaw.cos(),
+            self.pitch.sin(),
+            self.pitch.cos() * self.yaw.sin(),
+        )
+    }
+}
+
+impl CameraController for FlyController {
+    fn process_key(&mut self, key: KeyCode, pressed: bool) -> bool {
+        let b = &self.settings.bindings;
+        let slot = match key {
+            k if k == b.forward => 0,
+            k if k == b.backward => 1,
+            k if k == b.left => 2,
+            k if k == b.right => 3,
+            k if k == b.up => 4,
+            k if k == b.down => 5,
+            _ => return false,
+        };
+        self.keys[slot] = pressed;
+        true
+    }
+
+    fn process_mouse_button(&mut self, button: MouseButton, pressed: bool) {
+        if self.settings.bindings.look_button == Some(button) {
+            self.looking = pressed;
+        }
+    }
+
+    fn process_mouse_motion(&mut self, dx: f64, dy: f64) {
+        if self.looking {
+            self.pending_look.0 += dx as f32;
+            self.pending_look.1 += dy as f32;
+        }
+    }
+
+    fn update(&mut self, camera: &mut CameraMatrix, dt: f32) -> bool {
+        if !self.keys.contains(&true) && self.pending_look == (0.0, 0.0) {
+            return false;
+        }
+        if self.view != Some(camera.cam) {
+            self.sync_from(camera);
+        }
+        let s = self.settings;
+
+        self.yaw += self.pending_look.0 * s.sensitivity;
+        self.pitch -= self.pending_look.1 * s.sensitivity;
+        self.pitch = self.pitch.clamp(-PITCH_LIMIT, PITCH_LIMIT);
+        self.pending_look = (0.0, 0.0);
+
+        // WASD moves on the ground plane so looking down doesn't slow you down, up/down is straight along Y
+        let forward = Vector3::new(self.yaw.cos(), 0.0, self.yaw.sin());
+        let right = forward.cross(&Vector3::y()).normalize();
+        let axis = |pos: usize, neg: usize| (self.keys[pos] as i32 - self.keys[neg] as i32) as f32;
+
+        let velocity = forward * axis(0, 1) + right * axis(3, 2) + Vector3::y() * axis(4, 5);
+        if velocity.norm_squared() > 0.0 {
+            self.position += velocity.normalize() * s.speed * dt;
+        }
+
+        let target = self.position + self.forward();
+        camera.set_view(&self.position, &target, &Vector3::y());
+        self.view = Some(camera.cam);
+        true
+    }
+
+    fn sync_from(&mut self, camera: &CameraMatrix) {
+        let Some((eye, forward)) = eye_and_forward(camera) else {
+            return;
+        };
+        self.position = eye;
+        self.yaw = forward.z.atan2(forward.x);
+        self.pitch = forward.y.asin().clamp(-PITCH_LIMIT, PITCH_LIMIT);
+        self.view = Some(camera.cam);
+    }
+}
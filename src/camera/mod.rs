@@ -1,2 +1,3 @@
 
-pub mod camera;
\ No newline at end of file
+pub mod camera;
+pub mod controller;
@@ -1,7 +1,4 @@
 
-use nalgebra::{Matrix4, Point3, Translation, Translation3};
-
-
 // this is a vertex buffer so the shader is not hard coded and will not have to recompile everytime you want to change it.
 
 // you are creating a struct that has the position and color of the vertex. But the GPU has no idea what that means.
@@ -12,8 +9,8 @@ use nalgebra::{Matrix4, Point3, Translation, Translation3};
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Vertex {
-    position: [f32; 3],
-    color: [f32; 3],
+    pub position: [f32; 3],
+    pub color: [f32; 3],
 }
 
 impl Vertex {
@@ -39,45 +36,3 @@ impl Vertex {
         }
     }
 }
-
-#[repr(C)]
-#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable, Debug)]
-pub struct Cube {
-    pub vertices: [Vertex; 4],
-
-}
-impl Cube {
-    pub fn new() -> Self {
-        Self {
-            vertices: [
-                Vertex { position: [-0.5, -0.5, 0.0], color: [1.0, 0.0, 1.0] }, 
-                Vertex { position: [0.5, -0.5, 0.0], color: [0.0, 1.0, 1.0] }, 
-                Vertex { position: [0.5, 0.5, 0.0], color: [1.0, 1.0, 1.0] }, 
-                Vertex { position: [-0.5, 0.5, 0.0], color: [0.0, 0.0, 0.0] },
-            ],
-        }
-    }
-
-    pub fn move_cube(&self, translation: Point3<f32>) -> [[f32; 4]; 4] {
-        let translation = Translation3::new(translation.x, translation.y, translation.z).to_homogeneous();
-        let pod_friendly_mat: [[f32; 4]; 4] = translation.into();
-
-        pod_friendly_mat
-
-    }
-}
-
-// to draw triangles on each square face
-pub const INDICES: [u16; 12] = [
-       // Rectangle 1
-    0, 1, 2,
-    2, 3, 0,
-    // Rectangle 2 (offset by 4)
-    4, 5, 6,
-    6, 7, 4,
-];
-
-// should probably have some sort of erro handeling ot match the vertices with the indices
-
-
-
@@ -0,0 +1,159 @@
+use std::path::Path;
+use anyhow::{Context, Result, bail};
+use image::RgbaImage;
+use nalgebra::{Matrix4, Vector3};
+use crate::geometry::mesh::{MeshData, MeshVertex};
+use crate::geometry::model::{Material, Model, ModelMesh};
+
+// glTF 2.0, both .gltf with its .bin / image files next to it and .glb with everything inside. Only the
+// default scene is loaded (or the first one if there's no default) and only triangle primitives, anything
+// else is reported and skipped.
+pub fn load(path: &Path) -> Result<Model> {
+    let (document, buffers, images) = ::gltf::import(path).with_context(|| format!("couldn't load {}", path.display()))?;
+
+    let materials = document
+        .materials()
+        .map(|material| self::material(&material, &images))
+        .collect::<Result<Vec<_>>>()
+        .with_context(|| path.display().to_string())?;
+
+    let scene = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+        .with_context(|| format!("{}: there's no scene in the file", path.display()))?;
+
+    let mut meshes = Vec::new();
+    for node in scene.nodes() {
+        push_node(&node, Matrix4::identity(), &buffers, &mut meshes).with_context(|| path.display().to_string())?;
+    }
+
+    Ok(Model { meshes, materials })
+}
+
+// `parent` is the world transform of the node above, the root nodes get identity
+fn push_node(node: &::gltf::Node, parent: Matrix4<f32>, buffers: &[::gltf::buffer::Data], meshes: &mut Vec<ModelMesh>) -> Result<()> {
+    // glTF matrices are column major like nalgebra's
+    let transform = parent * Matrix4::from(node.transform().matrix());
+
+    if let Some(mesh) = node.mesh() {
+        let name = mesh.name().or(node.name()).map(str::to_string).unwrap_or_else(|| format!("mesh {}", mesh.index()));
+        for primitive in mesh.primitives() {
+            if primitive.mode() != ::gltf::mesh::Mode::Triangles {
+                eprintln!("{}: skipping a {:?} primitive, only triangles are loaded", name, primitive.mode());
+                continue;
+            }
+            meshes.push(ModelMesh {
+                data: mesh_data(&primitive, buffers).with_context(|| format!("mesh {}", name))?,
+                material: primitive.material().index(),
+                name: name.clone(),
+                transform,
+            });
+        }
+    }
+
+    for child in node.children() {
+        push_node(&child, transform, buffers, meshes)?;
+    }
+    Ok(())
+}
+
+fn mesh_data(primitive: &::gltf::Primitive, buffers: &[::gltf::buffer::Data]) -> Result<MeshData> {
+    let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|data| &data.0[..]));
+
+    let positions: Vec<[f32; 3]> = reader.read_positions().context("a primitive has no POSITION")?.collect();
+    let normals: Option<Vec<[f32; 3]>> = reader.read_normals().map(Iterator::collect);
+    // a vertex only has room for one uv, it's the set the base color texture is drawn with (TEXCOORD_n)
+    let set = primitive.material().pbr_metallic_roughness().base_color_texture().map_or(0, |info| info.tex_coord());
+    let uvs: Option<Vec<[f32; 2]>> = reader.read_tex_coords(set).map(|uvs| uvs.into_f32().collect());
+    if uvs.is_none() && set != 0 {
+        bail!("the base color texture uses TEXCOORD_{} but the primitive doesn't have it", set);
+    }
+    let colors: Option<Vec<[f32; 4]>> = reader.read_colors(0).map(|colors| colors.into_rgba_f32().collect());
+
+    let vertices = positions
+        .iter()
+        .enumerate()
+        .map(|(i, position)| {
+            let normal = normals.as_ref().and_then(|normals| normals.get(i)).copied().unwrap_or_default();
+            let uv = uvs.as_ref().and_then(|uvs| uvs.get(i)).copied().unwrap_or_default();
+            let vertex = MeshVertex::new(Vector3::from(*position), Vector3::from(normal), uv);
+            match colors.as_ref().and_then(|colors| colors.get(i)) {
+                Some(color) => vertex.with_color(*color),
+                None => vertex,
+            }
+        })
+        .collect();
+
+    // without indices every three vertices are a triangle
+    let indices = match reader.read_indices() {
+        Some(indices) => indices.into_u32().collect(),
+        None => (0..positions.len() as u32).collect(),
+    };
+
+    let mut data = MeshData::new(vertices, indices, wgpu::PrimitiveTopology::TriangleList)?;
+    if normals.is_none() {
+        data.compute_normals();
+    }
+    Ok(data)
+}
+
+// Which uv set the texture uses (info.tex_coord()) is picked up by mesh_data, each primitive reads that set
+fn material(material: &::gltf::Material, images: &[::gltf::image::Data]) -> Result<Material> {
+    let pbr = material.pbr_metallic_roughness();
+    let base_color_texture = match pbr.base_color_texture() {
+        Some(info) => {
+            let index = info.texture().source().index();
+            let data = images.get(index).with_context(|| format!("image {} is missing", index))?;
+            Some(rgba_image(data).with_context(|| format!("image {}", index))?)
+        }
+        None => None,
+    };
+
+    Ok(Material {
+        name: material.name().map(str::to_string).unwrap_or_default(),
+        base_color: pbr.base_color_factor(),
+        base_color_texture,
+    })
+}
+
+// gltf decodes images into whatever layout the file had, the texture loader only takes rgba8
+fn rgba_image(data: &::gltf::image::Data) -> Result<RgbaImage> {
+    use ::gltf::image::Format;
+
+    // (channels, bytes per channel)
+    let (channels, size) = match data.format {
+        Format::R8 => (1, 1),
+        Format::R8G8 => (2, 1),
+        Format::R8G8B8 => (3, 1),
+        Format::R8G8B8A8 => (4, 1),
+        Format::R16 => (1, 2),
+        Format::R16G16 => (2, 2),
+        Format::R16G16B16 => (3, 2),
+        Format::R16G16B16A16 => (4, 2),
+        Format::R32G32B32FLOAT => (3, 4),
+        Format::R32G32B32A32FLOAT => (4, 4),
+    };
+    let channel = |bytes: &[u8]| -> u8 {
+        match size {
+            1 => bytes[0],
+            2 => (u16::from_ne_bytes([bytes[0], bytes[1]]) >> 8) as u8,
+            _ => (f32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]).clamp(0.0, 1.0) * 255.0).round() as u8,
+        }
+    };
+
+    let mut pixels = Vec::with_capacity((data.width * data.height * 4) as usize);
+    for pixel in data.pixels.chunks_exact(channels * size) {
+        let value = |c: usize| channel(&pixel[c * size..(c + 1) * size]);
+        pixels.extend(match channels {
+            1 => [value(0), value(0), value(0), 255],
+            2 => [value(0), value(0), value(0), value(1)],
+            3 => [value(0), value(1), value(2), 255],
+            _ => [value(0), value(1), value(2), value(3)],
+        });
+    }
+
+    match RgbaImage::from_raw(data.width, data.height, pixels) {
+        Some(image) => Ok(image),
+        None => bail!("{}x{} {:?} image has the wrong number of bytes", data.width, data.height, data.format),
+    }
+}
@@ -0,0 +1,312 @@
+use std::f32::consts::{PI, TAU};
+use std::ops::Range;
+use anyhow::{Result, bail};
+use nalgebra::Vector3;
+use crate::shaders::buffers::BufferTypes;
+
+// The vertex every generated or loaded mesh uses
+// |    12 bytes    |    12 bytes     |  8 bytes    |     16 bytes     |
+// | [f32; 3] pos   | [f32; 3] normal | [f32; 2] uv | [f32; 4] color   |
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct MeshVertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub uv: [f32; 2],
+    // linear rgba, white for the generators. Models bring their own (glTF COLOR_0, OBJ vertex colors).
+    pub color: [f32; 4],
+}
+
+impl MeshVertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 4] = wgpu::vertex_attr_array![
+        0 => Float32x3,
+        1 => Float32x3,
+        2 => Float32x2,
+        3 => Float32x4,
+    ];
+
+    pub fn new(position: Vector3<f32>, normal: Vector3<f32>, uv: [f32; 2]) -> Self {
+        Self {
+            position: position.into(),
+            normal: normal.into(),
+            uv,
+            color: [1.0, 1.0, 1.0, 1.0],
+        }
+    }
+
+    pub fn with_color(mut self, color: [f32; 4]) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<MeshVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+// Vertices and indices on the CPU side. Triangles wind counter clockwise seen from the outside, y is up,
+// and every generator centers the shape on the origin.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MeshData {
+    pub vertices: Vec<MeshVertex>,
+    pub indices: Vec<u32>,
+    // TriangleList for everything except grid() which is LineList
+    pub topology: wgpu::PrimitiveTopology,
+}
+
+impl MeshData {
+    pub fn new(vertices: Vec<MeshVertex>, indices: Vec<u32>, topology: wgpu::PrimitiveTopology) -> Result<Self> {
+        let data = Self {
+            vertices,
+            indices,
+            topology,
+        };
+        data.validate()?;
+        Ok(data)
+    }
+
+    // Every index has to point at a vertex and lists have to be made of whole primitives. A bad index
+    // doesn't fail on the GPU, it just reads zeros (or garbage) and draws a broken shape.
+    pub fn validate(&self) -> Result<()> {
+        let per_primitive = match self.topology {
+            wgpu::PrimitiveTopology::TriangleList => 3,
+            wgpu::PrimitiveTopology::LineList => 2,
+            _ => 1,
+        };
+        if !self.indices.len().is_multiple_of(per_primitive) {
+            bail!(
+                "{} indices isn't a whole number of {:?} primitives ({} each)",
+                self.indices.len(),
+                self.topology,
+                per_primitive
+            );
+        }
+        if let Some((position, index)) = self
+            .indices
+            .iter()
+            .enumerate()
+            .find(|(_, index)| **index as usize >= self.vertices.len())
+        {
+            bail!("index {} (at {}) is out of range, there are {} vertices", index, position, self.vertices.len());
+        }
+        Ok(())
+    }
+
+    // Smooth normals for models that come without them. Each triangle adds its unnormalized face normal to
+    // its three vertices so big triangles count for more than slivers. Vertices that aren't part of any
+    // triangle (or only squashed ones) point up.
+    pub fn compute_normals(&mut self) {
+        let mut normals = vec![Vector3::<f32>::zeros(); self.vertices.len()];
+        if self.topology == wgpu::PrimitiveTopology::TriangleList {
+            for triangle in self.indices.chunks_exact(3) {
+                let [a, b, c] = [0, 1, 2].map(|i| Vector3::from(self.vertices[triangle[i] as usize].position));
+                let face = (b - a).cross(&(c - a));
+                for &index in triangle {
+                    normals[index as usize] += face;
+                }
+            }
+        }
+        for (vertex, normal) in self.vertices.iter_mut().zip(normals) {
+            vertex.normal = normal.try_normalize(1e-12).unwrap_or_else(Vector3::y).into();
+        }
+    }
+
+    // 24 vertices so every face gets its own normals and a full 0..1 uv square
+    pub fn cube(size: f32) -> Self {
+        let half = size / 2.0;
+        let mut data = Self::default();
+        // (normal, u axis, v axis) with u x v = normal so the quads come out counter clockwise from outside
+        let faces = [
+            (Vector3::x(), -Vector3::z(), Vector3::y()),
+            (-Vector3::x(), Vector3::z(), Vector3::y()),
+            (Vector3::y(), Vector3::x(), -Vector3::z()),
+            (-Vector3::y(), Vector3::x(), Vector3::z()),
+            (Vector3::z(), Vector3::x(), Vector3::y()),
+            (-Vector3::z(), -Vector3::x(), Vector3::y()),
+        ];
+        for (normal, u, v) in faces {
+            let origin = (normal - u - v) * half;
+            data.push_grid(1, 1, |s, t| MeshVertex::new(origin + u * (s * size) + v * (t * size), normal, [s, 1.0 - t]));
+        }
+        data
+    }
+
+    // Flat on the xz plane facing up, `subdivisions` cells along each side
+    pub fn plane(width: f32, depth: f32, subdivisions: u32) -> Self {
+        let subdivisions = subdivisions.max(1);
+        let mut data = Self::default();
+        data.push_grid(subdivisions, subdivisions, |s, t| {
+            let position = Vector3::new((s - 0.5) * width, 0.0, (0.5 - t) * depth);
+            MeshVertex::new(position, Vector3::y(), [s, 1.0 - t])
+        });
+        data
+    }
+
+    // `sectors` around the y axis, `stacks` from the bottom pole to the top one
+    pub fn uv_sphere(radius: f32, sectors: u32, stacks: u32) -> Self {
+        let (sectors, stacks) = (sectors.max(3), stacks.max(2));
+        let mut data = Self::default();
+        data.push_grid(sectors, stacks, |s, t| {
+            let theta = s * TAU;
+            let phi = (1.0 - t) * PI;
+            let normal = Vector3::new(phi.sin() * theta.sin(), phi.cos(), phi.sin() * theta.cos());
+            MeshVertex::new(normal * radius, normal, [s, 1.0 - t])
+        });
+        data
+    }
+
+    // Along the y axis with caps on both ends
+    pub fn cylinder(radius: f32, height: f32, segments: u32) -> Self {
+        let segments = segments.max(3);
+        let half = height / 2.0;
+        let mut data = Self::default();
+        data.push_grid(segments, 1, |s, t| {
+            let theta = s * TAU;
+            let normal = Vector3::new(theta.sin(), 0.0, theta.cos());
+            MeshVertex::new(normal * radius + Vector3::y() * (t * height - half), normal, [s, 1.0 - t])
+        });
+        data.push_disc(half, radius, segments, true);
+        data.push_disc(-half, radius, segments, false);
+        data
+    }
+
+    // Point up along y, the base sits at -height / 2
+    pub fn cone(radius: f32, height: f32, segments: u32) -> Self {
+        let segments = segments.max(3);
+        let half = height / 2.0;
+        let mut data = Self::default();
+        data.push_grid(segments, 1, |s, t| {
+            let theta = s * TAU;
+            let around = Vector3::new(theta.sin(), 0.0, theta.cos());
+            // the side leans in by radius / height so the normal leans up by the same amount
+            let normal = (around * height + Vector3::y() * radius).normalize();
+            let position = around * (radius * (1.0 - t)) + Vector3::y() * (t * height - half);
+            MeshVertex::new(position, normal, [s, 1.0 - t])
+        });
+        data.push_disc(-half, radius, segments, false);
+        data
+    }
+
+    // Lying flat around the y axis. `radius` is to the middle of the tube, `tube_radius` is the tube's own.
+    pub fn torus(radius: f32, tube_radius: f32, segments: u32, tube_segments: u32) -> Self {
+        let (segments, tube_segments) = (segments.max(3), tube_segments.max(3));
+        let mut data = Self::default();
+        data.push_grid(segments, tube_segments, |s, t| {
+            let theta = s * TAU;
+            let phi = t * TAU;
+            let around = Vector3::new(theta.sin(), 0.0, theta.cos());
+            let normal = around * phi.cos() + Vector3::y() * phi.sin();
+            MeshVertex::new(around * radius + normal * tube_radius, normal, [s, t])
+        });
+        data
+    }
+
+    // Lines on the xz plane, `divisions` cells along each side. Meant for a LineList pipeline.
+    pub fn grid(size: f32, divisions: u32) -> Self {
+        let divisions = divisions.max(1);
+        let half = size / 2.0;
+        let mut data = Self {
+            topology: wgpu::PrimitiveTopology::LineList,
+            ..Self::default()
+        };
+        for i in 0..=divisions {
+            let s = i as f32 / divisions as f32;
+            let offset = s * size - half;
+            for (start, end) in [
+                (Vector3::new(offset, 0.0, -half), Vector3::new(offset, 0.0, half)),
+                (Vector3::new(-half, 0.0, offset), Vector3::new(half, 0.0, offset)),
+            ] {
+                let first = data.vertices.len() as u32;
+                let uv = |point: Vector3<f32>| [point.x / size + 0.5, point.z / size + 0.5];
+                data.vertices.push(MeshVertex::new(start, Vector3::y(), uv(start)));
+                data.vertices.push(MeshVertex::new(end, Vector3::y(), uv(end)));
+                data.indices.extend([first, first + 1]);
+            }
+        }
+        data
+    }
+
+    // (columns + 1) x (rows + 1) vertices from `vertex(s, t)` with s and t going 0..1, and two triangles per
+    // cell. The surface faces the side ds x dt points to.
+    fn push_grid(&mut self, columns: u32, rows: u32, vertex: impl Fn(f32, f32) -> MeshVertex) {
+        let first = self.vertices.len() as u32;
+        for row in 0..=rows {
+            for column in 0..=columns {
+                self.vertices.push(vertex(column as f32 / columns as f32, row as f32 / rows as f32));
+            }
+        }
+
+        let stride = columns + 1;
+        for row in 0..rows {
+            for column in 0..columns {
+                let a = first + row * stride + column;
+                let b = a + 1;
+                let c = b + stride;
+                let d = a + stride;
+                self.indices.extend([a, b, c, c, d, a]);
+            }
+        }
+    }
+
+    // a flat cap at height y, facing up or down
+    fn push_disc(&mut self, y: f32, radius: f32, segments: u32, up: bool) {
+        let normal = if up { Vector3::y() } else { -Vector3::y() };
+        let center = self.vertices.len() as u32;
+        self.vertices.push(MeshVertex::new(Vector3::y() * y, normal, [0.5, 0.5]));
+        for i in 0..=segments {
+            let theta = i as f32 / segments as f32 * TAU;
+            let (sin, cos) = theta.sin_cos();
+            let position = Vector3::new(sin * radius, y, cos * radius);
+            self.vertices.push(MeshVertex::new(position, normal, [0.5 + sin * 0.5, 0.5 - cos * 0.5]));
+        }
+        for i in 0..segments {
+            let (current, next) = (center + 1 + i, center + 2 + i);
+            if up {
+                self.indices.extend([center, current, next]);
+            } else {
+                self.indices.extend([center, next, current]);
+            }
+        }
+    }
+}
+
+// MeshData and the GPU buffers made from it. The data is kept so it can be read back (bounds, picking)
+// without going to the GPU.
+pub struct Mesh {
+    pub data: MeshData,
+    pub vertex_buffer: wgpu::Buffer,
+    pub index_buffer: wgpu::Buffer,
+    pub num_indices: u32,
+}
+
+impl Mesh {
+    // fails if an index points past the vertices, see MeshData::validate
+    pub fn new(device: &wgpu::Device, label: &str, data: MeshData) -> Result<Self> {
+        data.validate()?;
+
+        let vertex_buffer = BufferTypes::VertexBuffer(&data.vertices).build(Some(label), device);
+        let index_buffer = BufferTypes::IndexBuffer(&data.indices).build(Some(label), device);
+
+        Ok(Self {
+            num_indices: data.indices.len() as u32,
+            data,
+            vertex_buffer,
+            index_buffer,
+        })
+    }
+
+    pub fn topology(&self) -> wgpu::PrimitiveTopology {
+        self.data.topology
+    }
+
+    // the pipeline has to be set already and made with MeshVertex::desc() and this mesh's topology
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass<'_>, instances: Range<u32>) {
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        render_pass.draw_indexed(0..self.num_indices, 0, instances);
+    }
+}
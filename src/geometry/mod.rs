@@ -1 +1,6 @@
-pub mod geometry;
\ No newline at end of file
+pub mod geometry;
+pub mod rect;
+pub mod mesh;
+pub mod model;
+pub mod obj;
+pub mod gltf;
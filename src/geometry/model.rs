@@ -0,0 +1,90 @@
+use std::path::Path;
+use anyhow::{Result, bail};
+use image::RgbaImage;
+use nalgebra::Matrix4;
+use crate::geometry::mesh::{Mesh, MeshData};
+use crate::render::instance::Instance;
+use crate::textures::loader::TextureOptions;
+use crate::textures::textures::ColorTexture;
+
+// What a surface looks like, only the parts the scene shader can use so far
+#[derive(Debug, Clone, PartialEq)]
+pub struct Material {
+    pub name: String,
+    // linear rgba, multiplies the texture and the vertex colors
+    pub base_color: [f32; 4],
+    // sRGB, already decoded. Textures in the file (.glb, data uris) and next to it end up the same.
+    pub base_color_texture: Option<RgbaImage>,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            base_color: [1.0, 1.0, 1.0, 1.0],
+            base_color_texture: None,
+        }
+    }
+}
+
+impl Material {
+    // None if the material has no texture
+    pub fn texture(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> Option<ColorTexture> {
+        let image = self.base_color_texture.as_ref()?;
+        let label = if self.name.is_empty() { "Material Texture" } else { &self.name };
+        Some(ColorTexture::from_image(device, queue, image, label, &TextureOptions::default().with_mipmaps()))
+    }
+}
+
+// One piece of a model with a single material. glTF meshes with several primitives become one of these
+// per primitive.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelMesh {
+    pub name: String,
+    pub data: MeshData,
+    // index into Model::materials, None for the default material
+    pub material: Option<usize>,
+    // where the node hierarchy puts it, parents already applied. Always identity for OBJ.
+    pub transform: Matrix4<f32>,
+}
+
+// Everything a model file had in it, still on the CPU. Load with Model::load and either hand it to
+// add_model (WindowState / HeadlessState) to get it drawn with its textures, or turn the parts into
+// Mesh / ColorTexture / Instance as needed.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Model {
+    pub meshes: Vec<ModelMesh>,
+    pub materials: Vec<Material>,
+}
+
+impl Model {
+    // .obj (with its .mtl), .gltf (with its .bin and images) or .glb, picked by the extension
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let extension = path.extension().and_then(|extension| extension.to_str()).unwrap_or("").to_ascii_lowercase();
+        match extension.as_str() {
+            "obj" => crate::geometry::obj::load(path),
+            "gltf" | "glb" => crate::geometry::gltf::load(path),
+            _ => bail!("{}: don't know how to load .{} models, use .obj, .gltf or .glb", path.display(), extension),
+        }
+    }
+
+    pub fn material(&self, mesh: &ModelMesh) -> Option<&Material> {
+        mesh.material.and_then(|index| self.materials.get(index))
+    }
+
+    // one Mesh per ModelMesh, in the same order
+    pub fn create_meshes(&self, device: &wgpu::Device) -> Result<Vec<Mesh>> {
+        self.meshes
+            .iter()
+            .map(|mesh| Mesh::new(device, &mesh.name, mesh.data.clone()))
+            .collect()
+    }
+
+    // An instance placed where the file put `mesh`, tinted with its material's base color. `model` is
+    // where the whole model goes.
+    pub fn instance(&self, mesh: &ModelMesh, model: Matrix4<f32>) -> Instance {
+        let color = self.material(mesh).map(|material| material.base_color).unwrap_or([1.0, 1.0, 1.0, 1.0]);
+        Instance::new(model * mesh.transform).with_color(color)
+    }
+}
@@ -0,0 +1,91 @@
+use std::path::Path;
+use anyhow::{Context, Result};
+use nalgebra::{Matrix4, Vector3};
+use crate::geometry::mesh::{MeshData, MeshVertex};
+use crate::geometry::model::{Material, Model, ModelMesh};
+
+// Wavefront OBJ through tobj. Faces get triangulated and every position / uv / normal combination becomes
+// its own vertex. The .mtl and its textures are looked up next to the .obj, a missing one is reported and
+// the model loads without it.
+pub fn load(path: &Path) -> Result<Model> {
+    let options = tobj::LoadOptions {
+        triangulate: true,
+        single_index: true,
+        ..Default::default()
+    };
+    let (models, materials) = tobj::load_obj(path, &options).with_context(|| format!("couldn't load {}", path.display()))?;
+    let directory = path.parent().unwrap_or(Path::new(""));
+
+    let materials = match materials {
+        Ok(materials) => materials.iter().map(|material| self::material(material, directory)).collect(),
+        Err(err) => {
+            eprintln!("{}: couldn't load the materials: {}", path.display(), err);
+            Vec::new()
+        }
+    };
+
+    let meshes = models
+        .into_iter()
+        .map(|model| {
+            Ok(ModelMesh {
+                data: mesh_data(&model.mesh).with_context(|| format!("{}: mesh {}", path.display(), model.name))?,
+                material: model.mesh.material_id.filter(|index| *index < materials.len()),
+                name: model.name,
+                transform: Matrix4::identity(),
+            })
+        })
+        .collect::<Result<_>>()?;
+
+    Ok(Model { meshes, materials })
+}
+
+fn mesh_data(mesh: &tobj::Mesh) -> Result<MeshData> {
+    let count = mesh.positions.len() / 3;
+    let has_normals = mesh.normals.len() == count * 3;
+    let vertices = (0..count)
+        .map(|i| {
+            let position = Vector3::from_column_slice(&mesh.positions[i * 3..i * 3 + 3]);
+            let normal = if has_normals {
+                Vector3::from_column_slice(&mesh.normals[i * 3..i * 3 + 3])
+            } else {
+                Vector3::zeros()
+            };
+            // OBJ puts v = 0 at the bottom of the image, wgpu at the top
+            let uv = match mesh.texcoords.get(i * 2..i * 2 + 2) {
+                Some(uv) => [uv[0], 1.0 - uv[1]],
+                None => [0.0, 0.0],
+            };
+            let vertex = MeshVertex::new(position, normal, uv);
+            match mesh.vertex_color.get(i * 3..i * 3 + 3) {
+                Some(color) => vertex.with_color([color[0], color[1], color[2], 1.0]),
+                None => vertex,
+            }
+        })
+        .collect();
+
+    let mut data = MeshData::new(vertices, mesh.indices.clone(), wgpu::PrimitiveTopology::TriangleList)?;
+    if !has_normals {
+        data.compute_normals();
+    }
+    Ok(data)
+}
+
+fn material(material: &tobj::Material, directory: &Path) -> Material {
+    let [r, g, b] = material.diffuse.unwrap_or([1.0, 1.0, 1.0]);
+    let base_color_texture = material.diffuse_texture.as_ref().and_then(|texture| {
+        let path = directory.join(texture);
+        match image::open(&path) {
+            Ok(image) => Some(image.to_rgba8()),
+            Err(err) => {
+                eprintln!("{}: couldn't load the texture of {}: {}", path.display(), material.name, err);
+                None
+            }
+        }
+    });
+
+    Material {
+        name: material.name.clone(),
+        base_color: [r, g, b, material.dissolve.unwrap_or(1.0)],
+        base_color_texture,
+    }
+}
@@ -1,6 +1,5 @@
 use anyhow::Result;
 use wgpu::Instance;
-use wgpu::TextureDescriptor;
 
 pub struct GPUDevice {
     pub instance: wgpu::Instance,
@@ -18,20 +17,64 @@ impl GPUDevice {
         let adapter = instance
             .request_adapter(&wgpu::RequestAdapterOptions {
                 power_preference: wgpu::PowerPreference::default(),
-                compatible_surface: Some(&surface),
+                compatible_surface: Some(surface),
                 force_fallback_adapter: false,
             })
             .await?;
-        
+
+        Self::from_adapter(instance, adapter).await
+    }
+
+    // Same as new() but without a surface, so nothing has to be on screen. This is what the tests and CI use.
+    // It asks for a real adapter first and if there is none (no GPU on the machine) it takes the software
+    // fallback adapter (lavapipe / llvmpipe / WARP) instead.
+    pub async fn new_headless(instance: Instance) -> Result<Self> {
+        let adapter = match instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::default(),
+                compatible_surface: None,
+                force_fallback_adapter: false,
+            })
+            .await
+        {
+            Ok(adapter) => adapter,
+            Err(_) => {
+                instance
+                    .request_adapter(&wgpu::RequestAdapterOptions {
+                        power_preference: wgpu::PowerPreference::LowPower,
+                        compatible_surface: None,
+                        force_fallback_adapter: true,
+                    })
+                    .await?
+            }
+        };
+
+        Self::from_adapter(instance, adapter).await
+    }
+
+    // The instance for headless rendering. Every backend is allowed (GL included) because on a machine with no GPU
+    // the only adapter might be a software GL driver. WGPU_BACKEND can still be used to narrow it down.
+    pub fn headless_instance() -> Instance {
+        wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all().with_env(),
+            ..Default::default()
+        })
+    }
+
+    async fn from_adapter(instance: Instance, adapter: wgpu::Adapter) -> Result<Self> {
         // Open connection to a graphics and/or compute device.
         // Responsible for the creation of most rendering and compute resources.
         // These are then used in commands, which are submitted to a [`Queue`].
         let (device, queue) = adapter
             .request_device(&wgpu::DeviceDescriptor {
                 label: None,
-                required_features: wgpu::Features::empty(),
+                // wireframe pipelines (PolygonMode::Line) need this, it's turned on whenever the adapter has it
+                required_features: adapter.features() & wgpu::Features::POLYGON_MODE_LINE,
+                // software adapters don't always reach the default limits so they get the downlevel ones
                 required_limits: if cfg!(target_arch = "wasm32") {
                     wgpu::Limits::downlevel_webgl2_defaults()
+                } else if !wgpu::Limits::default().check_limits(&adapter.limits()) {
+                    wgpu::Limits::downlevel_defaults().using_resolution(adapter.limits())
                 } else {
                     wgpu::Limits::default()
                 },
@@ -47,8 +90,4 @@ impl GPUDevice {
             adapter,
         })
     }
-
-
 }
-
-
@@ -0,0 +1,642 @@
+use crate::geometry::rect::{Rect, Size};
+use crate::layout::style::{Align, Dimension, Direction, Edges, Justify, Position, Style};
+
+// Whatever holds the nodes (the WidgetTree, or a plain Vec in the tests). The layout only needs
+// to read styles and children, ask leaves how big their content is, and hand back rectangles.
+pub trait LayoutTree {
+    type Id: Copy;
+
+    fn style(&self, id: Self::Id) -> Style;
+    fn children(&self, id: Self::Id) -> Vec<Self::Id>;
+    // content size of a node without flowing children (a widget's measure), `available` has the padding taken off already
+    fn measure(&mut self, id: Self::Id, available: Size) -> Size;
+    fn set_bounds(&mut self, id: Self::Id, bounds: Rect);
+}
+
+// Lays out everything under `root`. The root fills the viewport unless its style gives it a size.
+pub fn compute_layout<T: LayoutTree>(tree: &mut T, root: T::Id, viewport: Size) {
+    let style = tree.style(root);
+    let width = style.width.resolve(viewport.width).unwrap_or(viewport.width - style.margin.horizontal());
+    let height = style.height.resolve(viewport.height).unwrap_or(viewport.height - style.margin.vertical());
+    let bounds = Rect::new(
+        style.margin.left,
+        style.margin.top,
+        clamp(width, style.min_width, style.max_width),
+        clamp(height, style.min_height, style.max_height),
+    );
+    layout_node(tree, root, bounds);
+}
+
+// Main is the axis children are laid out along, cross goes across it
+impl Direction {
+    fn main(self, size: Size) -> f32 {
+        match self {
+            Direction::Row => size.width,
+            Direction::Column => size.height,
+        }
+    }
+
+    fn cross(self, size: Size) -> f32 {
+        match self {
+            Direction::Row => size.height,
+            Direction::Column => size.width,
+        }
+    }
+
+    fn size(self, main: f32, cross: f32) -> Size {
+        match self {
+            Direction::Row => Size::new(main, cross),
+            Direction::Column => Size::new(cross, main),
+        }
+    }
+
+    fn rect(self, main_pos: f32, cross_pos: f32, main: f32, cross: f32) -> Rect {
+        match self {
+            Direction::Row => Rect::new(main_pos, cross_pos, main, cross),
+            Direction::Column => Rect::new(cross_pos, main_pos, cross, main),
+        }
+    }
+
+    fn start(self, rect: Rect) -> (f32, f32) {
+        match self {
+            Direction::Row => (rect.x, rect.y),
+            Direction::Column => (rect.y, rect.x),
+        }
+    }
+
+    // (leading, trailing)
+    fn main_edges(self, edges: &Edges) -> (f32, f32) {
+        match self {
+            Direction::Row => (edges.left, edges.right),
+            Direction::Column => (edges.top, edges.bottom),
+        }
+    }
+
+    fn cross_edges(self, edges: &Edges) -> (f32, f32) {
+        match self {
+            Direction::Row => (edges.top, edges.bottom),
+            Direction::Column => (edges.left, edges.right),
+        }
+    }
+
+    fn main_dimension(self, style: &Style) -> Dimension {
+        match self {
+            Direction::Row => style.width,
+            Direction::Column => style.height,
+        }
+    }
+
+    fn cross_dimension(self, style: &Style) -> Dimension {
+        match self {
+            Direction::Row => style.height,
+            Direction::Column => style.width,
+        }
+    }
+
+    // (min, max)
+    fn main_limits(self, style: &Style) -> (f32, f32) {
+        match self {
+            Direction::Row => (style.min_width, style.max_width),
+            Direction::Column => (style.min_height, style.max_height),
+        }
+    }
+
+    fn cross_limits(self, style: &Style) -> (f32, f32) {
+        match self {
+            Direction::Row => (style.min_height, style.max_height),
+            Direction::Column => (style.min_width, style.max_width),
+        }
+    }
+}
+
+// min wins over max, same as CSS
+fn clamp(value: f32, min: f32, max: f32) -> f32 {
+    value.min(max).max(min).max(0.0)
+}
+
+fn shrink_by(size: Size, edges: &Edges) -> Size {
+    Size::new(
+        (size.width - edges.horizontal()).max(0.0),
+        (size.height - edges.vertical()).max(0.0),
+    )
+}
+
+fn in_flow<T: LayoutTree>(tree: &T, id: T::Id) -> bool {
+    tree.style(id).position == Position::Relative
+}
+
+// How big a node would like to be (without its margin) if it had `available` to work with. Nodes with
+// children in the flow size to fit them, leaves ask the tree to measure their content.
+fn intrinsic_size<T: LayoutTree>(tree: &mut T, id: T::Id, available: Size) -> Size {
+    let style = tree.style(id);
+    let fixed_width = style.width.resolve(available.width);
+    let fixed_height = style.height.resolve(available.height);
+
+    let size = match (fixed_width, fixed_height) {
+        (Some(width), Some(height)) => Size::new(width, height),
+        _ => {
+            let outer = Size::new(
+                fixed_width.unwrap_or(available.width),
+                fixed_height.unwrap_or(available.height),
+            );
+            let inner = shrink_by(outer, &style.padding);
+
+            let children: Vec<_> = tree.children(id).into_iter().filter(|child| in_flow(tree, *child)).collect();
+            let content = if children.is_empty() {
+                tree.measure(id, inner)
+            } else {
+                let direction = style.direction;
+                let mut main = style.gap * (children.len() - 1) as f32;
+                let mut cross: f32 = 0.0;
+                for child in children {
+                    let margin = tree.style(child).margin;
+                    let size = intrinsic_size(tree, child, shrink_by(inner, &margin));
+                    let (lead, trail) = direction.main_edges(&margin);
+                    let (cross_lead, cross_trail) = direction.cross_edges(&margin);
+                    main += lead + direction.main(size) + trail;
+                    cross = cross.max(cross_lead + direction.cross(size) + cross_trail);
+                }
+                direction.size(main, cross)
+            };
+
+            Size::new(
+                fixed_width.unwrap_or(content.width + style.padding.horizontal()),
+                fixed_height.unwrap_or(content.height + style.padding.vertical()),
+            )
+        }
+    };
+
+    Size::new(
+        clamp(size.width, style.min_width, style.max_width),
+        clamp(size.height, style.min_height, style.max_height),
+    )
+}
+
+fn layout_node<T: LayoutTree>(tree: &mut T, id: T::Id, bounds: Rect) {
+    tree.set_bounds(id, bounds);
+
+    let style = tree.style(id);
+    let inner_size = shrink_by(bounds.size(), &style.padding);
+    let inner = Rect::new(bounds.x + style.padding.left, bounds.y + style.padding.top, inner_size.width, inner_size.height);
+
+    let (flow, absolute): (Vec<_>, Vec<_>) = tree.children(id).into_iter().partition(|child| in_flow(tree, *child));
+    layout_flow(tree, &style, inner, &flow);
+    for child in absolute {
+        layout_absolute(tree, child, bounds, inner);
+    }
+}
+
+struct FlexItem<Id> {
+    id: Id,
+    margin_main: (f32, f32),
+    margin_cross: (f32, f32),
+    align: Align,
+    base: f32,
+    min: f32,
+    max: f32,
+    grow: f32,
+    shrink: f32,
+    // the grow or shrink weight, whichever applies this time
+    factor: f32,
+    // how far min / max moved the size away from its share in the last round
+    violation: f32,
+    main: f32,
+    cross: f32,
+    frozen: bool,
+}
+
+// Single line flexbox: work out every child's size on the main axis, hand out or take away the leftover
+// space with grow / shrink, then place them one after another with justify and align.
+fn layout_flow<T: LayoutTree>(tree: &mut T, style: &Style, inner: Rect, children: &[T::Id]) {
+    if children.is_empty() {
+        return;
+    }
+
+    let direction = style.direction;
+    let inner_main = direction.main(inner.size());
+    let inner_cross = direction.cross(inner.size());
+
+    let mut items = Vec::with_capacity(children.len());
+    for &id in children {
+        let child = tree.style(id);
+        let margin_main = direction.main_edges(&child.margin);
+        let margin_cross = direction.cross_edges(&child.margin);
+        let available = shrink_by(inner.size(), &child.margin);
+        let measured = intrinsic_size(tree, id, available);
+
+        let base = child
+            .basis
+            .resolve(inner_main)
+            .or_else(|| direction.main_dimension(&child).resolve(inner_main))
+            .unwrap_or_else(|| direction.main(measured));
+        let (min, max) = direction.main_limits(&child);
+
+        let align = child.align_self.unwrap_or(style.align_items);
+        let available_cross = direction.cross(available);
+        let (cross_min, cross_max) = direction.cross_limits(&child);
+        let cross = match direction.cross_dimension(&child).resolve(inner_cross) {
+            Some(cross) => cross,
+            None if align == Align::Stretch => available_cross,
+            None => direction.cross(measured).min(available_cross),
+        };
+
+        items.push(FlexItem {
+            id,
+            margin_main,
+            margin_cross,
+            align,
+            base,
+            min,
+            max,
+            grow: child.grow,
+            shrink: child.shrink,
+            factor: 0.0,
+            violation: 0.0,
+            main: clamp(base, min, max),
+            cross: clamp(cross, cross_min, cross_max),
+            frozen: false,
+        });
+    }
+
+    let gaps = style.gap * (items.len() - 1) as f32;
+    let margins: f32 = items.iter().map(|item| item.margin_main.0 + item.margin_main.1).sum();
+    let free = inner_main - gaps - margins - items.iter().map(|item| item.main).sum::<f32>();
+    resolve_flexible_lengths(&mut items, inner_main - gaps - margins, free > 0.0);
+
+    let used = gaps + margins + items.iter().map(|item| item.main).sum::<f32>();
+    let leftover = inner_main - used;
+    let count = items.len() as f32;
+    let (offset, between) = match style.justify {
+        Justify::Start => (0.0, 0.0),
+        Justify::Center => (leftover / 2.0, 0.0),
+        Justify::End => (leftover, 0.0),
+        // the spacing ones can't hand out negative space, they act like Start when things overflow
+        _ if leftover <= 0.0 => (0.0, 0.0),
+        Justify::SpaceBetween if items.len() > 1 => (0.0, leftover / (count - 1.0)),
+        Justify::SpaceBetween => (0.0, 0.0),
+        Justify::SpaceAround => (leftover / count / 2.0, leftover / count),
+        Justify::SpaceEvenly => (leftover / (count + 1.0), leftover / (count + 1.0)),
+    };
+
+    let (main_start, cross_start) = direction.start(inner);
+    let mut cursor = main_start + offset;
+    for item in items {
+        let (lead, trail) = item.margin_cross;
+        let cross_pos = match item.align {
+            Align::Start | Align::Stretch => cross_start + lead,
+            Align::Center => cross_start + lead + (inner_cross - lead - trail - item.cross) / 2.0,
+            Align::End => cross_start + inner_cross - trail - item.cross,
+        };
+
+        let main_pos = cursor + item.margin_main.0;
+        layout_node(tree, item.id, direction.rect(main_pos, cross_pos, item.main, item.cross));
+        cursor = main_pos + item.main + item.margin_main.1 + style.gap + between;
+    }
+}
+
+// The CSS "resolve flexible lengths" loop. Space is shared out by grow (or shrink * base size), any item
+// that hits its min / max gets frozen there and the rest is shared out again between the others.
+fn resolve_flexible_lengths<Id>(items: &mut [FlexItem<Id>], space: f32, growing: bool) {
+    for item in items.iter_mut() {
+        item.factor = if growing { item.grow } else { item.shrink * item.base };
+        // nothing to flex, or min / max already pushed it past the base size in the direction we're going
+        let clamped_away = if growing { item.main < item.base } else { item.main > item.base };
+        if item.factor <= 0.0 || clamped_away {
+            item.frozen = true;
+        }
+    }
+
+    loop {
+        let frozen: f32 = items.iter().filter(|item| item.frozen).map(|item| item.main).sum();
+        let unfrozen_base: f32 = items.iter().filter(|item| !item.frozen).map(|item| item.base).sum();
+        let total_factor: f32 = items.iter().filter(|item| !item.frozen).map(|item| item.factor).sum();
+        if total_factor <= 0.0 {
+            return;
+        }
+
+        let remaining = space - frozen - unfrozen_base;
+        let mut total_violation = 0.0;
+        for item in items.iter_mut().filter(|item| !item.frozen) {
+            let target = item.base + remaining * item.factor / total_factor;
+            item.main = clamp(target, item.min, item.max);
+            item.violation = item.main - target;
+            total_violation += item.violation;
+        }
+
+        if total_violation.abs() < 0.001 {
+            return;
+        }
+        // overall too big means mins got hit, freeze those. Too small means the maxes did.
+        for item in items.iter_mut().filter(|item| !item.frozen) {
+            if (total_violation > 0.0 && item.violation > 0.0) || (total_violation < 0.0 && item.violation < 0.0) {
+                item.frozen = true;
+            }
+        }
+    }
+}
+
+// Absolute children are placed against the parent's bounds and don't move anything else, insets count
+// from the outer edge like CSS does from the padding box. With neither side of an axis set they sit where
+// the parent's padding starts (`content`), which is where the first flowed child would go.
+fn layout_absolute<T: LayoutTree>(tree: &mut T, id: T::Id, container: Rect, content: Rect) {
+    let style = tree.style(id);
+    let margin = style.margin;
+    let inset = style.inset;
+    let measured = intrinsic_size(tree, id, shrink_by(container.size(), &margin));
+
+    let width = style
+        .width
+        .resolve(container.width)
+        .or(match (inset.left, inset.right) {
+            (Some(left), Some(right)) => Some(container.width - left - right - margin.horizontal()),
+            _ => None,
+        })
+        .unwrap_or(measured.width);
+    let height = style
+        .height
+        .resolve(container.height)
+        .or(match (inset.top, inset.bottom) {
+            (Some(top), Some(bottom)) => Some(container.height - top - bottom - margin.vertical()),
+            _ => None,
+        })
+        .unwrap_or(measured.height);
+    let width = clamp(width, style.min_width, style.max_width);
+    let height = clamp(height, style.min_height, style.max_height);
+
+    let x = match (inset.left, inset.right) {
+        (Some(left), _) => container.x + left + margin.left,
+        (None, Some(right)) => container.right() - right - margin.right - width,
+        (None, None) => content.x + margin.left,
+    };
+    let y = match (inset.top, inset.bottom) {
+        (Some(top), _) => container.y + top + margin.top,
+        (None, Some(bottom)) => container.bottom() - bottom - margin.bottom - height,
+        (None, None) => content.y + margin.top,
+    };
+
+    layout_node(tree, id, Rect::new(x, y, width, height));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::style::Inset;
+
+    struct TestNode {
+        style: Style,
+        children: Vec<usize>,
+        content: Size,
+        bounds: Rect,
+    }
+
+    // nodes in a Vec, leaves measure to a fixed content size
+    #[derive(Default)]
+    struct TestTree {
+        nodes: Vec<TestNode>,
+    }
+
+    impl TestTree {
+        fn root(style: Style) -> (Self, usize) {
+            let mut tree = Self::default();
+            let root = tree.push(style, Size::ZERO);
+            (tree, root)
+        }
+
+        fn push(&mut self, style: Style, content: Size) -> usize {
+            self.nodes.push(TestNode { style, children: Vec::new(), content, bounds: Rect::default() });
+            self.nodes.len() - 1
+        }
+
+        fn add(&mut self, parent: usize, style: Style, content: Size) -> usize {
+            let id = self.push(style, content);
+            self.nodes[parent].children.push(id);
+            id
+        }
+
+        fn leaf(&mut self, parent: usize, width: f32, height: f32) -> usize {
+            self.add(parent, Style::default(), Size::new(width, height))
+        }
+
+        fn bounds(&self, id: usize) -> Rect {
+            self.nodes[id].bounds
+        }
+    }
+
+    impl LayoutTree for TestTree {
+        type Id = usize;
+
+        fn style(&self, id: usize) -> Style {
+            self.nodes[id].style
+        }
+
+        fn children(&self, id: usize) -> Vec<usize> {
+            self.nodes[id].children.clone()
+        }
+
+        fn measure(&mut self, id: usize, _available: Size) -> Size {
+            self.nodes[id].content
+        }
+
+        fn set_bounds(&mut self, id: usize, bounds: Rect) {
+            self.nodes[id].bounds = bounds;
+        }
+    }
+
+    const VIEWPORT: Size = Size::new(200.0, 100.0);
+
+    #[test]
+    fn root_fills_viewport() {
+        let (mut tree, root) = TestTree::root(Style::default());
+        compute_layout(&mut tree, root, VIEWPORT);
+        assert_eq!(tree.bounds(root), Rect::new(0.0, 0.0, 200.0, 100.0));
+
+        // a resize just lays out again with the new viewport
+        compute_layout(&mut tree, root, Size::new(320.0, 240.0));
+        assert_eq!(tree.bounds(root), Rect::new(0.0, 0.0, 320.0, 240.0));
+    }
+
+    #[test]
+    fn column_stacks_children_at_their_size() {
+        let (mut tree, root) = TestTree::root(Style::column());
+        let a = tree.leaf(root, 50.0, 20.0);
+        let b = tree.leaf(root, 80.0, 30.0);
+        compute_layout(&mut tree, root, VIEWPORT);
+
+        assert_eq!(tree.bounds(a), Rect::new(0.0, 0.0, 50.0, 20.0));
+        assert_eq!(tree.bounds(b), Rect::new(0.0, 20.0, 80.0, 30.0));
+    }
+
+    #[test]
+    fn row_with_padding_gap_and_margin() {
+        let (mut tree, root) = TestTree::root(Style { padding: Edges::all(10.0), gap: 5.0, ..Style::row() });
+        let a = tree.leaf(root, 20.0, 20.0);
+        let b = tree.add(root, Style { margin: Edges::symmetric(3.0, 4.0), ..Default::default() }, Size::new(30.0, 10.0));
+        let c = tree.leaf(root, 10.0, 10.0);
+        compute_layout(&mut tree, root, VIEWPORT);
+
+        assert_eq!(tree.bounds(a), Rect::new(10.0, 10.0, 20.0, 20.0));
+        assert_eq!(tree.bounds(b), Rect::new(38.0, 14.0, 30.0, 10.0));
+        assert_eq!(tree.bounds(c), Rect::new(76.0, 10.0, 10.0, 10.0));
+    }
+
+    #[test]
+    fn grow_shares_free_space_by_weight() {
+        let (mut tree, root) = TestTree::root(Style::row());
+        let fixed = tree.leaf(root, 50.0, 10.0);
+        let one = tree.add(root, Style { grow: 1.0, ..Default::default() }, Size::new(0.0, 10.0));
+        let two = tree.add(root, Style { grow: 2.0, ..Default::default() }, Size::new(0.0, 10.0));
+        compute_layout(&mut tree, root, VIEWPORT);
+
+        assert_eq!(tree.bounds(fixed).width, 50.0);
+        assert_eq!(tree.bounds(one), Rect::new(50.0, 0.0, 50.0, 10.0));
+        assert_eq!(tree.bounds(two), Rect::new(100.0, 0.0, 100.0, 10.0));
+    }
+
+    #[test]
+    fn fill_ignores_content_size() {
+        let (mut tree, root) = TestTree::root(Style::row());
+        let a = tree.add(root, Style::fill(), Size::new(150.0, 10.0));
+        let b = tree.add(root, Style::fill(), Size::new(10.0, 10.0));
+        compute_layout(&mut tree, root, VIEWPORT);
+
+        assert_eq!(tree.bounds(a).width, 100.0);
+        assert_eq!(tree.bounds(b), Rect::new(100.0, 0.0, 100.0, 10.0));
+    }
+
+    #[test]
+    fn shrink_is_weighted_by_base_size() {
+        let (mut tree, root) = TestTree::root(Style::row());
+        let a = tree.leaf(root, 150.0, 10.0);
+        let b = tree.leaf(root, 100.0, 10.0);
+        let rigid = tree.add(root, Style { shrink: 0.0, ..Default::default() }, Size::new(50.0, 10.0));
+        compute_layout(&mut tree, root, VIEWPORT);
+
+        // 100 too wide, a gives up 60 and b 40
+        assert_eq!(tree.bounds(a).width, 90.0);
+        assert_eq!(tree.bounds(b), Rect::new(90.0, 0.0, 60.0, 10.0));
+        assert_eq!(tree.bounds(rigid), Rect::new(150.0, 0.0, 50.0, 10.0));
+    }
+
+    #[test]
+    fn max_size_freezes_and_gives_space_to_the_rest() {
+        let (mut tree, root) = TestTree::root(Style::row());
+        let capped = tree.add(root, Style { max_width: 40.0, ..Style::fill() }, Size::ZERO);
+        let free = tree.add(root, Style::fill(), Size::ZERO);
+        compute_layout(&mut tree, root, VIEWPORT);
+
+        assert_eq!(tree.bounds(capped).width, 40.0);
+        assert_eq!(tree.bounds(free), Rect::new(40.0, 0.0, 160.0, 0.0));
+    }
+
+    #[test]
+    fn min_size_stops_shrinking() {
+        let (mut tree, root) = TestTree::root(Style::row());
+        let floor = tree.add(root, Style { min_width: 140.0, ..Default::default() }, Size::new(150.0, 10.0));
+        let other = tree.leaf(root, 150.0, 10.0);
+        compute_layout(&mut tree, root, VIEWPORT);
+
+        assert_eq!(tree.bounds(floor).width, 140.0);
+        assert_eq!(tree.bounds(other), Rect::new(140.0, 0.0, 60.0, 10.0));
+    }
+
+    #[test]
+    fn min_and_max_clamp_measured_size() {
+        let (mut tree, root) = TestTree::root(Style::column());
+        let small = tree.add(root, Style { min_width: 30.0, min_height: 15.0, ..Default::default() }, Size::new(10.0, 10.0));
+        let big = tree.add(root, Style { max_width: 60.0, ..Default::default() }, Size::new(100.0, 10.0));
+        compute_layout(&mut tree, root, VIEWPORT);
+
+        assert_eq!(tree.bounds(small), Rect::new(0.0, 0.0, 30.0, 15.0));
+        assert_eq!(tree.bounds(big), Rect::new(0.0, 15.0, 60.0, 10.0));
+    }
+
+    #[test]
+    fn justify_positions_leftover_space() {
+        let cases = [
+            (Justify::Start, [0.0, 25.0]),
+            (Justify::Center, [75.0, 100.0]),
+            (Justify::End, [150.0, 175.0]),
+            (Justify::SpaceBetween, [0.0, 175.0]),
+            (Justify::SpaceAround, [37.5, 137.5]),
+            (Justify::SpaceEvenly, [50.0, 125.0]),
+        ];
+
+        for (justify, expected) in cases {
+            let (mut tree, root) = TestTree::root(Style { justify, ..Style::row() });
+            let a = tree.leaf(root, 25.0, 10.0);
+            let b = tree.leaf(root, 25.0, 10.0);
+            compute_layout(&mut tree, root, VIEWPORT);
+            assert_eq!([tree.bounds(a).x, tree.bounds(b).x], expected, "{:?}", justify);
+        }
+    }
+
+    #[test]
+    fn align_on_the_cross_axis() {
+        let (mut tree, root) = TestTree::root(Style { align_items: Align::Center, ..Style::row() });
+        let centered = tree.leaf(root, 20.0, 20.0);
+        let end = tree.add(root, Style { align_self: Some(Align::End), ..Default::default() }, Size::new(20.0, 20.0));
+        let stretched = tree.add(root, Style { align_self: Some(Align::Stretch), margin: Edges::all(5.0), ..Default::default() }, Size::new(20.0, 20.0));
+        compute_layout(&mut tree, root, VIEWPORT);
+
+        assert_eq!(tree.bounds(centered), Rect::new(0.0, 40.0, 20.0, 20.0));
+        assert_eq!(tree.bounds(end), Rect::new(20.0, 80.0, 20.0, 20.0));
+        assert_eq!(tree.bounds(stretched), Rect::new(45.0, 5.0, 20.0, 90.0));
+    }
+
+    #[test]
+    fn percent_of_parent_inner_size() {
+        let (mut tree, root) = TestTree::root(Style { padding: Edges::all(10.0), ..Style::row() });
+        let half = tree.add(root, Style { width: Dimension::Percent(0.5), height: Dimension::Percent(1.0), ..Default::default() }, Size::ZERO);
+        compute_layout(&mut tree, root, VIEWPORT);
+
+        assert_eq!(tree.bounds(half), Rect::new(10.0, 10.0, 90.0, 80.0));
+    }
+
+    #[test]
+    fn containers_size_to_their_children() {
+        let (mut tree, root) = TestTree::root(Style::column());
+        let row = tree.add(root, Style { padding: Edges::all(4.0), gap: 2.0, ..Style::row() }, Size::ZERO);
+        let a = tree.leaf(row, 10.0, 30.0);
+        let b = tree.leaf(row, 20.0, 10.0);
+        let after = tree.leaf(root, 5.0, 5.0);
+        compute_layout(&mut tree, root, VIEWPORT);
+
+        assert_eq!(tree.bounds(row), Rect::new(0.0, 0.0, 40.0, 38.0));
+        assert_eq!(tree.bounds(a), Rect::new(4.0, 4.0, 10.0, 30.0));
+        assert_eq!(tree.bounds(b), Rect::new(16.0, 4.0, 20.0, 10.0));
+        assert_eq!(tree.bounds(after), Rect::new(0.0, 38.0, 5.0, 5.0));
+    }
+
+    #[test]
+    fn absolute_children_are_out_of_the_flow() {
+        let (mut tree, root) = TestTree::root(Style { padding: Edges::all(10.0), ..Style::column() });
+        let corner = tree.add(
+            root,
+            Style::absolute(Inset { right: Some(5.0), bottom: Some(5.0), ..Default::default() }),
+            Size::new(20.0, 10.0),
+        );
+        let banner = tree.add(
+            root,
+            Style { height: Dimension::Px(12.0), ..Style::absolute(Inset { left: Some(0.0), right: Some(0.0), top: Some(0.0), ..Default::default() }) },
+            Size::new(20.0, 10.0),
+        );
+        let flowed = tree.leaf(root, 30.0, 30.0);
+        compute_layout(&mut tree, root, VIEWPORT);
+
+        assert_eq!(tree.bounds(corner), Rect::new(175.0, 85.0, 20.0, 10.0));
+        assert_eq!(tree.bounds(banner), Rect::new(0.0, 0.0, 200.0, 12.0));
+        assert_eq!(tree.bounds(flowed), Rect::new(10.0, 10.0, 30.0, 30.0));
+    }
+
+    #[test]
+    fn absolute_without_insets_starts_inside_the_padding() {
+        let (mut tree, root) = TestTree::root(Style { padding: Edges { left: 10.0, top: 6.0, ..Edges::ZERO }, ..Style::column() });
+        let floating = tree.add(root, Style::absolute(Inset::default()), Size::new(20.0, 10.0));
+        // one axis pinned, the other still starts at the padding
+        let pinned = tree.add(root, Style::absolute(Inset { right: Some(5.0), ..Default::default() }), Size::new(20.0, 10.0));
+        compute_layout(&mut tree, root, VIEWPORT);
+
+        assert_eq!(tree.bounds(floating), Rect::new(10.0, 6.0, 20.0, 10.0));
+        assert_eq!(tree.bounds(pinned), Rect::new(175.0, 6.0, 20.0, 10.0));
+    }
+}
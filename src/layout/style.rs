@@ -0,0 +1,183 @@
+// How a node wants to be laid out. These are the flexbox properties from CSS, trimmed down to what
+// a single line (no wrapping) flex layout needs. All lengths are logical pixels.
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Dimension {
+    // whatever the content measures to
+    #[default]
+    Auto,
+    Px(f32),
+    // 0.0 .. 1.0 of the parent's inner size (size minus padding)
+    Percent(f32),
+}
+
+impl Dimension {
+    pub fn resolve(self, parent: f32) -> Option<f32> {
+        match self {
+            Dimension::Auto => None,
+            Dimension::Px(px) => Some(px),
+            Dimension::Percent(fraction) => Some(parent * fraction),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Edges {
+    pub left: f32,
+    pub right: f32,
+    pub top: f32,
+    pub bottom: f32,
+}
+
+impl Edges {
+    pub const ZERO: Edges = Edges::all(0.0);
+
+    pub const fn all(amount: f32) -> Self {
+        Self { left: amount, right: amount, top: amount, bottom: amount }
+    }
+
+    pub const fn symmetric(horizontal: f32, vertical: f32) -> Self {
+        Self { left: horizontal, right: horizontal, top: vertical, bottom: vertical }
+    }
+
+    pub fn horizontal(&self) -> f32 {
+        self.left + self.right
+    }
+
+    pub fn vertical(&self) -> f32 {
+        self.top + self.bottom
+    }
+}
+
+// Offsets for Position::Absolute, from the matching edge of the parent. None means not set.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Inset {
+    pub left: Option<f32>,
+    pub right: Option<f32>,
+    pub top: Option<f32>,
+    pub bottom: Option<f32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Direction {
+    Row,
+    // top to bottom, the way the tree stacked widgets before it had a layout engine
+    #[default]
+    Column,
+}
+
+// where children sit on the cross axis (across the direction)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Align {
+    #[default]
+    Start,
+    Center,
+    End,
+    // fill the parent, only works when the size on that axis is Auto
+    Stretch,
+}
+
+// what to do with leftover space on the main axis
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Justify {
+    #[default]
+    Start,
+    Center,
+    End,
+    SpaceBetween,
+    SpaceAround,
+    SpaceEvenly,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Position {
+    // takes part in its parent's flex layout
+    #[default]
+    Relative,
+    // taken out of the flow and placed with `inset` relative to the parent's bounds
+    Absolute,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Style {
+    pub position: Position,
+    pub inset: Inset,
+
+    pub width: Dimension,
+    pub height: Dimension,
+    pub min_width: f32,
+    pub min_height: f32,
+    pub max_width: f32,
+    pub max_height: f32,
+
+    pub padding: Edges,
+    pub margin: Edges,
+
+    // how this node lays out its children
+    pub direction: Direction,
+    pub gap: f32,
+    pub align_items: Align,
+    pub justify: Justify,
+
+    // how this node sits in its parent
+    pub align_self: Option<Align>,
+    pub basis: Dimension,
+    pub grow: f32,
+    pub shrink: f32,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self {
+            position: Position::Relative,
+            inset: Inset::default(),
+            width: Dimension::Auto,
+            height: Dimension::Auto,
+            min_width: 0.0,
+            min_height: 0.0,
+            max_width: f32::INFINITY,
+            max_height: f32::INFINITY,
+            padding: Edges::ZERO,
+            margin: Edges::ZERO,
+            direction: Direction::Column,
+            gap: 0.0,
+            align_items: Align::Start,
+            justify: Justify::Start,
+            align_self: None,
+            basis: Dimension::Auto,
+            grow: 0.0,
+            // like CSS, children squeeze to fit when there isn't enough room
+            shrink: 1.0,
+        }
+    }
+}
+
+impl Style {
+    pub fn row() -> Self {
+        Self {
+            direction: Direction::Row,
+            ..Default::default()
+        }
+    }
+
+    pub fn column() -> Self {
+        Self::default()
+    }
+
+    // `flex: 1` in CSS, takes an equal share of whatever space is left
+    pub fn fill() -> Self {
+        Self {
+            basis: Dimension::Px(0.0),
+            grow: 1.0,
+            ..Default::default()
+        }
+    }
+
+    pub fn absolute(inset: Inset) -> Self {
+        Self {
+            position: Position::Absolute,
+            inset,
+            ..Default::default()
+        }
+    }
+}
@@ -1,11 +1,17 @@
+// every module keeps its code in a file with the same name (app/app.rs, gpu/gpu.rs ...)
+#![allow(clippy::module_inception)]
+
 pub mod app;
 pub mod gpu;
-mod render;
+pub mod render;
 pub mod shaders;
-mod widgets;
+pub mod widgets;
 pub mod window;
 pub mod textures;
 pub mod geometry;
 pub mod camera;
+pub mod text;
+pub mod layout;
+pub mod scene;
 
 // you have add the modules here for the rust analyzer to work
@@ -0,0 +1,247 @@
+use std::ops::Range;
+use nalgebra::{Matrix3, Matrix4, Vector3};
+use crate::geometry::mesh::Mesh;
+use crate::shaders::buffers::DynamicBuffer;
+
+// One copy of a mesh
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Instance {
+    pub model: Matrix4<f32>,
+    // linear rgba, multiplies whatever color the shader works out for the mesh
+    pub color: [f32; 4],
+}
+
+impl Default for Instance {
+    fn default() -> Self {
+        Self {
+            model: Matrix4::identity(),
+            color: [1.0, 1.0, 1.0, 1.0],
+        }
+    }
+}
+
+impl Instance {
+    pub fn new(model: Matrix4<f32>) -> Self {
+        Self { model, ..Self::default() }
+    }
+
+    pub fn at(translation: Vector3<f32>) -> Self {
+        Self::new(Matrix4::new_translation(&translation))
+    }
+
+    pub fn with_color(mut self, color: [f32; 4]) -> Self {
+        self.color = color;
+        self
+    }
+
+    // a negative determinant means the model matrix mirrors, which turns the triangles' winding around
+    pub fn is_mirrored(&self) -> bool {
+        self.model.fixed_view::<3, 3>(0, 0).determinant() < 0.0
+    }
+
+    // The inverse transpose of the model matrix's 3x3, what keeps normals at right angles to the surface
+    // when the scale isn't the same on every axis. A scale of 0 on some axis has no inverse, that one
+    // keeps the plain 3x3 since it's flat anyway.
+    pub fn normal_matrix(&self) -> Matrix3<f32> {
+        let linear = self.model.fixed_view::<3, 3>(0, 0).into_owned();
+        linear.try_inverse().map(|inverse| inverse.transpose()).unwrap_or(linear)
+    }
+
+    pub fn to_raw(&self) -> InstanceRaw {
+        InstanceRaw {
+            model: self.model.into(),
+            color: self.color,
+            normal: self.normal_matrix().into(),
+        }
+    }
+}
+
+// What an instance looks like in the instance buffer. A mat4 can't be one vertex attribute so it goes in
+// as its four columns at @location(4) to @location(7), the color is @location(8) and the normal matrix's
+// three columns are @location(9) to @location(11).
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct InstanceRaw {
+    pub model: [[f32; 4]; 4],
+    pub color: [f32; 4],
+    pub normal: [[f32; 3]; 3],
+}
+
+impl InstanceRaw {
+    // after MeshVertex's 0..=3
+    const ATTRIBUTES: [wgpu::VertexAttribute; 8] = wgpu::vertex_attr_array![
+        4 => Float32x4,
+        5 => Float32x4,
+        6 => Float32x4,
+        7 => Float32x4,
+        8 => Float32x4,
+        9 => Float32x3,
+        10 => Float32x3,
+        11 => Float32x3,
+    ];
+
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<InstanceRaw>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+// Handed out by InstanceBuffer::add, stays valid until that instance is removed no matter what else is
+// added or removed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct InstanceId(u32);
+
+// The instances of one mesh and the vertex buffer they're drawn from (slot 1, the mesh is slot 0).
+// They're packed so removing one moves the last instance into its place, the ids keep track of where
+// everything went. Changes only reach the GPU in upload(), and only the part that changed is written
+// unless the buffer has to grow.
+pub struct InstanceBuffer {
+    instances: Vec<Instance>,
+    // the id of the instance at the same index in `instances`
+    ids: Vec<InstanceId>,
+    // id -> index into `instances`, None once removed
+    slots: Vec<Option<usize>>,
+    free: Vec<u32>,
+    buffer: DynamicBuffer,
+    // instances that have to be written again, None when the GPU copy is up to date
+    dirty: Option<Range<usize>>,
+}
+
+impl Default for InstanceBuffer {
+    fn default() -> Self {
+        Self {
+            instances: Vec::new(),
+            ids: Vec::new(),
+            slots: Vec::new(),
+            free: Vec::new(),
+            buffer: DynamicBuffer::vertex("Instance Buffer"),
+            dirty: None,
+        }
+    }
+}
+
+impl InstanceBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, instance: Instance) -> InstanceId {
+        let index = self.instances.len();
+        let id = match self.free.pop() {
+            Some(id) => {
+                self.slots[id as usize] = Some(index);
+                InstanceId(id)
+            }
+            None => {
+                self.slots.push(Some(index));
+                InstanceId(self.slots.len() as u32 - 1)
+            }
+        };
+
+        self.instances.push(instance);
+        self.ids.push(id);
+        self.mark_dirty(index..index + 1);
+        id
+    }
+
+    // Returns the instance, or None if the id was already removed
+    pub fn remove(&mut self, id: InstanceId) -> Option<Instance> {
+        let index = self.index(id)?;
+        let instance = self.instances.swap_remove(index);
+        self.ids.swap_remove(index);
+        self.slots[id.0 as usize] = None;
+        self.free.push(id.0);
+
+        // the last one moved into the hole
+        if let Some(moved) = self.ids.get(index) {
+            self.slots[moved.0 as usize] = Some(index);
+            self.mark_dirty(index..index + 1);
+        }
+        Some(instance)
+    }
+
+    pub fn get(&self, id: InstanceId) -> Option<&Instance> {
+        self.index(id).map(|index| &self.instances[index])
+    }
+
+    // false if the id was removed
+    pub fn update(&mut self, id: InstanceId, instance: Instance) -> bool {
+        match self.index(id) {
+            Some(index) => {
+                self.instances[index] = instance;
+                self.mark_dirty(index..index + 1);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // ids handed out before this don't mean anything afterwards, new instances reuse them
+    pub fn clear(&mut self) {
+        self.instances.clear();
+        self.ids.clear();
+        self.slots.clear();
+        self.free.clear();
+        self.dirty = None;
+    }
+
+    pub fn len(&self) -> usize {
+        self.instances.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.instances.is_empty()
+    }
+
+    // in buffer order, which changes when instances are removed
+    pub fn iter(&self) -> impl Iterator<Item = (InstanceId, &Instance)> {
+        self.ids.iter().copied().zip(self.instances.iter())
+    }
+
+    // Call once a frame before drawing
+    pub fn upload(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        let Some(dirty) = self.dirty.take() else {
+            return;
+        };
+        // removing from the end can leave the range past what's left
+        let dirty = dirty.start.min(self.instances.len())..dirty.end.min(self.instances.len());
+        if dirty.is_empty() {
+            return;
+        }
+
+        let offset = (dirty.start * std::mem::size_of::<InstanceRaw>()) as wgpu::BufferAddress;
+        let raw: Vec<InstanceRaw> = self.instances[dirty].iter().map(Instance::to_raw).collect();
+        if !self.buffer.write_at(queue, offset, bytemuck::cast_slice(&raw)) {
+            // a new buffer starts out empty so everything goes in, not just what changed
+            let raw: Vec<InstanceRaw> = self.instances.iter().map(Instance::to_raw).collect();
+            self.buffer.write(device, queue, bytemuck::cast_slice(&raw));
+        }
+    }
+
+    // Draws `mesh` once per instance. The pipeline has to take MeshVertex::desc() and InstanceRaw::desc().
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass<'_>, mesh: &Mesh) {
+        let Some(buffer) = self.buffer.buffer() else {
+            return;
+        };
+        if self.instances.is_empty() {
+            return;
+        }
+
+        render_pass.set_vertex_buffer(1, buffer.slice(..));
+        mesh.draw(render_pass, 0..self.instances.len() as u32);
+    }
+
+    fn index(&self, id: InstanceId) -> Option<usize> {
+        self.slots.get(id.0 as usize).copied().flatten()
+    }
+
+    fn mark_dirty(&mut self, range: Range<usize>) {
+        self.dirty = Some(match self.dirty.take() {
+            Some(dirty) => dirty.start.min(range.start)..dirty.end.max(range.end),
+            None => range,
+        });
+    }
+}
@@ -0,0 +1,4 @@
+pub mod render;
+pub mod painter;
+pub mod ui;
+pub mod instance;
@@ -0,0 +1,154 @@
+use crate::geometry::rect::{Point, Rect};
+use crate::text::text::TextStyle;
+
+// Colors are given the way a designer would write them, in sRGB. The renderer converts them to linear
+// when it draws into an sRGB target so #808080 on screen is #808080 either way.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Color {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+impl Color {
+    pub const TRANSPARENT: Color = Color::rgba(0.0, 0.0, 0.0, 0.0);
+    pub const BLACK: Color = Color::rgb(0.0, 0.0, 0.0);
+    pub const WHITE: Color = Color::rgb(1.0, 1.0, 1.0);
+
+    pub const fn rgb(r: f32, g: f32, b: f32) -> Self {
+        Self { r, g, b, a: 1.0 }
+    }
+
+    pub const fn rgba(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Self { r, g, b, a }
+    }
+
+    pub fn from_rgba8(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Self::rgba(r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0, a as f32 / 255.0)
+    }
+
+    pub fn with_alpha(self, a: f32) -> Self {
+        Self { a, ..self }
+    }
+
+    // straight line between two colors, t = 0 is self and t = 1 is other
+    pub fn lerp(self, other: Color, t: f32) -> Self {
+        let t = t.clamp(0.0, 1.0);
+        Self::rgba(
+            self.r + (other.r - self.r) * t,
+            self.g + (other.g - self.g) * t,
+            self.b + (other.b - self.b) * t,
+            self.a + (other.a - self.a) * t,
+        )
+    }
+
+    pub fn to_linear(self) -> Self {
+        fn channel(c: f32) -> f32 {
+            if c <= 0.04045 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        }
+        Self::rgba(channel(self.r), channel(self.g), channel(self.b), self.a)
+    }
+
+    pub fn to_array(self) -> [f32; 4] {
+        [self.r, self.g, self.b, self.a]
+    }
+}
+
+// An outline drawn on the inside edge of a shape, like a CSS border. Width is in logical pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stroke {
+    pub width: f32,
+    pub color: Color,
+}
+
+impl Stroke {
+    pub const NONE: Stroke = Stroke::new(0.0, Color::TRANSPARENT);
+
+    pub const fn new(width: f32, color: Color) -> Self {
+        Self { width, color }
+    }
+}
+
+// One thing to draw. Widgets don't touch the GPU, they push these into a Painter and the UI renderer
+// turns the whole list into vertices once per frame. A transparent fill with a stroke is just an outline.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Primitive {
+    Rect { rect: Rect, corner_radius: f32, fill: Color, stroke: Stroke },
+    Ellipse { rect: Rect, fill: Color, stroke: Stroke },
+    // laid out inside `rect` by the renderer, see text::text::layout_text
+    Text { rect: Rect, text: String, style: TextStyle },
+}
+
+// The display list for one frame, in the order things should be drawn (back to front)
+#[derive(Debug, Default)]
+pub struct Painter {
+    primitives: Vec<Primitive>,
+}
+
+impl Painter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn clear(&mut self) {
+        self.primitives.clear();
+    }
+
+    pub fn primitives(&self) -> &[Primitive] {
+        &self.primitives
+    }
+
+    // filled and outlined in one go, cheaper than a fill and a stroke on top of each other
+    pub fn rounded_rect(&mut self, rect: Rect, corner_radius: f32, fill: Color, stroke: Stroke) {
+        self.primitives.push(Primitive::Rect { rect, corner_radius, fill, stroke });
+    }
+
+    pub fn ellipse(&mut self, rect: Rect, fill: Color, stroke: Stroke) {
+        self.primitives.push(Primitive::Ellipse { rect, fill, stroke });
+    }
+
+    pub fn fill_rect(&mut self, rect: Rect, color: Color) {
+        self.fill_rounded_rect(rect, 0.0, color);
+    }
+
+    pub fn fill_rounded_rect(&mut self, rect: Rect, corner_radius: f32, color: Color) {
+        self.rounded_rect(rect, corner_radius, color, Stroke::NONE);
+    }
+
+    pub fn fill_ellipse(&mut self, rect: Rect, color: Color) {
+        self.ellipse(rect, color, Stroke::NONE);
+    }
+
+    pub fn fill_circle(&mut self, center: Point, radius: f32, color: Color) {
+        self.fill_ellipse(circle_rect(center, radius), color);
+    }
+
+    pub fn stroke_rect(&mut self, rect: Rect, stroke: Stroke) {
+        self.stroke_rounded_rect(rect, 0.0, stroke);
+    }
+
+    pub fn stroke_rounded_rect(&mut self, rect: Rect, corner_radius: f32, stroke: Stroke) {
+        self.rounded_rect(rect, corner_radius, Color::TRANSPARENT, stroke);
+    }
+
+    pub fn stroke_ellipse(&mut self, rect: Rect, stroke: Stroke) {
+        self.ellipse(rect, Color::TRANSPARENT, stroke);
+    }
+
+    pub fn stroke_circle(&mut self, center: Point, radius: f32, stroke: Stroke) {
+        self.stroke_ellipse(circle_rect(center, radius), stroke);
+    }
+
+    pub fn text(&mut self, rect: Rect, text: impl Into<String>, style: &TextStyle) {
+        self.primitives.push(Primitive::Text { rect, text: text.into(), style: style.clone() });
+    }
+}
+
+fn circle_rect(center: Point, radius: f32) -> Rect {
+    Rect::new(center.x - radius, center.y - radius, radius * 2.0, radius * 2.0)
+}
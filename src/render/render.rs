@@ -1 +1,160 @@
+use crate::shaders::shader::VertexShaders;
+use crate::textures::textures::{MultisampledTexture, StencilDepthTexture};
 
+// How the attachments of a window / offscreen target are set up
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RenderSettings {
+    // None turns the depth buffer off. A format with a stencil aspect (Depth24PlusStencil8) adds a stencil.
+    pub depth_format: Option<wgpu::TextureFormat>,
+    // MSAA samples per pixel, 1 / 2 / 4 / 8. Gets lowered to what the adapter can do, see supported().
+    pub sample_count: u32,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            depth_format: Some(StencilDepthTexture::DEFAULT_FORMAT),
+            sample_count: 1,
+        }
+    }
+}
+
+impl RenderSettings {
+    // Not every adapter can multisample every format (and WebGL / some software adapters only do 1 and 4).
+    // This drops sample_count to the highest count both the color and depth format support.
+    pub fn supported(mut self, adapter: &wgpu::Adapter, color_format: wgpu::TextureFormat) -> Self {
+        let color = adapter.get_texture_format_features(color_format).flags;
+        let depth = self.depth_format.map(|format| adapter.get_texture_format_features(format).flags);
+
+        let requested = self.sample_count.max(1);
+        let supported = [16, 8, 4, 2]
+            .into_iter()
+            .filter(|&count| count <= requested)
+            .find(|&count| {
+                color.sample_count_supported(count) && depth.is_none_or(|depth| depth.sample_count_supported(count))
+            })
+            .unwrap_or(1);
+
+        if supported != requested {
+            eprintln!("{}x MSAA is not supported for {:?}, using {}x", requested, color_format, supported);
+        }
+
+        self.sample_count = supported;
+        self
+    }
+}
+
+// The depth and MSAA textures that go with a color target. They all have to be the same size
+// so resize() remakes them whenever the surface / offscreen texture changes size.
+pub struct FrameAttachments {
+    pub settings: RenderSettings,
+    pub color_format: wgpu::TextureFormat,
+    pub stencil_depth: Option<StencilDepthTexture>,
+    pub multisampled: Option<MultisampledTexture>,
+}
+
+impl FrameAttachments {
+    // `settings` should already have gone through RenderSettings::supported
+    pub fn new(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        color_format: wgpu::TextureFormat,
+        settings: RenderSettings,
+    ) -> Self {
+        let mut attachments = Self {
+            settings,
+            color_format,
+            stencil_depth: None,
+            multisampled: None,
+        };
+        attachments.resize(device, width, height);
+        attachments
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        let samples = self.settings.sample_count;
+
+        self.stencil_depth = self
+            .settings
+            .depth_format
+            .map(|format| StencilDepthTexture::with_format(device, width, height, format, samples));
+
+        self.multisampled = (samples > 1)
+            .then(|| MultisampledTexture::new(device, width, height, self.color_format, samples));
+    }
+
+    // With MSAA on the pass draws into the multisampled texture and resolves into `view`
+    pub fn target<'a>(&'a self, view: &'a wgpu::TextureView) -> FrameTarget<'a> {
+        match &self.multisampled {
+            Some(msaa) => FrameTarget {
+                color: &msaa.view,
+                resolve: Some(view),
+                depth: self.stencil_depth.as_ref(),
+            },
+            None => FrameTarget {
+                color: view,
+                resolve: None,
+                depth: self.stencil_depth.as_ref(),
+            },
+        }
+    }
+}
+
+// Everything a frame gets drawn into
+pub struct FrameTarget<'a> {
+    pub color: &'a wgpu::TextureView,
+    // the single sampled view the multisampled `color` gets resolved into
+    pub resolve: Option<&'a wgpu::TextureView>,
+    pub depth: Option<&'a StencilDepthTexture>,
+}
+
+// Anything that records draw calls into the frame's render pass. They all share one pass so every
+// pipeline has to be made with the same depth format and sample count as the FrameAttachments.
+pub trait Draw {
+    fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>);
+}
+
+impl Draw for VertexShaders {
+    fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        render_pass.set_bind_group(0, Some(&self.bind_group), &[]);
+
+        self.draw_meshes(render_pass);
+    }
+}
+
+// Records the frame into `target`, `layers` are drawn in order so the scene goes first and the UI last.
+// This does not care where the views come from, the window's swapchain and an OffscreenTexture both go
+// through here so the headless path draws exactly what the window draws.
+pub fn draw_scene(encoder: &mut wgpu::CommandEncoder, target: &FrameTarget, layers: &[&dyn Draw]) {
+    // Now we can get to clearing the screen a long time coming. We need to use the encoder to create a RenderPass. The RenderPass has all the methods for the actual drawing.
+    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some("Render Pass"),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view: target.color,
+            resolve_target: target.resolve,
+            ops: wgpu::Operations {
+                // THIS IS JUST FOR THE BACKGROUND COLOR
+                load: wgpu::LoadOp::Clear(wgpu::Color {
+                    r: 0.1,
+                    g: 0.2,
+                    b: 1.0,
+                    a: 1.0,
+                }),
+                // the multisampled texture isn't needed after it is resolved
+                store: if target.resolve.is_some() {
+                    wgpu::StoreOp::Discard
+                } else {
+                    wgpu::StoreOp::Store
+                },
+            },
+        })],
+        depth_stencil_attachment: target.depth.map(|depth| depth.attachment()),
+        occlusion_query_set: None,
+        timestamp_writes: None,
+    });
+
+    for layer in layers {
+        layer.draw(&mut render_pass);
+    }
+}
@@ -0,0 +1,291 @@
+use crate::camera::camera::{CameraBuffer, CameraMatrix, Projection};
+use crate::geometry::rect::Rect;
+use crate::render::painter::{Color, Painter, Primitive};
+use crate::render::render::{Draw, RenderSettings};
+use anyhow::Result;
+use crate::shaders::bind_group::BindGrouping;
+use crate::shaders::buffers::DynamicBuffer;
+use crate::shaders::pipeline::{PipelineBuilder, PipelineCache};
+use crate::shaders::source::{ShaderFile, ShaderPipeline};
+use crate::text::atlas::GlyphAtlas;
+use crate::text::text::layout_text;
+
+// One corner of a shape's or glyph's quad. The whole shape description rides along on every corner so a
+// frame of mixed rects, ellipses and text is still just one vertex buffer and one draw call.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct UiVertex {
+    pub position: [f32; 2],
+    // position relative to the center of the shape, the SDF is evaluated in this space
+    pub local: [f32; 2],
+    pub half_size: [f32; 2],
+    // corner radius, stroke width, kind (0 rect, 1 ellipse, 2 glyph), unused
+    pub params: [f32; 4],
+    pub fill: [f32; 4],
+    pub stroke: [f32; 4],
+    // where in the glyph atlas, only used by glyphs
+    pub uv: [f32; 2],
+}
+
+impl UiVertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 7] = wgpu::vertex_attr_array![
+        0 => Float32x2,
+        1 => Float32x2,
+        2 => Float32x2,
+        3 => Float32x4,
+        4 => Float32x4,
+        5 => Float32x4,
+        6 => Float32x2,
+    ];
+
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<UiVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+// Draws a Painter's display list on top of the scene with a pixel space camera.
+// Every primitive is a single quad, ui.wgsl cuts the shape out with a distance field so edges get
+// anti aliased without any tessellation. The whole frame is one draw call.
+pub struct UiRenderer {
+    pub render_pipeline: ShaderPipeline,
+    bind_group: wgpu::BindGroup,
+    atlas: GlyphAtlas,
+    atlas_bind_group: wgpu::BindGroup,
+    camera: CameraMatrix,
+    camera_buffer: CameraBuffer,
+    vertex_buffer: DynamicBuffer,
+    index_buffer: DynamicBuffer,
+    num_indices: u32,
+    batch: ShapeBatch,
+    // the painter's colors are sRGB, an sRGB target wants them linear
+    srgb_target: bool,
+}
+
+impl UiRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        pipelines: &mut PipelineCache,
+        format: wgpu::TextureFormat,
+        settings: &RenderSettings,
+        width: u32,
+        height: u32,
+        scale_factor: f32,
+    ) -> Result<Self> {
+        let file = ShaderFile::UI;
+        let camera = CameraMatrix::pixel_space(width, height, scale_factor);
+        let camera_buffer = CameraBuffer::new(device, &camera);
+        let atlas = GlyphAtlas::new(device);
+
+        // see VertexShaders::new, a ui.wgsl on disk that doesn't build falls back to the embedded copy
+        let (bg, atlas_bg, render_pipeline) = file.build(|source| {
+            let reflection = &pipelines.shader(device, file.name(), &source)?.reflection;
+            let bg = BindGrouping::builder("UI Camera")
+                .uniform(0, wgpu::ShaderStages::VERTEX, &camera_buffer.buffer)
+                .build_checked(device, reflection, 0)?;
+            let atlas_bg = BindGrouping::builder("UI Glyph Atlas")
+                .color_texture(0, 1, wgpu::ShaderStages::FRAGMENT, device, &atlas.texture)
+                .build_checked(device, reflection, 1)?;
+
+            // y is flipped in pixel space so the winding flips too, nothing is culled.
+            // UI goes on top of the scene no matter what is in the depth buffer and doesn't write to it.
+            let render_pipeline = ShaderPipeline::new(
+                device,
+                pipelines,
+                file,
+                PipelineBuilder::new(file.name(), source)
+                    .vertex_layout(UiVertex::desc())
+                    .bind_group_layout(&bg.bind_group.0)
+                    .bind_group_layout(&atlas_bg.bind_group.0)
+                    // ui.wgsl outputs premultiplied alpha
+                    .blend(Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING))
+                    .render_settings(settings, false, wgpu::CompareFunction::Always)
+                    .target(format),
+            )?;
+            Ok((bg, atlas_bg, render_pipeline))
+        })?;
+
+        Ok(Self {
+            render_pipeline,
+            bind_group: bg.bind_group.1,
+            atlas,
+            atlas_bind_group: atlas_bg.bind_group.1,
+            camera,
+            camera_buffer,
+            vertex_buffer: DynamicBuffer::vertex("UI Vertices"),
+            index_buffer: DynamicBuffer::index("UI Indices"),
+            num_indices: 0,
+            batch: ShapeBatch::default(),
+            srgb_target: format.is_srgb(),
+        })
+    }
+
+    // physical size of the target, the camera works out the logical size from the scale factor
+    pub fn resize(&mut self, width: u32, height: u32, scale_factor: f32) {
+        self.camera.resize(width, height, scale_factor);
+    }
+
+    // Turns the painter's primitives into this frame's vertices and writes them into the buffers.
+    // The buffers are kept between frames and only get replaced when the UI outgrows them.
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, painter: &Painter) {
+        self.camera_buffer.update(queue, &self.camera);
+
+        // a full atlas gets emptied and this frame's glyphs packed again from scratch
+        if !self.build_batch(queue, painter) {
+            self.atlas.clear();
+            if !self.build_batch(queue, painter) {
+                eprintln!("Glyph atlas is too small for this frame's text, some glyphs are missing");
+            }
+        }
+
+        self.num_indices = self.batch.indices.len() as u32;
+        if self.batch.indices.is_empty() {
+            return;
+        }
+
+        self.vertex_buffer.write(device, queue, bytemuck::cast_slice(&self.batch.vertices));
+        self.index_buffer.write(device, queue, bytemuck::cast_slice(&self.batch.indices));
+    }
+
+    // false if some glyph didn't fit in the atlas
+    fn build_batch(&mut self, queue: &wgpu::Queue, painter: &Painter) -> bool {
+        let mut complete = true;
+        self.batch.clear();
+        for primitive in painter.primitives() {
+            match primitive {
+                Primitive::Rect { rect, corner_radius, fill, stroke } => {
+                    self.batch.push(*rect, ShapeKind::Rect, *corner_radius, self.color(*fill), stroke.width, self.color(stroke.color))
+                }
+                Primitive::Ellipse { rect, fill, stroke } => {
+                    self.batch.push(*rect, ShapeKind::Ellipse, 0.0, self.color(*fill), stroke.width, self.color(stroke.color))
+                }
+                Primitive::Text { rect, text, style } => {
+                    let color = self.color(style.color);
+                    for glyph in layout_text(text, style, *rect, self.scale_factor()) {
+                        match self.atlas.insert(queue, &style.font, glyph.key) {
+                            Some(region) => self.batch.push_glyph(glyph.rect, self.atlas.uv(region), color),
+                            None => complete = false,
+                        }
+                    }
+                }
+            }
+        }
+        complete
+    }
+
+    fn scale_factor(&self) -> f32 {
+        match self.camera.mode {
+            Projection::Pixels { scale_factor } => scale_factor,
+            Projection::Perspective => 1.0,
+        }
+    }
+
+    fn color(&self, color: Color) -> [f32; 4] {
+        if self.srgb_target {
+            color.to_linear().to_array()
+        } else {
+            color.to_array()
+        }
+    }
+}
+
+impl Draw for UiRenderer {
+    fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        let (Some(vertices), Some(indices)) = (self.vertex_buffer.slice(), self.index_buffer.slice()) else {
+            return;
+        };
+        if self.num_indices == 0 {
+            return;
+        }
+
+        render_pass.set_pipeline(&self.render_pipeline.pipeline);
+        render_pass.set_bind_group(0, Some(&self.bind_group), &[]);
+        render_pass.set_bind_group(1, Some(&self.atlas_bind_group), &[]);
+        render_pass.set_vertex_buffer(0, vertices);
+        render_pass.set_index_buffer(indices, wgpu::IndexFormat::Uint32);
+        render_pass.draw_indexed(0..self.num_indices, 0, 0..1);
+    }
+}
+
+// has to match the KIND_ constants in ui.wgsl
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ShapeKind {
+    Rect = 0,
+    Ellipse = 1,
+    Glyph = 2,
+}
+
+// the quad is a bit bigger than the shape so the soft edge isn't cut off
+const AA_PADDING: f32 = 1.0;
+
+#[derive(Default)]
+struct ShapeBatch {
+    vertices: Vec<UiVertex>,
+    indices: Vec<u32>,
+}
+
+impl ShapeBatch {
+    fn clear(&mut self) {
+        self.vertices.clear();
+        self.indices.clear();
+    }
+
+    fn push(&mut self, rect: Rect, kind: ShapeKind, corner_radius: f32, fill: [f32; 4], stroke_width: f32, stroke: [f32; 4]) {
+        if rect.width <= 0.0 || rect.height <= 0.0 {
+            return;
+        }
+        let stroke_width = stroke_width.max(0.0);
+        // nothing would show up
+        if fill[3] <= 0.0 && (stroke_width == 0.0 || stroke[3] <= 0.0) {
+            return;
+        }
+
+        let center = rect.center();
+        let half_size = [rect.width * 0.5, rect.height * 0.5];
+        let corner_radius = corner_radius.clamp(0.0, half_size[0].min(half_size[1]));
+        let params = [corner_radius, stroke_width, kind as u32 as f32, 0.0];
+
+        let start = self.vertices.len() as u32;
+        for (sx, sy) in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)] {
+            let local = [sx * (half_size[0] + AA_PADDING), sy * (half_size[1] + AA_PADDING)];
+            self.vertices.push(UiVertex {
+                position: [center.x + local[0], center.y + local[1]],
+                local,
+                half_size,
+                params,
+                fill,
+                stroke,
+                uv: [0.0, 0.0],
+            });
+        }
+        self.indices.extend_from_slice(&[start, start + 1, start + 2, start, start + 2, start + 3]);
+    }
+
+    // `uv` is (u0, v0, u1, v1), the quad is exactly the glyph bitmap so there is no padding here
+    fn push_glyph(&mut self, rect: Rect, uv: [f32; 4], color: [f32; 4]) {
+        let params = [0.0, 0.0, ShapeKind::Glyph as u32 as f32, 0.0];
+        let corners = [
+            (rect.x, rect.y, uv[0], uv[1]),
+            (rect.right(), rect.y, uv[2], uv[1]),
+            (rect.right(), rect.bottom(), uv[2], uv[3]),
+            (rect.x, rect.bottom(), uv[0], uv[3]),
+        ];
+
+        let start = self.vertices.len() as u32;
+        for (x, y, u, v) in corners {
+            self.vertices.push(UiVertex {
+                position: [x, y],
+                local: [0.0, 0.0],
+                half_size: [0.0, 0.0],
+                params,
+                fill: color,
+                stroke: [0.0; 4],
+                uv: [u, v],
+            });
+        }
+        self.indices.extend_from_slice(&[start, start + 1, start + 2, start, start + 2, start + 3]);
+    }
+}
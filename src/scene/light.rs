@@ -0,0 +1,136 @@
+use nalgebra::{Matrix4, Point3, Vector3, Vector4};
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LightKind {
+    // like the sun, shines down the node's -z everywhere in the scene
+    Directional,
+    // from the node's position, fades out to nothing at `range`
+    Point { range: f32 },
+}
+
+// Something a scene node can carry to light the meshes, see Attachment::Light
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Light {
+    pub kind: LightKind,
+    // linear rgb
+    pub color: [f32; 3],
+    pub intensity: f32,
+}
+
+impl Light {
+    pub fn directional(color: [f32; 3], intensity: f32) -> Self {
+        Self {
+            kind: LightKind::Directional,
+            color,
+            intensity,
+        }
+    }
+
+    pub fn point(color: [f32; 3], intensity: f32, range: f32) -> Self {
+        Self {
+            kind: LightKind::Point { range },
+            color,
+            intensity,
+        }
+    }
+
+    // `world` is the world matrix of the node the light is on
+    pub fn to_raw(&self, world: &Matrix4<f32>) -> LightRaw {
+        let [r, g, b] = self.color.map(|channel| channel * self.intensity);
+        match self.kind {
+            LightKind::Directional => {
+                // the shader wants the way to the light, the light itself points down -z
+                let to_light = (world * Vector4::z()).xyz().try_normalize(1e-12).unwrap_or_else(Vector3::z);
+                LightRaw {
+                    position: [to_light.x, to_light.y, to_light.z, 0.0],
+                    color: [r, g, b, 0.0],
+                }
+            }
+            LightKind::Point { range } => {
+                let position = world * Vector4::w();
+                LightRaw {
+                    position: [position.x, position.y, position.z, 1.0],
+                    color: [r, g, b, range],
+                }
+            }
+        }
+    }
+}
+
+// One light in the uniform. position.w is 0 for directional lights (xyz is the direction to the light)
+// and 1 for point lights, color.a is a point light's range.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LightRaw {
+    pub position: [f32; 4],
+    pub color: [f32; 4],
+}
+
+// `Lights` in shader.wgsl, the vec3 and the count share the first 16 bytes
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LightsUniform {
+    pub ambient: [f32; 3],
+    pub count: u32,
+    pub lights: [LightRaw; LightsUniform::MAX_LIGHTS],
+}
+
+impl LightsUniform {
+    // lights past this are left out
+    pub const MAX_LIGHTS: usize = 8;
+
+    pub fn new<'a>(ambient: [f32; 3], lights: impl IntoIterator<Item = (&'a Light, Matrix4<f32>)>) -> Self {
+        let mut uniform = Self {
+            ambient,
+            ..bytemuck::Zeroable::zeroed()
+        };
+        for (light, world) in lights.into_iter().take(Self::MAX_LIGHTS) {
+            uniform.lights[uniform.count as usize] = light.to_raw(&world);
+            uniform.count += 1;
+        }
+        uniform
+    }
+
+    // What a scene without lights gets: one white light from the upper right front, the light shader.wgsl
+    // had built in before there were lights
+    pub fn fallback(ambient: [f32; 3]) -> Self {
+        let light = Light::directional([1.0, 1.0, 1.0], 0.7);
+        // +z towards the light so it shines down -z at the origin
+        let world = Matrix4::face_towards(&Point3::origin(), &Point3::new(0.4, 0.7, 0.6), &Vector3::y());
+        Self::new(ambient, [(&light, world)])
+    }
+}
+
+// The GPU side of the scene's lights, rewritten only when something about them changed (see CameraBuffer)
+pub struct LightBuffer {
+    pub buffer: wgpu::Buffer,
+    uploaded: LightsUniform,
+}
+
+impl LightBuffer {
+    pub fn new(device: &wgpu::Device, lights: &LightsUniform) -> Self {
+        let buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("lights"),
+            contents: bytemuck::bytes_of(lights),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        Self { buffer, uploaded: *lights }
+    }
+
+    pub fn ambient(&self) -> [f32; 3] {
+        self.uploaded.ambient
+    }
+
+    // true if it wrote
+    pub fn update(&mut self, queue: &wgpu::Queue, lights: &LightsUniform) -> bool {
+        if *lights == self.uploaded {
+            return false;
+        }
+
+        queue.write_buffer(&self.buffer, 0, bytemuck::bytes_of(lights));
+        self.uploaded = *lights;
+        true
+    }
+}
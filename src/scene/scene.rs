@@ -0,0 +1,447 @@
+use anyhow::{Result, bail};
+use nalgebra::{Matrix4, Point3, UnitQuaternion, Vector3};
+use crate::scene::light::Light;
+
+// Where a node sits relative to its parent. Applied scale first, then rotation, then translation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    pub translation: Vector3<f32>,
+    pub rotation: UnitQuaternion<f32>,
+    pub scale: Vector3<f32>,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            translation: Vector3::zeros(),
+            rotation: UnitQuaternion::identity(),
+            scale: Vector3::new(1.0, 1.0, 1.0),
+        }
+    }
+}
+
+impl Transform {
+    pub fn at(translation: Vector3<f32>) -> Self {
+        Self {
+            translation,
+            ..Self::default()
+        }
+    }
+
+    // At `eye` with -z pointing at `target`, the way cameras and lights look
+    pub fn looking_at(eye: Point3<f32>, target: Point3<f32>, up: Vector3<f32>) -> Self {
+        Self {
+            translation: eye.coords,
+            rotation: UnitQuaternion::face_towards(&(eye - target), &up),
+            ..Self::default()
+        }
+    }
+
+    // Splits a model matrix (a glTF node, ModelMesh::transform) back into TRS. Shear, from a non uniform scale
+    // under a rotation, has nowhere to go and gets lost. A mirrored matrix comes out with a negative x scale.
+    pub fn from_matrix(matrix: &Matrix4<f32>) -> Self {
+        let translation = matrix.fixed_view::<3, 1>(0, 3).into_owned();
+        let mut linear = matrix.fixed_view::<3, 3>(0, 0).into_owned();
+        let mut scale = Vector3::new(linear.column(0).norm(), linear.column(1).norm(), linear.column(2).norm());
+        for axis in 0..3 {
+            if scale[axis] > 0.0 {
+                let mut column = linear.column_mut(axis);
+                column /= scale[axis];
+            }
+        }
+        if linear.determinant() < 0.0 {
+            scale.x = -scale.x;
+            linear.column_mut(0).neg_mut();
+        }
+
+        Self {
+            translation,
+            rotation: UnitQuaternion::from_matrix(&linear),
+            scale,
+        }
+    }
+
+    pub fn with_rotation(mut self, rotation: UnitQuaternion<f32>) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    pub fn with_scale(mut self, scale: Vector3<f32>) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn matrix(&self) -> Matrix4<f32> {
+        Matrix4::new_translation(&self.translation) * self.rotation.to_homogeneous() * Matrix4::new_nonuniform_scaling(&self.scale)
+    }
+}
+
+// A mesh the renderer knows about, handed out by VertexShaders::add_mesh (WindowState / HeadlessState::add_mesh)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MeshId(pub(crate) usize);
+
+// What a node brings into the scene besides its place in the hierarchy
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Attachment {
+    // drawn with the node's world matrix, `color` multiplies the mesh like Instance::color does
+    Mesh { mesh: MeshId, color: [f32; 4] },
+    // The view comes from the first camera in the graph, looking down its -z. The projection stays the
+    // window's CameraMatrix one.
+    Camera,
+    Light(Light),
+}
+
+impl Attachment {
+    pub fn mesh(mesh: MeshId) -> Self {
+        Self::Mesh {
+            mesh,
+            color: [1.0, 1.0, 1.0, 1.0],
+        }
+    }
+}
+
+// Handed out by SceneGraph::add, stays valid until the node is removed. The generation changes when a
+// slot is reused, so an id for a removed node never points at whatever took its place.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeId {
+    index: u32,
+    generation: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node {
+    pub name: String,
+    transform: Transform,
+    attachment: Option<Attachment>,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    // parent's world * transform, as of the last SceneGraph::update
+    world: Matrix4<f32>,
+    // the transform of this node or one above it changed since world was worked out
+    dirty: bool,
+    // in SceneGraph::changed already
+    changed: bool,
+}
+
+impl Node {
+    pub fn transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    pub fn attachment(&self) -> Option<&Attachment> {
+        self.attachment.as_ref()
+    }
+
+    pub fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+
+    pub fn children(&self) -> &[NodeId] {
+        &self.children
+    }
+
+    pub fn world(&self) -> &Matrix4<f32> {
+        &self.world
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Slot {
+    generation: u32,
+    node: Option<Node>,
+}
+
+// Nodes with a local Transform, parents and children. World matrices are cached and only worked out again
+// in update() for nodes that were moved or are under one that was. The renderer calls update() once a
+// frame and then picks up what changed with take_changed() / take_removed(), so a still scene costs a
+// walk over the nodes and no uploads.
+#[derive(Debug, Clone)]
+pub struct SceneGraph {
+    slots: Vec<Slot>,
+    free: Vec<u32>,
+    roots: Vec<NodeId>,
+    // linear rgb that every mesh gets on top of the lights
+    pub ambient: [f32; 3],
+    // some node is dirty, without it update() doesn't walk anything
+    dirty: bool,
+    // nodes whose world matrix or attachment changed since take_changed()
+    changed: Vec<NodeId>,
+    removed: Vec<NodeId>,
+}
+
+impl Default for SceneGraph {
+    fn default() -> Self {
+        Self {
+            slots: Vec::new(),
+            free: Vec::new(),
+            roots: Vec::new(),
+            ambient: [0.3, 0.3, 0.3],
+            dirty: false,
+            changed: Vec::new(),
+            removed: Vec::new(),
+        }
+    }
+}
+
+impl SceneGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // a node at the top of the hierarchy
+    pub fn add(&mut self, name: impl Into<String>, transform: Transform) -> NodeId {
+        let id = self.insert(name.into(), transform, None);
+        self.roots.push(id);
+        id
+    }
+
+    // None if `parent` was removed
+    pub fn add_child(&mut self, parent: NodeId, name: impl Into<String>, transform: Transform) -> Option<NodeId> {
+        self.get(parent)?;
+        let id = self.insert(name.into(), transform, Some(parent));
+        self.node_mut(parent)?.children.push(id);
+        Some(id)
+    }
+
+    // Removes the node and everything under it, false if it was already gone
+    pub fn remove(&mut self, id: NodeId) -> bool {
+        let Some(parent) = self.get(id).map(Node::parent) else {
+            return false;
+        };
+        match parent.and_then(|parent| self.node_mut(parent)) {
+            Some(parent) => parent.children.retain(|child| *child != id),
+            None => self.roots.retain(|root| *root != id),
+        }
+
+        let mut stack = vec![id];
+        while let Some(id) = stack.pop() {
+            let slot = &mut self.slots[id.index as usize];
+            if slot.generation == id.generation
+                && let Some(node) = slot.node.take()
+            {
+                slot.generation = slot.generation.wrapping_add(1);
+                stack.extend(node.children);
+                self.free.push(id.index);
+                self.removed.push(id);
+            }
+        }
+        true
+    }
+
+    // Moves `id` under `parent` (or to the top with None). It keeps its local transform, so it moves in
+    // the world along with its new parent.
+    pub fn set_parent(&mut self, id: NodeId, parent: Option<NodeId>) -> Result<()> {
+        let Some(old) = self.get(id).map(Node::parent) else {
+            bail!("node {:?} doesn't exist", id);
+        };
+        if let Some(parent) = parent {
+            if self.get(parent).is_none() {
+                bail!("node {:?} doesn't exist", parent);
+            }
+            // walking up from the new parent must not run into the node itself
+            let mut above = Some(parent);
+            while let Some(current) = above {
+                if current == id {
+                    bail!("can't put {:?} under {:?}, it's one of its own children", id, parent);
+                }
+                above = self.get(current).and_then(Node::parent);
+            }
+        }
+
+        match old.and_then(|old| self.node_mut(old)) {
+            Some(old) => old.children.retain(|child| *child != id),
+            None => self.roots.retain(|root| *root != id),
+        }
+        match parent.and_then(|parent| self.node_mut(parent)) {
+            Some(parent) => parent.children.push(id),
+            None => self.roots.push(id),
+        }
+        if let Some(node) = self.node_mut(id) {
+            node.parent = parent;
+        }
+        self.mark_dirty(id);
+        Ok(())
+    }
+
+    pub fn get(&self, id: NodeId) -> Option<&Node> {
+        let slot = self.slots.get(id.index as usize)?;
+        if slot.generation != id.generation {
+            return None;
+        }
+        slot.node.as_ref()
+    }
+
+    // false if the node was removed
+    pub fn set_transform(&mut self, id: NodeId, transform: Transform) -> bool {
+        match self.node_mut(id) {
+            Some(node) => {
+                node.transform = transform;
+                self.mark_dirty(id);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // replaces whatever was attached before, false if the node was removed
+    pub fn attach(&mut self, id: NodeId, attachment: Attachment) -> bool {
+        self.set_attachment(id, Some(attachment))
+    }
+
+    pub fn detach(&mut self, id: NodeId) -> Option<Attachment> {
+        let old = self.get(id)?.attachment;
+        self.set_attachment(id, None);
+        old
+    }
+
+    // as of the last update()
+    pub fn world(&self, id: NodeId) -> Option<&Matrix4<f32>> {
+        self.get(id).map(Node::world)
+    }
+
+    // Works out the world matrices of every dirty node and what's under it, parents before children
+    pub fn update(&mut self) {
+        if !self.dirty {
+            return;
+        }
+        self.dirty = false;
+
+        let mut stack: Vec<(NodeId, Matrix4<f32>, bool)> = self.roots.iter().rev().map(|root| (*root, Matrix4::identity(), false)).collect();
+        while let Some((id, parent_world, parent_dirty)) = stack.pop() {
+            let Some(node) = self.slots[id.index as usize].node.as_mut() else {
+                continue;
+            };
+            let dirty = node.dirty || parent_dirty;
+            if dirty {
+                node.world = parent_world * node.transform.matrix();
+                node.dirty = false;
+                if !node.changed {
+                    node.changed = true;
+                    self.changed.push(id);
+                }
+            }
+            stack.extend(node.children.iter().rev().map(|child| (*child, node.world, dirty)));
+        }
+    }
+
+    // Nodes that moved or got a different attachment since the last call, each once
+    pub fn take_changed(&mut self) -> Vec<NodeId> {
+        let changed = std::mem::take(&mut self.changed);
+        changed
+            .into_iter()
+            .filter(|id| match self.node_mut(*id) {
+                Some(node) if node.changed => {
+                    node.changed = false;
+                    true
+                }
+                _ => false,
+            })
+            .collect()
+    }
+
+    // Nodes removed since the last call
+    pub fn take_removed(&mut self) -> Vec<NodeId> {
+        std::mem::take(&mut self.removed)
+    }
+
+    // the first camera, depth first from the roots in the order they were added
+    pub fn camera(&self) -> Option<NodeId> {
+        self.walk().find(|(_, node)| node.attachment == Some(Attachment::Camera)).map(|(id, _)| id)
+    }
+
+    // the inverse of the camera's world matrix, None without a camera
+    pub fn view(&self) -> Option<Matrix4<f32>> {
+        self.camera().and_then(|camera| self.get(camera)?.world.try_inverse())
+    }
+
+    // every light with the world matrix of its node
+    pub fn lights(&self) -> impl Iterator<Item = (&Light, Matrix4<f32>)> {
+        self.walk().filter_map(|(_, node)| match &node.attachment {
+            Some(Attachment::Light(light)) => Some((light, node.world)),
+            _ => None,
+        })
+    }
+
+    // depth first, parents before their children
+    pub fn walk(&self) -> impl Iterator<Item = (NodeId, &Node)> {
+        let mut stack: Vec<NodeId> = self.roots.iter().rev().copied().collect();
+        std::iter::from_fn(move || {
+            let id = stack.pop()?;
+            let node = self.get(id)?;
+            stack.extend(node.children.iter().rev());
+            Some((id, node))
+        })
+    }
+
+    pub fn roots(&self) -> &[NodeId] {
+        &self.roots
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len() - self.free.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // every node shows up in take_removed()
+    pub fn clear(&mut self) {
+        for root in self.roots.clone() {
+            self.remove(root);
+        }
+    }
+
+    fn insert(&mut self, name: String, transform: Transform, parent: Option<NodeId>) -> NodeId {
+        let node = Node {
+            name,
+            transform,
+            attachment: None,
+            parent,
+            children: Vec::new(),
+            world: Matrix4::identity(),
+            dirty: true,
+            changed: false,
+        };
+        let id = match self.free.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+                slot.node = Some(node);
+                NodeId { index, generation: slot.generation }
+            }
+            None => {
+                self.slots.push(Slot { generation: 0, node: Some(node) });
+                NodeId { index: self.slots.len() as u32 - 1, generation: 0 }
+            }
+        };
+        self.dirty = true;
+        id
+    }
+
+    fn set_attachment(&mut self, id: NodeId, attachment: Option<Attachment>) -> bool {
+        let Some(node) = self.node_mut(id) else {
+            return false;
+        };
+        node.attachment = attachment;
+        if !node.changed {
+            node.changed = true;
+            self.changed.push(id);
+        }
+        true
+    }
+
+    fn mark_dirty(&mut self, id: NodeId) {
+        if let Some(node) = self.node_mut(id) {
+            node.dirty = true;
+            self.dirty = true;
+        }
+    }
+
+    fn node_mut(&mut self, id: NodeId) -> Option<&mut Node> {
+        let slot = self.slots.get_mut(id.index as usize)?;
+        if slot.generation != id.generation {
+            return None;
+        }
+        slot.node.as_mut()
+    }
+}
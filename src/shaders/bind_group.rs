@@ -1,64 +1,230 @@
-use wgpu::{BindGroup, BindGroupLayout, BindGroupEntry, BindGroupLayoutEntry, Buffer, BindGroupDescriptor};
+use wgpu::{BindGroup, BindGroupLayout, BindGroupEntry, BindGroupLayoutEntry, Buffer, BindGroupDescriptor, ShaderStages};
+use anyhow::{bail, Result};
+use crate::shaders::reflect::ShaderReflection;
+use crate::textures::textures::ColorTexture;
 
 pub struct BindGrouping {
     pub bind_group: (BindGroupLayout, BindGroup)
-
 }
 
 impl BindGrouping {
-   pub fn new(device: &wgpu::Device, buffer: &[Buffer]) -> Self{
-        let bg= BindingGroupSetup::new(buffer);
-
-            let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                label: Some("bind_group"),
-                entries: &bg.layout[..]
-            });
-
-            let bind_group = device.create_bind_group(&BindGroupDescriptor {
-                label: Some("Cam Bind Group"),
-                layout: &bind_group_layout,
-                entries: &bg.bg_entry[..]
-        });
-        Self {
-            bind_group: (bind_group_layout, bind_group)
-        }
-    } 
+    pub fn builder<'a>(label: &'a str) -> BindGroupBuilder<'a> {
+        BindGroupBuilder::new(label)
+    }
+
+    // A texture at binding 0 and its sampler at binding 1, for the fragment shader
+    //   @group(N) @binding(0) var t: texture_2d<f32>;
+    //   @group(N) @binding(1) var s: sampler;
+    pub fn texture(device: &wgpu::Device, texture: &ColorTexture) -> Result<Self> {
+        BindGroupBuilder::new("Texture Bind Group")
+            .color_texture(0, 1, ShaderStages::FRAGMENT, device, texture)
+            .build(device)
+    }
 }
 
-struct BindingGroupSetup<'a> {
+// Collects the layout entry and the resource for every binding of one @group, then makes both in build().
+//
+// let bg = BindGrouping::builder("camera + material")
+//     .uniform(0, ShaderStages::VERTEX, &camera_buffer)
+//     .uniform(1, ShaderStages::FRAGMENT, &material_buffer)
+//     .color_texture(2, 3, ShaderStages::FRAGMENT, device, &albedo)
+//     .build(device)?;
+pub struct BindGroupBuilder<'a> {
+    label: &'a str,
     layout: Vec<BindGroupLayoutEntry>,
-    bg_entry: Vec<BindGroupEntry<'a>>,
+    entries: Vec<BindGroupEntry<'a>>,
+    // bindings that were given more than once, build() turns the first one into the error
+    duplicates: Vec<u32>,
 }
 
-impl <'a> BindingGroupSetup<'a> {
-    fn new(buffer: &'a [Buffer]) -> Self {
-        let layout: Vec<_> = buffer.into_iter().enumerate().map(|(i, _b)|{
-            BindGroupLayoutEntry {
-            binding: i as u32,
-            visibility: wgpu::ShaderStages::VERTEX,
-            ty: wgpu::BindingType::Buffer {
-                ty: wgpu::BufferBindingType::Uniform,
-                has_dynamic_offset: false,
-                min_binding_size: None,
-        },
-        count: None, 
-            }
-        }).collect();
-
-       let bg_entry: Vec<_> = buffer.into_iter().enumerate().map(|(i, b)|{
-            BindGroupEntry {
-                binding: i as u32,
-                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
-                    buffer: b,
-                    offset: 0,
-                    size: None,
-                    }),
-                }
-        }).collect();
-
+impl<'a> BindGroupBuilder<'a> {
+    pub fn new(label: &'a str) -> Self {
         Self {
-            layout,
-            bg_entry
+            label,
+            layout: Vec::new(),
+            entries: Vec::new(),
+            duplicates: Vec::new(),
         }
     }
-}
\ No newline at end of file
+
+    // var<uniform>
+    pub fn uniform(self, binding: u32, visibility: ShaderStages, buffer: &'a Buffer) -> Self {
+        self.buffer(binding, visibility, buffer, wgpu::BufferBindingType::Uniform, None)
+    }
+
+    // var<uniform> bound with a dynamic offset, `size` is how much of the buffer one offset sees
+    pub fn uniform_dynamic(self, binding: u32, visibility: ShaderStages, buffer: &'a Buffer, size: u64) -> Self {
+        self.buffer(binding, visibility, buffer, wgpu::BufferBindingType::Uniform, Some(size))
+    }
+
+    // var<storage, read> when read_only, var<storage, read_write> otherwise
+    pub fn storage(self, binding: u32, visibility: ShaderStages, buffer: &'a Buffer, read_only: bool) -> Self {
+        self.buffer(binding, visibility, buffer, wgpu::BufferBindingType::Storage { read_only }, None)
+    }
+
+    pub fn storage_dynamic(self, binding: u32, visibility: ShaderStages, buffer: &'a Buffer, read_only: bool, size: u64) -> Self {
+        self.buffer(binding, visibility, buffer, wgpu::BufferBindingType::Storage { read_only }, Some(size))
+    }
+
+    // Any buffer binding. With `dynamic_size` the layout gets has_dynamic_offset and the offset is passed
+    // to set_bind_group every draw, the binding then covers `dynamic_size` bytes from that offset.
+    pub fn buffer(
+        self,
+        binding: u32,
+        visibility: ShaderStages,
+        buffer: &'a Buffer,
+        ty: wgpu::BufferBindingType,
+        dynamic_size: Option<u64>,
+    ) -> Self {
+        let size = dynamic_size.and_then(wgpu::BufferSize::new);
+        self.entry(
+            binding,
+            visibility,
+            wgpu::BindingType::Buffer {
+                ty,
+                has_dynamic_offset: dynamic_size.is_some(),
+                min_binding_size: size,
+            },
+            wgpu::BindingResource::Buffer(wgpu::BufferBinding { buffer, offset: 0, size }),
+        )
+    }
+
+    // texture_2d<f32> (or the depth / int variant, whatever sample_type says)
+    pub fn texture(
+        self,
+        binding: u32,
+        visibility: ShaderStages,
+        view: &'a wgpu::TextureView,
+        sample_type: wgpu::TextureSampleType,
+        view_dimension: wgpu::TextureViewDimension,
+    ) -> Self {
+        self.entry(
+            binding,
+            visibility,
+            wgpu::BindingType::Texture {
+                sample_type,
+                view_dimension,
+                multisampled: false,
+            },
+            wgpu::BindingResource::TextureView(view),
+        )
+    }
+
+    pub fn sampler(self, binding: u32, visibility: ShaderStages, sampler: &'a wgpu::Sampler, ty: wgpu::SamplerBindingType) -> Self {
+        self.entry(binding, visibility, wgpu::BindingType::Sampler(ty), wgpu::BindingResource::Sampler(sampler))
+    }
+
+    // texture_storage_2d<format, access>, for compute shaders writing images
+    pub fn storage_texture(
+        self,
+        binding: u32,
+        visibility: ShaderStages,
+        view: &'a wgpu::TextureView,
+        format: wgpu::TextureFormat,
+        access: wgpu::StorageTextureAccess,
+    ) -> Self {
+        self.entry(
+            binding,
+            visibility,
+            wgpu::BindingType::StorageTexture {
+                access,
+                format,
+                view_dimension: wgpu::TextureViewDimension::D2,
+            },
+            wgpu::BindingResource::TextureView(view),
+        )
+    }
+
+    // A ColorTexture and its own sampler. Formats that can't be filtered (R32Float without the feature
+    // and so on) get a non filtering sampler binding.
+    pub fn color_texture(
+        self,
+        texture_binding: u32,
+        sampler_binding: u32,
+        visibility: ShaderStages,
+        device: &wgpu::Device,
+        texture: &'a ColorTexture,
+    ) -> Self {
+        let filterable = matches!(
+            texture.format.sample_type(None, Some(device.features())),
+            Some(wgpu::TextureSampleType::Float { filterable: true })
+        );
+        let sampler_type = if filterable {
+            wgpu::SamplerBindingType::Filtering
+        } else {
+            wgpu::SamplerBindingType::NonFiltering
+        };
+
+        self.texture(
+            texture_binding,
+            visibility,
+            &texture.view,
+            wgpu::TextureSampleType::Float { filterable },
+            wgpu::TextureViewDimension::D2,
+        )
+        .sampler(sampler_binding, visibility, &texture.sampler, sampler_type)
+    }
+
+    // fails if a binding number was used twice, wgpu would only say the layout is invalid, this says which binding
+    pub fn build(self, device: &wgpu::Device) -> Result<BindGrouping> {
+        self.check_duplicates()?;
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some(self.label),
+            entries: &self.layout,
+        });
+
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some(self.label),
+            layout: &bind_group_layout,
+            entries: &self.entries,
+        });
+
+        Ok(BindGrouping {
+            bind_group: (bind_group_layout, bind_group)
+        })
+    }
+
+    // build() after checking the entries against what `shader` declares for @group(group), so a wrong
+    // binding is an error naming it here instead of a validation panic when the pipeline is made
+    pub fn build_checked(self, device: &wgpu::Device, shader: &ShaderReflection, group: u32) -> Result<BindGrouping> {
+        self.check_duplicates()?;
+        let buffer_sizes: Vec<(u32, u64)> = self
+            .entries
+            .iter()
+            .filter_map(|entry| match &entry.resource {
+                wgpu::BindingResource::Buffer(binding) => Some((
+                    entry.binding,
+                    binding.size.map(|size| size.get()).unwrap_or(binding.buffer.size() - binding.offset),
+                )),
+                _ => None,
+            })
+            .collect();
+        shader.check_bind_group(group, &self.layout, &buffer_sizes)?;
+        self.build(device)
+    }
+
+    fn check_duplicates(&self) -> Result<()> {
+        if let Some(binding) = self.duplicates.first() {
+            bail!("binding {} is used twice in bind group '{}'", binding, self.label);
+        }
+        Ok(())
+    }
+
+    // a binding that is already taken is only remembered, the builder chain can't fail part way
+    fn entry(mut self, binding: u32, visibility: ShaderStages, ty: wgpu::BindingType, resource: wgpu::BindingResource<'a>) -> Self {
+        if self.layout.iter().any(|entry| entry.binding == binding) {
+            self.duplicates.push(binding);
+            return self;
+        }
+
+        self.layout.push(BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty,
+            count: None,
+        });
+        self.entries.push(BindGroupEntry { binding, resource });
+        self
+    }
+}
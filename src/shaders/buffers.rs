@@ -1,10 +1,13 @@
 use wgpu::util::{BufferInitDescriptor, DeviceExt};
 use bytemuck::{Pod, cast_slice};
 
+// Buffers made once with their contents and never written again (mesh data). Anything that changes goes
+// in a DynamicBuffer instead, dynamic() makes one with the same contents.
 pub enum BufferTypes<'a, T: Pod> {
     VertexBuffer(&'a [T]),
     IndexBuffer(&'a [T]),
     UniformBuffer(&'a [T]),
+    StorageBuffer(&'a [T]),
 }
 
 impl<'a, T: Pod> BufferTypes<'a, T> {
@@ -32,6 +35,240 @@ impl<'a, T: Pod> BufferTypes<'a, T> {
                     usage: wgpu::BufferUsages::UNIFORM,
                 })
             }
+            BufferTypes::StorageBuffer(contents) => {
+                device.create_buffer_init(&BufferInitDescriptor {
+                    label,
+                    contents: cast_slice(contents),
+                    usage: wgpu::BufferUsages::STORAGE,
+                })
+            }
+        }
+    }
+
+    // the same contents in a buffer that can be written again and grow
+    pub fn dynamic(&self, label: &str, device: &wgpu::Device, queue: &wgpu::Queue) -> DynamicBuffer {
+        let (mut buffer, contents) = match self {
+            BufferTypes::VertexBuffer(contents) => (DynamicBuffer::vertex(label), contents),
+            BufferTypes::IndexBuffer(contents) => (DynamicBuffer::index(label), contents),
+            BufferTypes::UniformBuffer(contents) => (DynamicBuffer::uniform(label), contents),
+            BufferTypes::StorageBuffer(contents) => (DynamicBuffer::storage(label), contents),
+        };
+        buffer.write(device, queue, cast_slice(contents));
+        buffer
+    }
+}
+
+// A buffer that gets rewritten every frame (or whenever), for batched UI vertices, streamed geometry,
+// instance data. It only gets replaced by a bigger one (the next power of two) when something written
+// doesn't fit, so a steady frame writes into the same buffer forever. COPY_DST is always added to `usage`.
+pub struct DynamicBuffer {
+    label: String,
+    usage: wgpu::BufferUsages,
+    buffer: Option<wgpu::Buffer>,
+    // bytes written so far, see slice()
+    len: wgpu::BufferAddress,
+}
+
+impl DynamicBuffer {
+    // no GPU buffer until the first write or with_capacity
+    pub fn new(label: impl Into<String>, usage: wgpu::BufferUsages) -> Self {
+        Self {
+            label: label.into(),
+            usage: usage | wgpu::BufferUsages::COPY_DST,
+            buffer: None,
+            len: 0,
+        }
+    }
+
+    pub fn vertex(label: impl Into<String>) -> Self {
+        Self::new(label, wgpu::BufferUsages::VERTEX)
+    }
+
+    pub fn index(label: impl Into<String>) -> Self {
+        Self::new(label, wgpu::BufferUsages::INDEX)
+    }
+
+    pub fn uniform(label: impl Into<String>) -> Self {
+        Self::new(label, wgpu::BufferUsages::UNIFORM)
+    }
+
+    pub fn storage(label: impl Into<String>) -> Self {
+        Self::new(label, wgpu::BufferUsages::STORAGE)
+    }
+
+    // Makes the buffer right away, for uniform / storage buffers that have to go in a bind group before
+    // anything is written to them
+    pub fn with_capacity(mut self, device: &wgpu::Device, capacity: wgpu::BufferAddress) -> Self {
+        self.reserve(device, capacity);
+        self
+    }
+
+    // Makes sure `size` bytes fit. Returns true if that took a new buffer, what was in the old one is gone
+    // and bind groups made with it have to be made again.
+    pub fn reserve(&mut self, device: &wgpu::Device, size: wgpu::BufferAddress) -> bool {
+        if self.buffer.as_ref().is_some_and(|buffer| buffer.size() >= size) {
+            return false;
+        }
+        self.buffer = Some(device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(&self.label),
+            size: size.next_power_of_two().max(wgpu::COPY_BUFFER_ALIGNMENT),
+            usage: self.usage,
+            mapped_at_creation: false,
+        }));
+        self.len = 0;
+        true
+    }
+
+    // Replaces the contents with `data`, growing first if it doesn't fit. Returns true if the buffer was
+    // replaced (see reserve).
+    pub fn write(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, data: &[u8]) -> bool {
+        let grew = self.reserve(device, data.len() as wgpu::BufferAddress);
+        if let Some(buffer) = &self.buffer {
+            write_aligned(queue, buffer, 0, data);
+        }
+        self.len = data.len() as wgpu::BufferAddress;
+        grew
+    }
+
+    // Rewrites part of what's there without ever growing, false (and nothing written) if it doesn't fit.
+    // `offset` has to be a multiple of 4.
+    pub fn write_at(&mut self, queue: &wgpu::Queue, offset: wgpu::BufferAddress, data: &[u8]) -> bool {
+        let end = offset + data.len() as wgpu::BufferAddress;
+        match &self.buffer {
+            Some(buffer) if buffer.size() >= end => {
+                write_aligned(queue, buffer, offset, data);
+                self.len = self.len.max(end);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn buffer(&self) -> Option<&wgpu::Buffer> {
+        self.buffer.as_ref()
+    }
+
+    // the part that has been written, what set_vertex_buffer / set_index_buffer want. None when empty.
+    pub fn slice(&self) -> Option<wgpu::BufferSlice<'_>> {
+        match &self.buffer {
+            Some(buffer) if self.len > 0 => Some(buffer.slice(..self.len)),
+            _ => None,
         }
     }
+
+    pub fn len(&self) -> wgpu::BufferAddress {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // how big the buffer is, 0 before it's made
+    pub fn capacity(&self) -> wgpu::BufferAddress {
+        self.buffer.as_ref().map_or(0, wgpu::Buffer::size)
+    }
+}
+
+// write_buffer wants a multiple of 4 bytes, u16 indices or odd sized structs get padded with zeros. The
+// padding always fits, buffers are a power of two long and offsets a multiple of 4.
+fn write_aligned(queue: &wgpu::Queue, buffer: &wgpu::Buffer, offset: wgpu::BufferAddress, data: &[u8]) {
+    if data.is_empty() {
+        return;
+    }
+    if data.len().is_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT as usize) {
+        queue.write_buffer(buffer, offset, data);
+    } else {
+        let mut padded = data.to_vec();
+        padded.resize(data.len().next_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT as usize), 0);
+        queue.write_buffer(buffer, offset, &padded);
+    }
+}
+
+// Uniforms that change every draw (per-widget clip rects, per-object material values) out of one buffer.
+// Each push() gets its own slot at the device's min_uniform_buffer_offset_alignment (256 on most GPUs)
+// and hands back the dynamic offset to pass to set_bind_group. Everything pushed is written in one go by
+// upload(), and reset() starts over from the front for the next frame. write_buffer lands before the next
+// submit, so the previous frame's draws have already read what gets overwritten.
+//
+// upload() can replace the buffer, so nothing may be recorded with an offset before it has run: push the
+// whole frame, upload, make the bind group again if it grew, and only then record the draws.
+//
+// let mut ring = UniformRing::<DrawUniform>::new(device, "draws", 64);
+// let make_bg = |ring: &UniformRing<DrawUniform>| BindGrouping::builder("draws")
+//     .uniform_dynamic(0, ShaderStages::VERTEX, ring.buffer(), ring.binding_size())
+//     .build(device);
+// let mut bg = make_bg(&ring)?;
+// ...
+// ring.reset();
+// let offsets: Vec<u32> = uniforms.iter().map(|uniform| ring.push(uniform)).collect();
+// if ring.upload(device, queue) {
+//     bg = make_bg(&ring)?;
+// }
+// for offset in offsets {
+//     render_pass.set_bind_group(1, &bg.bind_group.1, &[offset]);
+//     ...
+// }
+pub struct UniformRing<T: Pod> {
+    buffer: DynamicBuffer,
+    // this frame's values, each padded to `stride`
+    staging: Vec<u8>,
+    stride: wgpu::BufferAddress,
+    _values: std::marker::PhantomData<T>,
+}
+
+impl<T: Pod> UniformRing<T> {
+    // room for `capacity` values to start with, more makes upload() grow the buffer
+    pub fn new(device: &wgpu::Device, label: &str, capacity: u64) -> Self {
+        let alignment = device.limits().min_uniform_buffer_offset_alignment as wgpu::BufferAddress;
+        let stride = (std::mem::size_of::<T>() as wgpu::BufferAddress).max(1).next_multiple_of(alignment);
+        Self {
+            buffer: DynamicBuffer::uniform(label).with_capacity(device, stride * capacity.max(1)),
+            staging: Vec::new(),
+            stride,
+            _values: std::marker::PhantomData,
+        }
+    }
+
+    // the start of a frame, offsets handed out before don't mean anything afterwards
+    pub fn reset(&mut self) {
+        self.staging.clear();
+    }
+
+    // the dynamic offset of `value`, it reaches the GPU with the next upload()
+    pub fn push(&mut self, value: &T) -> u32 {
+        let offset = self.staging.len();
+        self.staging.extend_from_slice(bytemuck::bytes_of(value));
+        self.staging.resize(offset + self.stride as usize, 0);
+        offset as u32
+    }
+
+    // Writes everything pushed since reset(). Returns true if the buffer had to grow, bind groups made with
+    // buffer() have to be made again before they're used.
+    pub fn upload(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) -> bool {
+        self.buffer.write(device, queue, &self.staging)
+    }
+
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        self.buffer.buffer().expect("UniformRing makes its buffer in new()")
+    }
+
+    // how much of the buffer one offset sees, for BindGroupBuilder::uniform_dynamic. WGSL rounds the size of a
+    // uniform struct up to 16 bytes, a smaller binding fails the layout check.
+    pub fn binding_size(&self) -> u64 {
+        (std::mem::size_of::<T>() as u64).max(1).next_multiple_of(16)
+    }
+
+    // distance between two offsets
+    pub fn stride(&self) -> wgpu::BufferAddress {
+        self.stride
+    }
+
+    pub fn len(&self) -> usize {
+        self.staging.len() / self.stride as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.staging.is_empty()
+    }
 }
@@ -1,3 +1,8 @@
 pub mod shader;
 pub mod buffers;
-pub mod bind_group;
\ No newline at end of file
+pub mod bind_group;
+pub mod reflect;
+pub mod pipeline;
+pub mod source;
+#[cfg(feature = "hot-reload")]
+pub mod watcher;
@@ -0,0 +1,334 @@
+use std::collections::HashMap;
+use anyhow::{Result, bail};
+use crate::render::render::RenderSettings;
+use crate::shaders::reflect::ShaderReflection;
+use crate::textures::textures::depth_stencil_state;
+
+// An owned VertexBufferLayout so it can be part of the cache key
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct VertexLayout {
+    pub array_stride: wgpu::BufferAddress,
+    pub step_mode: wgpu::VertexStepMode,
+    pub attributes: Vec<wgpu::VertexAttribute>,
+}
+
+impl From<wgpu::VertexBufferLayout<'_>> for VertexLayout {
+    fn from(layout: wgpu::VertexBufferLayout<'_>) -> Self {
+        Self {
+            array_stride: layout.array_stride,
+            step_mode: layout.step_mode,
+            attributes: layout.attributes.to_vec(),
+        }
+    }
+}
+
+impl VertexLayout {
+    pub fn desc(&self) -> wgpu::VertexBufferLayout<'_> {
+        wgpu::VertexBufferLayout {
+            array_stride: self.array_stride,
+            step_mode: self.step_mode,
+            attributes: &self.attributes,
+        }
+    }
+}
+
+// Everything that decides what comes out of create_render_pipeline. Two builders with the same descriptor
+// get the same pipeline out of the PipelineCache, the label isn't part of it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PipelineDescriptor {
+    // WGSL source
+    pub shader: String,
+    pub vertex_entry: String,
+    // None for depth only pipelines (shadow maps, depth pre-pass)
+    pub fragment_entry: Option<String>,
+    pub vertex_layouts: Vec<VertexLayout>,
+    // empty means the layouts come from the shader's reflection, see ShaderReflection::create_bind_group_layouts
+    pub bind_group_layouts: Vec<wgpu::BindGroupLayout>,
+    pub primitive: wgpu::PrimitiveState,
+    pub depth_stencil: Option<wgpu::DepthStencilState>,
+    pub multisample: wgpu::MultisampleState,
+    // used for every target, None is the same as BlendState::REPLACE
+    pub blend: Option<wgpu::BlendState>,
+    pub targets: Vec<wgpu::TextureFormat>,
+}
+
+// Sets up one render pipeline. The defaults are what most pipelines want: vs_main / fs_main, triangle list,
+// counter clockwise front faces, no culling, no blending, no depth and no MSAA.
+//
+// let opaque = PipelineBuilder::new("Scene", include_str!("shader.wgsl"))
+//     .vertex_layout(Vertex::desc())
+//     .bind_group_layout(&camera_bg.bind_group.0)
+//     .cull_mode(Some(wgpu::Face::Back))
+//     .render_settings(&settings, true, wgpu::CompareFunction::Less)
+//     .target(format);
+// let pipeline = pipelines.get(device, opaque.clone())?;
+// let wireframe = pipelines.get(device, opaque.polygon_mode(wgpu::PolygonMode::Line))?;
+#[derive(Debug, Clone)]
+pub struct PipelineBuilder {
+    pub label: String,
+    pub descriptor: PipelineDescriptor,
+}
+
+impl PipelineBuilder {
+    pub fn new(label: &str, shader: impl Into<String>) -> Self {
+        Self {
+            label: label.to_string(),
+            descriptor: PipelineDescriptor {
+                shader: shader.into(),
+                vertex_entry: "vs_main".to_string(),
+                fragment_entry: Some("fs_main".to_string()),
+                vertex_layouts: Vec::new(),
+                bind_group_layouts: Vec::new(),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                blend: None,
+                targets: Vec::new(),
+            },
+        }
+    }
+
+    pub fn vertex_entry(mut self, name: &str) -> Self {
+        self.descriptor.vertex_entry = name.to_string();
+        self
+    }
+
+    pub fn fragment_entry(mut self, name: Option<&str>) -> Self {
+        self.descriptor.fragment_entry = name.map(str::to_string);
+        self
+    }
+
+    // one per vertex buffer slot, in set_vertex_buffer order
+    pub fn vertex_layout(mut self, layout: wgpu::VertexBufferLayout<'_>) -> Self {
+        self.descriptor.vertex_layouts.push(layout.into());
+        self
+    }
+
+    // in @group order
+    pub fn bind_group_layout(mut self, layout: &wgpu::BindGroupLayout) -> Self {
+        self.descriptor.bind_group_layouts.push(layout.clone());
+        self
+    }
+
+    pub fn topology(mut self, topology: wgpu::PrimitiveTopology) -> Self {
+        self.descriptor.primitive.topology = topology;
+        self
+    }
+
+    pub fn cull_mode(mut self, cull_mode: Option<wgpu::Face>) -> Self {
+        self.descriptor.primitive.cull_mode = cull_mode;
+        self
+    }
+
+    pub fn front_face(mut self, front_face: wgpu::FrontFace) -> Self {
+        self.descriptor.primitive.front_face = front_face;
+        self
+    }
+
+    // Line / Point need Features::POLYGON_MODE_LINE / POLYGON_MODE_POINT, build() says so if they're missing
+    pub fn polygon_mode(mut self, polygon_mode: wgpu::PolygonMode) -> Self {
+        self.descriptor.primitive.polygon_mode = polygon_mode;
+        self
+    }
+
+    pub fn blend(mut self, blend: Option<wgpu::BlendState>) -> Self {
+        self.descriptor.blend = blend;
+        self
+    }
+
+    pub fn depth_stencil(mut self, depth_stencil: Option<wgpu::DepthStencilState>) -> Self {
+        self.descriptor.depth_stencil = depth_stencil;
+        self
+    }
+
+    pub fn depth(self, format: wgpu::TextureFormat, write: bool, compare: wgpu::CompareFunction) -> Self {
+        self.depth_stencil(Some(depth_stencil_state(format, write, compare)))
+    }
+
+    pub fn sample_count(mut self, count: u32) -> Self {
+        self.descriptor.multisample.count = count;
+        self
+    }
+
+    // Pipelines drawn in draw_scene's pass have to match its attachments, this takes the depth format and
+    // sample count from the same settings the FrameAttachments were made with
+    pub fn render_settings(self, settings: &RenderSettings, depth_write: bool, depth_compare: wgpu::CompareFunction) -> Self {
+        self.depth_stencil(settings.depth_format.map(|format| depth_stencil_state(format, depth_write, depth_compare)))
+            .sample_count(settings.sample_count)
+    }
+
+    pub fn target(mut self, format: wgpu::TextureFormat) -> Self {
+        self.descriptor.targets.push(format);
+        self
+    }
+
+    // Makes the pipeline without caching it
+    pub fn build(self, device: &wgpu::Device) -> Result<wgpu::RenderPipeline> {
+        let shader = CachedShader::new(device, &self.label, &self.descriptor.shader)?;
+        create_pipeline(device, &self.label, &self.descriptor, &shader)
+    }
+}
+
+// A shader module and what it declares, made once per source
+#[derive(Debug)]
+pub struct CachedShader {
+    pub module: wgpu::ShaderModule,
+    pub reflection: ShaderReflection,
+}
+
+impl CachedShader {
+    // the source is parsed by naga first so a broken shader is an error with line numbers, not a panic
+    pub fn new(device: &wgpu::Device, label: &str, source: &str) -> Result<Self> {
+        let reflection = ShaderReflection::from_wgsl(label, source)?;
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(label),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+        Ok(Self { module, reflection })
+    }
+}
+
+// Pipelines keyed by their descriptor, so asking for the same pipeline twice (every window, every mesh with
+// the same material) makes it once. wgpu pipelines are cheap handles, get() hands out clones.
+#[derive(Debug, Default)]
+pub struct PipelineCache {
+    shaders: HashMap<String, CachedShader>,
+    pipelines: HashMap<PipelineDescriptor, wgpu::RenderPipeline>,
+}
+
+impl PipelineCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&mut self, device: &wgpu::Device, builder: PipelineBuilder) -> Result<wgpu::RenderPipeline> {
+        if let Some(pipeline) = self.pipelines.get(&builder.descriptor) {
+            return Ok(pipeline.clone());
+        }
+
+        let shader = self.shader(device, &builder.label, &builder.descriptor.shader)?;
+        let pipeline = create_pipeline(device, &builder.label, &builder.descriptor, shader)?;
+        self.pipelines.insert(builder.descriptor, pipeline.clone());
+        Ok(pipeline)
+    }
+
+    // The module and reflection for `source`, for making bind groups that go with a pipeline
+    pub fn shader(&mut self, device: &wgpu::Device, label: &str, source: &str) -> Result<&CachedShader> {
+        if !self.shaders.contains_key(source) {
+            let shader = CachedShader::new(device, label, source)?;
+            self.shaders.insert(source.to_string(), shader);
+        }
+        Ok(&self.shaders[source])
+    }
+
+    pub fn len(&self) -> usize {
+        self.pipelines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pipelines.is_empty()
+    }
+
+    // drops every pipeline and shader, the ones already handed out keep working
+    pub fn clear(&mut self) {
+        self.pipelines.clear();
+        self.shaders.clear();
+    }
+}
+
+fn create_pipeline(
+    device: &wgpu::Device,
+    label: &str,
+    descriptor: &PipelineDescriptor,
+    shader: &CachedShader,
+) -> Result<wgpu::RenderPipeline> {
+    let reflection = &shader.reflection;
+    let vertex_layouts: Vec<_> = descriptor.vertex_layouts.iter().map(VertexLayout::desc).collect();
+    reflection.check_vertex_buffers(&descriptor.vertex_entry, &vertex_layouts)?;
+    if let Some(fragment) = &descriptor.fragment_entry {
+        reflection.entry_point(fragment)?;
+        if descriptor.targets.is_empty() {
+            bail!("{}: has a fragment shader but no color targets", label);
+        }
+    }
+
+    let feature = match descriptor.primitive.polygon_mode {
+        wgpu::PolygonMode::Fill => None,
+        wgpu::PolygonMode::Line => Some(wgpu::Features::POLYGON_MODE_LINE),
+        wgpu::PolygonMode::Point => Some(wgpu::Features::POLYGON_MODE_POINT),
+    };
+    if let Some(feature) = feature
+        && !device.features().contains(feature)
+    {
+        bail!("{}: {:?} polygons need {:?}, the device wasn't made with it", label, descriptor.primitive.polygon_mode, feature);
+    }
+
+    let generated;
+    let bind_group_layouts: Vec<&wgpu::BindGroupLayout> = if descriptor.bind_group_layouts.is_empty() {
+        generated = reflection.create_bind_group_layouts(device);
+        generated.iter().collect()
+    } else {
+        descriptor.bind_group_layouts.iter().collect()
+    };
+    if (bind_group_layouts.len() as u32) < reflection.group_count() {
+        bail!(
+            "{}: the shader uses {} bind groups but only {} layouts were given",
+            label,
+            reflection.group_count(),
+            bind_group_layouts.len()
+        );
+    }
+
+    let targets: Vec<_> = descriptor
+        .targets
+        .iter()
+        .map(|&format| {
+            Some(wgpu::ColorTargetState {
+                format,
+                blend: descriptor.blend,
+                write_mask: wgpu::ColorWrites::ALL,
+            })
+        })
+        .collect();
+
+    // anything the checks above don't cover comes back as an error instead of a panic. wasm can't block on
+    // the scope, there wgpu's own error handler reports it.
+    let scoped = !cfg!(target_arch = "wasm32");
+    if scoped {
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+    }
+
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some(label),
+        // this is an array of the @group(0) attribute and then in that group is the @binding()
+        bind_group_layouts: &bind_group_layouts,
+        push_constant_ranges: &[],
+    });
+
+    let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(label),
+        layout: Some(&layout),
+        vertex: wgpu::VertexState {
+            module: &shader.module,
+            entry_point: Some(&descriptor.vertex_entry),
+            buffers: &vertex_layouts,
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        },
+        fragment: descriptor.fragment_entry.as_ref().map(|entry_point| wgpu::FragmentState {
+            module: &shader.module,
+            entry_point: Some(entry_point),
+            targets: &targets,
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        }),
+        primitive: descriptor.primitive,
+        depth_stencil: descriptor.depth_stencil.clone(),
+        multisample: descriptor.multisample,
+        multiview: None,
+        cache: None,
+    });
+
+    if scoped && let Some(err) = pollster::block_on(device.pop_error_scope()) {
+        bail!("{}: couldn't create the pipeline: {}", label, err);
+    }
+    Ok(pipeline)
+}
@@ -0,0 +1,469 @@
+use std::collections::BTreeMap;
+use anyhow::{Result, anyhow, bail};
+use naga::valid::{Capabilities, ValidationFlags, Validator};
+use naga::{AddressSpace, ImageClass, ImageDimension, ScalarKind, ShaderStage, StorageAccess, TypeInner};
+use wgpu::{BindGroupLayoutEntry, BindingType, ShaderStages};
+
+// A resource the shader declares with @group / @binding
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReflectedBinding {
+    pub group: u32,
+    pub binding: u32,
+    pub name: String,
+    // what the WGSL declaration needs. Textures are reported as filterable and samplers as Filtering,
+    // the shader can't tell, the checks accept either.
+    pub ty: BindingType,
+    // the stages whose entry points actually use it
+    pub visibility: ShaderStages,
+    // size of the uniform / storage struct (for storage with a runtime array, the part before the array)
+    pub size: Option<u64>,
+}
+
+// A @location input of a vertex entry point
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReflectedVertexInput {
+    pub location: u32,
+    pub name: String,
+    pub kind: ScalarKind,
+    pub components: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReflectedEntryPoint {
+    pub name: String,
+    pub stage: ShaderStages,
+    // only filled in for vertex entry points
+    pub vertex_inputs: Vec<ReflectedVertexInput>,
+}
+
+// What a WGSL module expects from the Rust side: its bindings and vertex inputs, read out of the module
+// with naga. Pipelines check their bind groups and vertex buffers against this when they're made so a
+// mismatch is an error saying which binding / location is wrong instead of a wgpu validation panic.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShaderReflection {
+    pub label: String,
+    pub bindings: Vec<ReflectedBinding>,
+    pub entry_points: Vec<ReflectedEntryPoint>,
+}
+
+impl ShaderReflection {
+    // Parses and validates the module. Errors come with the file:line:column and the offending source line.
+    pub fn from_wgsl(label: &str, source: &str) -> Result<Self> {
+        let module = naga::front::wgsl::parse_str(source)
+            .map_err(|err| anyhow!("{}", err.emit_to_string_with_path(source, label)))?;
+        let info = Validator::new(ValidationFlags::all(), Capabilities::all())
+            .validate(&module)
+            .map_err(|err| anyhow!("{}", err.emit_to_string_with_path(source, label)))?;
+
+        let mut layouter = naga::proc::Layouter::default();
+        layouter
+            .update(module.to_ctx())
+            .map_err(|err| anyhow!("{}: couldn't lay out types: {}", label, err))?;
+
+        let mut bindings = Vec::new();
+        for (handle, global) in module.global_variables.iter() {
+            let Some(resource) = &global.binding else {
+                continue;
+            };
+
+            let mut visibility = ShaderStages::NONE;
+            for (index, entry_point) in module.entry_points.iter().enumerate() {
+                if !info.get_entry_point(index)[handle].is_empty() {
+                    visibility |= stage(entry_point.stage);
+                }
+            }
+
+            let name = global.name.clone().unwrap_or_else(|| format!("binding {}", resource.binding));
+            let inner = &module.types[global.ty].inner;
+            let (ty, size) = match global.space {
+                AddressSpace::Uniform => (
+                    BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: wgpu::BufferSize::new(layouter[global.ty].size as u64),
+                    },
+                    Some(layouter[global.ty].size as u64),
+                ),
+                AddressSpace::Storage { access } => (
+                    BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: !access.contains(StorageAccess::STORE) },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    Some(fixed_size(&module, &layouter, global.ty)),
+                ),
+                AddressSpace::Handle => (handle_binding(inner).map_err(|err| anyhow!("{}: {} ({}): {}", label, name, resource.binding, err))?, None),
+                other => bail!("{}: {} has a binding but lives in {:?}, that can't be bound", label, name, other),
+            };
+
+            bindings.push(ReflectedBinding {
+                group: resource.group,
+                binding: resource.binding,
+                name,
+                ty,
+                visibility,
+                size,
+            });
+        }
+        bindings.sort_by_key(|binding| (binding.group, binding.binding));
+
+        let entry_points = module
+            .entry_points
+            .iter()
+            .map(|entry_point| ReflectedEntryPoint {
+                name: entry_point.name.clone(),
+                stage: stage(entry_point.stage),
+                vertex_inputs: if entry_point.stage == ShaderStage::Vertex {
+                    vertex_inputs(&module, &entry_point.function)
+                } else {
+                    Vec::new()
+                },
+            })
+            .collect();
+
+        Ok(Self {
+            label: label.to_string(),
+            bindings,
+            entry_points,
+        })
+    }
+
+    // number of bind groups a pipeline layout for this shader needs (highest @group + 1)
+    pub fn group_count(&self) -> u32 {
+        self.bindings.iter().map(|binding| binding.group + 1).max().unwrap_or(0)
+    }
+
+    pub fn group(&self, group: u32) -> impl Iterator<Item = &ReflectedBinding> {
+        self.bindings.iter().filter(move |binding| binding.group == group)
+    }
+
+    // The layout the shader asks for, so a group can be made without writing the entries by hand
+    pub fn bind_group_layout_entries(&self, group: u32) -> Vec<BindGroupLayoutEntry> {
+        self.group(group)
+            .map(|binding| BindGroupLayoutEntry {
+                binding: binding.binding,
+                visibility: binding.visibility,
+                ty: binding.ty,
+                count: None,
+            })
+            .collect()
+    }
+
+    pub fn create_bind_group_layouts(&self, device: &wgpu::Device) -> Vec<wgpu::BindGroupLayout> {
+        (0..self.group_count())
+            .map(|group| {
+                device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some(&format!("{} group {}", self.label, group)),
+                    entries: &self.bind_group_layout_entries(group),
+                })
+            })
+            .collect()
+    }
+
+    pub fn entry_point(&self, name: &str) -> Result<&ReflectedEntryPoint> {
+        self.entry_points
+            .iter()
+            .find(|entry_point| entry_point.name == name)
+            .ok_or_else(|| anyhow!("{}: there is no entry point called {}", self.label, name))
+    }
+
+    // Checks a group the Rust side built against what the shader declares for @group(group). `buffer_sizes`
+    // are the sizes of the buffers bound at each binding, when they're known.
+    pub fn check_bind_group(&self, group: u32, entries: &[BindGroupLayoutEntry], buffer_sizes: &[(u32, u64)]) -> Result<()> {
+        let mut problems = Vec::new();
+
+        for expected in self.group(group) {
+            let what = format!("@group({}) @binding({}) `{}`", group, expected.binding, expected.name);
+            let Some(actual) = entries.iter().find(|entry| entry.binding == expected.binding) else {
+                problems.push(format!("{} ({}) is used by the shader but nothing is bound there", what, describe(&expected.ty)));
+                continue;
+            };
+
+            if !actual.visibility.contains(expected.visibility) {
+                problems.push(format!(
+                    "{} is used in {:?} but the binding is only visible to {:?}",
+                    what, expected.visibility, actual.visibility
+                ));
+            }
+            if !compatible(&expected.ty, &actual.ty) {
+                problems.push(format!("{} is {} in the shader but {} was bound", what, describe(&expected.ty), describe(&actual.ty)));
+                continue;
+            }
+
+            let bound_size = match actual.ty {
+                BindingType::Buffer { min_binding_size: Some(size), has_dynamic_offset: true, .. } => Some(size.get()),
+                _ => buffer_sizes.iter().find(|(binding, _)| *binding == expected.binding).map(|(_, size)| *size),
+            };
+            if let (Some(needed), Some(bound)) = (expected.size, bound_size)
+                && bound < needed
+            {
+                problems.push(format!("{} needs {} bytes but the buffer bound there is {} bytes", what, needed, bound));
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            bail!("{}: bind group {} doesn't match the shader:\n  {}", self.label, group, problems.join("\n  "))
+        }
+    }
+
+    // Checks that every @location the vertex entry point reads comes from one of the buffers, with the same
+    // kind of number (float / signed / unsigned)
+    pub fn check_vertex_buffers(&self, entry_point: &str, buffers: &[wgpu::VertexBufferLayout]) -> Result<()> {
+        let entry = self.entry_point(entry_point)?;
+        let mut problems = Vec::new();
+
+        let mut attributes = BTreeMap::new();
+        for (index, buffer) in buffers.iter().enumerate() {
+            for attribute in buffer.attributes {
+                if attributes.insert(attribute.shader_location, attribute.format).is_some() {
+                    problems.push(format!("location {} is in more than one vertex attribute (buffer {})", attribute.shader_location, index));
+                }
+            }
+        }
+
+        for input in &entry.vertex_inputs {
+            let shader_type = vector_name(input.kind, input.components);
+            match attributes.get(&input.location) {
+                None => problems.push(format!(
+                    "{} reads @location({}) `{}` ({}) but no vertex buffer has an attribute at that location",
+                    entry.name, input.location, input.name, shader_type
+                )),
+                Some(format) if format_kind(*format) != input.kind => problems.push(format!(
+                    "{} reads @location({}) `{}` as {} but the attribute is {:?}",
+                    entry.name, input.location, input.name, shader_type, format
+                )),
+                Some(_) => {}
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            bail!("{}: vertex buffers don't match {}:\n  {}", self.label, entry.name, problems.join("\n  "))
+        }
+    }
+}
+
+fn stage(stage: ShaderStage) -> ShaderStages {
+    match stage {
+        ShaderStage::Vertex => ShaderStages::VERTEX,
+        ShaderStage::Fragment => ShaderStages::FRAGMENT,
+        ShaderStage::Compute => ShaderStages::COMPUTE,
+        _ => ShaderStages::NONE,
+    }
+}
+
+// a struct ending in a runtime sized array only has a fixed part
+fn fixed_size(module: &naga::Module, layouter: &naga::proc::Layouter, ty: naga::Handle<naga::Type>) -> u64 {
+    match &module.types[ty].inner {
+        TypeInner::Struct { members, .. } => match members.last() {
+            Some(last) if matches!(module.types[last.ty].inner, TypeInner::Array { size: naga::ArraySize::Dynamic, .. }) => last.offset as u64,
+            _ => layouter[ty].size as u64,
+        },
+        TypeInner::Array { size: naga::ArraySize::Dynamic, .. } => 0,
+        _ => layouter[ty].size as u64,
+    }
+}
+
+// textures, samplers and storage textures
+fn handle_binding(inner: &TypeInner) -> Result<BindingType> {
+    match *inner {
+        TypeInner::Sampler { comparison } => Ok(BindingType::Sampler(if comparison {
+            wgpu::SamplerBindingType::Comparison
+        } else {
+            wgpu::SamplerBindingType::Filtering
+        })),
+        TypeInner::Image { dim, arrayed, class } => {
+            let view_dimension = view_dimension(dim, arrayed);
+            match class {
+                ImageClass::Sampled { kind, multi } => Ok(BindingType::Texture {
+                    sample_type: match kind {
+                        ScalarKind::Sint => wgpu::TextureSampleType::Sint,
+                        ScalarKind::Uint => wgpu::TextureSampleType::Uint,
+                        _ => wgpu::TextureSampleType::Float { filterable: !multi },
+                    },
+                    view_dimension,
+                    multisampled: multi,
+                }),
+                ImageClass::Depth { multi } => Ok(BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Depth,
+                    view_dimension,
+                    multisampled: multi,
+                }),
+                ImageClass::Storage { format, access } => Ok(BindingType::StorageTexture {
+                    access: if access.contains(StorageAccess::LOAD | StorageAccess::STORE) {
+                        wgpu::StorageTextureAccess::ReadWrite
+                    } else if access.contains(StorageAccess::STORE) {
+                        wgpu::StorageTextureAccess::WriteOnly
+                    } else {
+                        wgpu::StorageTextureAccess::ReadOnly
+                    },
+                    format: storage_format(format)?,
+                    view_dimension,
+                }),
+            }
+        }
+        ref other => bail!("{:?} can't be bound", other),
+    }
+}
+
+fn view_dimension(dim: ImageDimension, arrayed: bool) -> wgpu::TextureViewDimension {
+    match (dim, arrayed) {
+        (ImageDimension::D1, _) => wgpu::TextureViewDimension::D1,
+        (ImageDimension::D2, false) => wgpu::TextureViewDimension::D2,
+        (ImageDimension::D2, true) => wgpu::TextureViewDimension::D2Array,
+        (ImageDimension::D3, _) => wgpu::TextureViewDimension::D3,
+        (ImageDimension::Cube, false) => wgpu::TextureViewDimension::Cube,
+        (ImageDimension::Cube, true) => wgpu::TextureViewDimension::CubeArray,
+    }
+}
+
+fn storage_format(format: naga::StorageFormat) -> Result<wgpu::TextureFormat> {
+    use naga::StorageFormat as S;
+    use wgpu::TextureFormat as T;
+    Ok(match format {
+        S::R8Unorm => T::R8Unorm,
+        S::R8Snorm => T::R8Snorm,
+        S::R8Uint => T::R8Uint,
+        S::R8Sint => T::R8Sint,
+        S::R16Uint => T::R16Uint,
+        S::R16Sint => T::R16Sint,
+        S::R16Float => T::R16Float,
+        S::Rg8Unorm => T::Rg8Unorm,
+        S::Rg8Snorm => T::Rg8Snorm,
+        S::Rg8Uint => T::Rg8Uint,
+        S::Rg8Sint => T::Rg8Sint,
+        S::R32Uint => T::R32Uint,
+        S::R32Sint => T::R32Sint,
+        S::R32Float => T::R32Float,
+        S::Rg16Uint => T::Rg16Uint,
+        S::Rg16Sint => T::Rg16Sint,
+        S::Rg16Float => T::Rg16Float,
+        S::Rgba8Unorm => T::Rgba8Unorm,
+        S::Rgba8Snorm => T::Rgba8Snorm,
+        S::Rgba8Uint => T::Rgba8Uint,
+        S::Rgba8Sint => T::Rgba8Sint,
+        S::Bgra8Unorm => T::Bgra8Unorm,
+        S::Rgb10a2Uint => T::Rgb10a2Uint,
+        S::Rgb10a2Unorm => T::Rgb10a2Unorm,
+        S::Rg11b10Ufloat => T::Rg11b10Ufloat,
+        S::R64Uint => T::R64Uint,
+        S::Rg32Uint => T::Rg32Uint,
+        S::Rg32Sint => T::Rg32Sint,
+        S::Rg32Float => T::Rg32Float,
+        S::Rgba16Uint => T::Rgba16Uint,
+        S::Rgba16Sint => T::Rgba16Sint,
+        S::Rgba16Float => T::Rgba16Float,
+        S::Rgba32Uint => T::Rgba32Uint,
+        S::Rgba32Sint => T::Rgba32Sint,
+        S::Rgba32Float => T::Rgba32Float,
+        S::R16Unorm => T::R16Unorm,
+        S::R16Snorm => T::R16Snorm,
+        S::Rg16Unorm => T::Rg16Unorm,
+        S::Rg16Snorm => T::Rg16Snorm,
+        S::Rgba16Unorm => T::Rgba16Unorm,
+        S::Rgba16Snorm => T::Rgba16Snorm,
+    })
+}
+
+// @location arguments of the entry point, including the ones inside an input struct
+fn vertex_inputs(module: &naga::Module, function: &naga::Function) -> Vec<ReflectedVertexInput> {
+    let mut inputs = Vec::new();
+    for argument in &function.arguments {
+        let name = argument.name.clone().unwrap_or_default();
+        match (&argument.binding, &module.types[argument.ty].inner) {
+            (Some(naga::Binding::Location { location, .. }), inner) => push_input(&mut inputs, *location, name, inner),
+            (None, TypeInner::Struct { members, .. }) => {
+                for member in members {
+                    if let Some(naga::Binding::Location { location, .. }) = member.binding {
+                        let name = member.name.clone().unwrap_or_default();
+                        push_input(&mut inputs, location, name, &module.types[member.ty].inner);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    inputs.sort_by_key(|input| input.location);
+    inputs
+}
+
+fn push_input(inputs: &mut Vec<ReflectedVertexInput>, location: u32, name: String, inner: &TypeInner) {
+    let (kind, components) = match *inner {
+        TypeInner::Scalar(scalar) => (scalar.kind, 1),
+        TypeInner::Vector { size, scalar } => (scalar.kind, size as u32),
+        _ => return,
+    };
+    inputs.push(ReflectedVertexInput { location, name, kind, components });
+}
+
+// what kind of number the shader sees, Unorm / Snorm / Float formats all come in as f32
+fn format_kind(format: wgpu::VertexFormat) -> ScalarKind {
+    use wgpu::VertexFormat as F;
+    match format {
+        F::Uint8 | F::Uint8x2 | F::Uint8x4 | F::Uint16 | F::Uint16x2 | F::Uint16x4 | F::Uint32 | F::Uint32x2 | F::Uint32x3
+        | F::Uint32x4 => ScalarKind::Uint,
+        F::Sint8 | F::Sint8x2 | F::Sint8x4 | F::Sint16 | F::Sint16x2 | F::Sint16x4 | F::Sint32 | F::Sint32x2 | F::Sint32x3
+        | F::Sint32x4 => ScalarKind::Sint,
+        _ => ScalarKind::Float,
+    }
+}
+
+fn vector_name(kind: ScalarKind, components: u32) -> String {
+    let scalar = match kind {
+        ScalarKind::Sint => "i32",
+        ScalarKind::Uint => "u32",
+        ScalarKind::Bool => "bool",
+        _ => "f32",
+    };
+    if components == 1 {
+        scalar.to_string()
+    } else {
+        format!("vec{}<{}>", components, scalar)
+    }
+}
+
+// filterable / non filterable textures and filtering / non filtering samplers are a choice made on the
+// Rust side, everything else has to be exactly the same
+fn compatible(expected: &BindingType, actual: &BindingType) -> bool {
+    match (expected, actual) {
+        (BindingType::Buffer { ty: expected, .. }, BindingType::Buffer { ty: actual, .. }) => expected == actual,
+        (
+            BindingType::Texture { sample_type: expected_sample, view_dimension: expected_dim, multisampled: expected_multi },
+            BindingType::Texture { sample_type: actual_sample, view_dimension: actual_dim, multisampled: actual_multi },
+        ) => {
+            let same_sample = matches!(
+                (expected_sample, actual_sample),
+                (wgpu::TextureSampleType::Float { .. }, wgpu::TextureSampleType::Float { .. })
+            ) || expected_sample == actual_sample;
+            same_sample && expected_dim == actual_dim && expected_multi == actual_multi
+        }
+        (BindingType::Sampler(expected), BindingType::Sampler(actual)) => {
+            (*expected == wgpu::SamplerBindingType::Comparison) == (*actual == wgpu::SamplerBindingType::Comparison)
+        }
+        (BindingType::StorageTexture { .. }, BindingType::StorageTexture { .. }) => expected == actual,
+        _ => false,
+    }
+}
+
+fn describe(ty: &BindingType) -> String {
+    match ty {
+        BindingType::Buffer { ty: wgpu::BufferBindingType::Uniform, .. } => "a uniform buffer".to_string(),
+        BindingType::Buffer { ty: wgpu::BufferBindingType::Storage { read_only: true }, .. } => "a read only storage buffer".to_string(),
+        BindingType::Buffer { ty: wgpu::BufferBindingType::Storage { read_only: false }, .. } => "a read_write storage buffer".to_string(),
+        BindingType::Sampler(wgpu::SamplerBindingType::Comparison) => "a comparison sampler".to_string(),
+        BindingType::Sampler(_) => "a sampler".to_string(),
+        BindingType::Texture { sample_type, view_dimension, multisampled } => format!(
+            "a {}{:?} texture ({:?})",
+            if *multisampled { "multisampled " } else { "" },
+            view_dimension,
+            sample_type
+        ),
+        BindingType::StorageTexture { access, format, view_dimension } => {
+            format!("a {:?} storage texture ({:?}, {:?})", view_dimension, format, access)
+        }
+        other => format!("{:?}", other),
+    }
+}
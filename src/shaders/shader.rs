@@ -1,184 +1,296 @@
-use anyhow::Result;
-use crate::geometry::geometry::{Vertex, Cube, INDICES};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use anyhow::{Result, bail};
 use crate::camera::camera::CameraMatrix;
-use crate::camera::camera::CameraUniform;
-use crate::shaders::buffers::BufferTypes;
-use nalgebra::Point3;
-use crate::shaders::bind_group::BindGrouping;
+use crate::geometry::mesh::{Mesh, MeshData, MeshVertex};
+use crate::geometry::model::Model;
+use nalgebra::Vector3;
+use crate::render::instance::{Instance, InstanceBuffer, InstanceId, InstanceRaw};
+use crate::scene::light::{LightBuffer, LightsUniform};
+use crate::scene::scene::{Attachment, MeshId, NodeId, SceneGraph, Transform};
+use crate::shaders::bind_group::{BindGroupBuilder, BindGrouping};
+use crate::shaders::pipeline::{PipelineBuilder, PipelineCache};
+use crate::shaders::source::{ShaderFile, ShaderPipeline};
+use crate::render::render::RenderSettings;
+use crate::textures::textures::ColorTexture;
+
+// A mesh the scene can draw and every copy of it
+pub struct SceneMesh {
+    pub mesh: Mesh,
+    // Copies placed by hand, the scene graph's nodes aren't in here. These are drawn with back faces culled,
+    // a mirrored model matrix shows the inside of the mesh, put mirrored copies in the scene graph instead.
+    pub instances: InstanceBuffer,
+    // one per scene graph node showing this mesh, VertexShaders::prepare keeps them in sync
+    node_instances: InstanceBuffer,
+    // the nodes whose world matrix mirrors (negative determinant), that flips the winding so they get
+    // drawn with the pipeline that culls front faces
+    mirrored_instances: InstanceBuffer,
+    // @group(1), the base color texture. The white one until VertexShaders::set_texture gives it another.
+    texture: wgpu::BindGroup,
+}
 
 pub struct VertexShaders {
-    pub vertex_buffer: wgpu::Buffer,
-    pub index_buffer: wgpu::Buffer,
-    pub num_vertices: u32,
-    pub num_indices: u32,
-    pub render_pipeline: wgpu::RenderPipeline,
-    pub camera_bytes: Option<wgpu::Buffer>,
+    pub meshes: Vec<SceneMesh>,
+    pub scene: SceneGraph,
+    // which instance each mesh node got, and if it's in the mirrored buffer
+    node_instances: HashMap<NodeId, NodeInstance>,
+    lights: LightBuffer,
+    // the nodes with a light attached, the lights only get gathered again when one of them changed
+    light_nodes: HashSet<NodeId>,
+    // a light node changed or went away since the lights were last gathered
+    lights_dirty: bool,
+    // what meshes without a texture sample, white so the vertex / instance colors come through as they are
+    white: wgpu::BindGroup,
+    pub render_pipeline: ShaderPipeline,
+    // the same pipeline culling front faces, for mirrored nodes
+    pub mirrored_pipeline: ShaderPipeline,
     pub bind_group: wgpu::BindGroup,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct NodeInstance {
+    mesh: MeshId,
+    mirrored: bool,
+    id: InstanceId,
+}
+
 impl VertexShaders {
-    pub fn new(device: &wgpu::Device, config: wgpu::SurfaceConfiguration) -> Result<Self> {
-        // config shader
-        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/shader.wgsl").into()),
-        });
+    // the cube every VertexShaders starts with
+    pub const CUBE: MeshId = MeshId(0);
 
-        let cube_1 = Cube::new();
-        let cube_tran = cube_1.move_cube(Point3::new(-0.5, 0.8, 0.0));
-
-        //Camera Buffer
-        let asp_ratio = config.width / config.height;
-        let cam = CameraMatrix::new(asp_ratio as f32);
-
-        let cam_uniform = &CameraUniform::from(cam);
-        let cam_bytes = bytemuck::bytes_of(cam_uniform);
-        let camera_buffer = BufferTypes::UniformBuffer(cam_bytes).build(Some("camera"), &device);
-
-        // TRANSLATION BUFFER
-        let translation_mat_bytes = bytemuck::bytes_of(&cube_tran);
-        let translation_buffer = BufferTypes::UniformBuffer(translation_mat_bytes).build(Some("translation buffer"), &device);
-
-        let v_buff = vec![camera_buffer, translation_buffer];
-        let bg = BindGrouping::new(device, &v_buff[..]);
-
-        // let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-        //     label: Some("bind_group"),
-        //     // CAMERA
-        //     entries: &[wgpu::BindGroupLayoutEntry {
-        //         binding: 0,
-        //         visibility: wgpu::ShaderStages::VERTEX,
-        //         ty: wgpu::BindingType::Buffer {
-        //             ty: wgpu::BufferBindingType::Uniform,
-        //             has_dynamic_offset: false,
-        //             min_binding_size: None,
-        //         },
-        //         count: None,
-        //     },
-        //     // CUBE TRANSLATION 
-        //    wgpu::BindGroupLayoutEntry {
-        //         binding: 1,
-        //         visibility: wgpu::ShaderStages::VERTEX,
-        //         ty: wgpu::BindingType::Buffer {
-        //             ty: wgpu::BufferBindingType::Uniform,
-        //             has_dynamic_offset: false,
-        //             min_binding_size: None,
-        //         },
-        //         count: None,
-        //     }, 
-        //     ],
-        // });
-
-        let render_pipeline_layout =
-            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: Some("Render Pipeline Layout"),
-                // this is an array of the @group(0) attribute and then in that group is the @binding()
-                bind_group_layouts: &[&bg.bind_group.0],
-                push_constant_ranges: &[],
-            });
-
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Render Pipeline"),
-            layout: Some(&render_pipeline_layout),
-            // THIS IS WHERE THE SETUP OF SHADERS IS BEING DONE.
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: Some("vs_main"),
-                buffers: &[Vertex::desc()],
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-            },
-            fragment: Some(wgpu::FragmentState {
-                // 3.
-                module: &shader,
-                entry_point: Some("fs_main"),
-                targets: &[Some(wgpu::ColorTargetState {
-                    // 4.
-                    format: config.format,
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-            }),
-
-            // The primitive field describes how to interpret our vertices when converting them into triangles.
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: None, //Some(wgpu::Face::Back), // This will hide back faces
-                polygon_mode: wgpu::PolygonMode::Fill,
-                unclipped_depth: false,
-                conservative: false,
-            },
-            depth_stencil: None,
-            // Some(wgpu::DepthStencilState {
-            //     format: wgpu::TextureFormat::Depth32Float,
-            //     depth_write_enabled: true,
-            //     depth_compare: wgpu::CompareFunction::Less,
-            //     stencil: wgpu::StencilState::default(),
-            //     bias: wgpu::DepthBiasState::default(),
-            //  }),
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            multiview: None,
-            cache: None,
-        });
+    // the camera buffer belongs to the camera (CameraBuffer), this just binds it at @binding(0).
+    // The depth format and sample count in `settings` have to match the attachments of the pass this gets drawn in.
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        pipelines: &mut PipelineCache,
+        format: wgpu::TextureFormat,
+        settings: &RenderSettings,
+        camera_buffer: &wgpu::Buffer,
+    ) -> Result<Self> {
+        let file = ShaderFile::SCENE;
+        let cube = Mesh::new(device, "cube", MeshData::cube(1.0))?;
+
+        // the default scene, two cubes. Add / remove / move them through `scene`.
+        let mut scene = SceneGraph::new();
+        let first = scene.add("cube", Transform::default());
+        scene.attach(first, Attachment::mesh(Self::CUBE));
+        let second = scene.add("cube 2", Transform::at(Vector3::new(-0.5, 0.8, 0.0)));
+        scene.attach(second, Attachment::mesh(Self::CUBE));
+
+        let lights = LightBuffer::new(device, &LightsUniform::fallback(scene.ambient));
+
+        let white = ColorTexture::new(device, "White Texture", 1, 1, wgpu::TextureFormat::Rgba8UnormSrgb);
+        white.write_region(queue, 0, 0, 1, 1, &[255, 255, 255, 255]);
 
-        // //Bind group for Camera and Translation
-        // let bind_group = device.create_bind_group(&BindGroupDescriptor {
-        //     label: Some("Cam Bind Group"),
-        //     layout: &bg.bind_group.0,
-        //     // CAMERA
-        //     entries: &[BindGroupEntry {
-        //         binding: 0,
-        //         resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
-        //             buffer: &self.camera_buffer,
-        //             offset: 0,
-        //             // this is not the size of th ebuffer but how much of the buffer you are binding to the shader.
-        //             // I AM USING None TO USE THE ENTIRE SIZE OF THE BUFFER PER THE DOCS
-        //             size: None,
-        //         }),
-        //     }, 
-        //     // TRANSLATION
-        //     BindGroupEntry {
-        //         binding: 1,
-        //         resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
-        //             buffer: &translation_buffer,
-        //             offset: 0,
-        //             // this is not the size of th ebuffer but how much of the buffer you are binding to the shader.
-        //             size: None,
-        //         }),
-        //     }
-        // ],
-        // });
-
-        let vertex_bytes:Vec<u8> = bytemuck::cast_slice(&cube_1.vertices).to_vec();
-
-        let vertex_buffer = BufferTypes::VertexBuffer(&vertex_bytes).build(Some("v1"), &device);
-
-        let index_buffer = BufferTypes::IndexBuffer(&INDICES).build(Some("indices"), &device);
-
-        let num_vertices = cube_1.vertices.len() as u32;
-
-        let num_indices = INDICES.len() as u32;
+        // everything that comes from the shader source, built again from the embedded copy if the file doesn't build
+        let (bg, material, render_pipeline, mirrored_pipeline) = file.build(|source| {
+            let reflection = pipelines.shader(device, file.name(), &source)?.reflection.clone();
+            let bg = BindGrouping::builder("Scene Bind Group")
+                .uniform(0, wgpu::ShaderStages::VERTEX, camera_buffer)
+                .uniform(1, wgpu::ShaderStages::FRAGMENT, &lights.buffer)
+                .build_checked(device, &reflection, 0)?;
+            let material = Self::texture_group(device, &white).build_checked(device, &reflection, 1)?;
+
+            let builder = PipelineBuilder::new(file.name(), source)
+                .vertex_layout(MeshVertex::desc())
+                .vertex_layout(InstanceRaw::desc())
+                .bind_group_layout(&bg.bind_group.0)
+                .bind_group_layout(&material.bind_group.0)
+                .topology(cube.topology())
+                // LessEqual so geometry at the same depth still draws in the order it was submitted
+                .render_settings(settings, true, wgpu::CompareFunction::LessEqual)
+                .target(format);
+            let render_pipeline = ShaderPipeline::new(device, pipelines, file, builder.clone().cull_mode(Some(wgpu::Face::Back)))?;
+            let mirrored_pipeline = ShaderPipeline::new(device, pipelines, file, builder.cull_mode(Some(wgpu::Face::Front)))?;
+            Ok((bg, material, render_pipeline, mirrored_pipeline))
+        })?;
 
         Ok(Self {
-            vertex_buffer,
-            index_buffer,
-            num_vertices,
-            num_indices,
+            meshes: vec![SceneMesh {
+                mesh: cube,
+                instances: InstanceBuffer::new(),
+                node_instances: InstanceBuffer::new(),
+                mirrored_instances: InstanceBuffer::new(),
+                texture: material.bind_group.1.clone(),
+            }],
+            scene,
+            node_instances: HashMap::new(),
+            lights,
+            light_nodes: HashSet::new(),
+            lights_dirty: false,
+            white: material.bind_group.1,
             render_pipeline,
-            camera_bytes: None,
+            mirrored_pipeline,
             bind_group: bg.bind_group.1,
         })
     }
 
-}
+    // Makes `data` something scene nodes can show with Attachment::Mesh. There's one pipeline for the
+    // scene so only triangle lists go for now.
+    pub fn add_mesh(&mut self, device: &wgpu::Device, label: &str, data: MeshData) -> Result<MeshId> {
+        if data.topology != wgpu::PrimitiveTopology::TriangleList {
+            bail!("{}: the scene only draws TriangleList meshes, this one is {:?}", label, data.topology);
+        }
+        self.meshes.push(SceneMesh {
+            mesh: Mesh::new(device, label, data)?,
+            instances: InstanceBuffer::new(),
+            node_instances: InstanceBuffer::new(),
+            mirrored_instances: InstanceBuffer::new(),
+            texture: self.white.clone(),
+        });
+        Ok(MeshId(self.meshes.len() - 1))
+    }
 
+    // What `mesh` samples for its base color from now on. It has to be a filterable float texture
+    // (Rgba8UnormSrgb from ColorTexture::from_image / Material::texture is), the pipeline has a filtering sampler.
+    pub fn set_texture(&mut self, device: &wgpu::Device, mesh: MeshId, texture: &ColorTexture) -> Result<()> {
+        let filterable = matches!(
+            texture.format.sample_type(None, Some(device.features())),
+            Some(wgpu::TextureSampleType::Float { filterable: true })
+        );
+        if !filterable {
+            bail!("{:?} textures can't be sampled with the scene's filtering sampler", texture.format);
+        }
+        let Some(scene_mesh) = self.meshes.get_mut(mesh.0) else {
+            bail!("no mesh {:?}", mesh);
+        };
+        scene_mesh.texture = Self::texture_group(device, texture).build(device)?.bind_group.1;
+        Ok(())
+    }
+
+    // Puts every mesh of `model` into the scene under one new root node called `name` and returns that node.
+    // The meshes get their material's texture and the base color goes on the node, move the whole model
+    // with the root's transform.
+    pub fn add_model(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, name: &str, model: &Model) -> Result<NodeId> {
+        // one texture per material, shared by the meshes that use it
+        let textures: Vec<Option<ColorTexture>> = model.materials.iter().map(|material| material.texture(device, queue)).collect();
 
+        let mut meshes = Vec::with_capacity(model.meshes.len());
+        for model_mesh in &model.meshes {
+            let mesh = self.add_mesh(device, &model_mesh.name, model_mesh.data.clone())?;
+            if let Some(texture) = model_mesh.material.and_then(|index| textures.get(index)?.as_ref()) {
+                self.set_texture(device, mesh, texture)?;
+            }
+            meshes.push(mesh);
+        }
 
+        let root = self.scene.add(name, Transform::default());
+        for (model_mesh, mesh) in model.meshes.iter().zip(meshes) {
+            let color = model.material(model_mesh).map(|material| material.base_color).unwrap_or([1.0, 1.0, 1.0, 1.0]);
+            if let Some(node) = self.scene.add_child(root, model_mesh.name.as_str(), Transform::from_matrix(&model_mesh.transform)) {
+                self.scene.attach(node, Attachment::Mesh { mesh, color });
+            }
+        }
+        Ok(root)
+    }
 
+    fn texture_group<'a>(device: &wgpu::Device, texture: &'a ColorTexture) -> BindGroupBuilder<'a> {
+        BindGrouping::builder("Base Color Texture").color_texture(0, 1, wgpu::ShaderStages::FRAGMENT, device, texture)
+    }
 
+    pub fn mesh(&mut self, mesh: MeshId) -> Option<&mut SceneMesh> {
+        self.meshes.get_mut(mesh.0)
+    }
+
+    // Walks the scene graph and uploads whatever changed since the last frame: node world matrices into the
+    // instance buffers, the lights, and the view if the scene has a camera.
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, camera: &mut CameraMatrix) {
+        self.scene.update();
+
+        for id in self.scene.take_removed() {
+            if let Some(old) = self.node_instances.remove(&id) {
+                self.node_buffer(old.mesh, old.mirrored).remove(old.id);
+            }
+            self.lights_dirty |= self.light_nodes.remove(&id);
+        }
+        for id in self.scene.take_changed() {
+            let Some(node) = self.scene.get(id) else {
+                continue;
+            };
+            // a light that moved, or a node that stopped being one
+            if let Some(Attachment::Light(_)) = node.attachment() {
+                self.light_nodes.insert(id);
+                self.lights_dirty = true;
+            } else {
+                self.lights_dirty |= self.light_nodes.remove(&id);
+            }
+            // meshes that were never added get skipped
+            let wanted = match node.attachment() {
+                Some(Attachment::Mesh { mesh, color }) if mesh.0 < self.meshes.len() => {
+                    Some((*mesh, Instance::new(*node.world()).with_color(*color)))
+                }
+                _ => None,
+            };
+
+            match (self.node_instances.get(&id).copied(), wanted) {
+                (Some(old), Some((mesh, instance))) if old.mesh == mesh && old.mirrored == instance.is_mirrored() => {
+                    self.node_buffer(mesh, old.mirrored).update(old.id, instance);
+                }
+                (old, wanted) => {
+                    if let Some(old) = old {
+                        self.node_buffer(old.mesh, old.mirrored).remove(old.id);
+                        self.node_instances.remove(&id);
+                    }
+                    if let Some((mesh, instance)) = wanted {
+                        let mirrored = instance.is_mirrored();
+                        let instance_id = self.node_buffer(mesh, mirrored).add(instance);
+                        self.node_instances.insert(id, NodeInstance { mesh, mirrored, id: instance_id });
+                    }
+                }
+            }
+        }
+
+        if let Some(view) = self.scene.view() {
+            camera.cam = view;
+        }
+
+        // ambient is a plain field, it's checked against what was uploaded instead
+        if self.lights_dirty || self.scene.ambient != self.lights.ambient() {
+            self.lights_dirty = false;
+            let lights = if self.light_nodes.is_empty() {
+                LightsUniform::fallback(self.scene.ambient)
+            } else {
+                LightsUniform::new(self.scene.ambient, self.scene.lights())
+            };
+            self.lights.update(queue, &lights);
+        }
+
+        for mesh in &mut self.meshes {
+            mesh.instances.upload(device, queue);
+            mesh.node_instances.upload(device, queue);
+            mesh.mirrored_instances.upload(device, queue);
+        }
+    }
+
+    // The bind group at @group(0) has to be set already. This sets the pipelines and each mesh's texture at
+    // @group(1), the mirrored nodes go last with their own pipeline.
+    pub fn draw_meshes<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        render_pass.set_pipeline(&self.render_pipeline.pipeline);
+        for mesh in &self.meshes {
+            render_pass.set_bind_group(1, Some(&mesh.texture), &[]);
+            mesh.node_instances.draw(render_pass, &mesh.mesh);
+            mesh.instances.draw(render_pass, &mesh.mesh);
+        }
+
+        if self.meshes.iter().all(|mesh| mesh.mirrored_instances.is_empty()) {
+            return;
+        }
+        render_pass.set_pipeline(&self.mirrored_pipeline.pipeline);
+        for mesh in self.meshes.iter().filter(|mesh| !mesh.mirrored_instances.is_empty()) {
+            render_pass.set_bind_group(1, Some(&mesh.texture), &[]);
+            mesh.mirrored_instances.draw(render_pass, &mesh.mesh);
+        }
+    }
+
+    // hot reload for both scene pipelines, see ShaderPipeline::reload_changed
+    pub fn reload_changed(&mut self, device: &wgpu::Device, pipelines: &mut PipelineCache, changed: &[PathBuf]) {
+        self.render_pipeline.reload_changed(device, pipelines, changed);
+        self.mirrored_pipeline.reload_changed(device, pipelines, changed);
+    }
+
+    fn node_buffer(&mut self, mesh: MeshId, mirrored: bool) -> &mut InstanceBuffer {
+        let mesh = &mut self.meshes[mesh.0];
+        if mirrored { &mut mesh.mirrored_instances } else { &mut mesh.node_instances }
+    }
+}
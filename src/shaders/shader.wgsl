@@ -8,23 +8,55 @@ struct Camera {
 @group(0) @binding(0)
 var<uniform> camera: Camera;
 
-// @location is mapped out in the geometry::Vertex.desc()
-struct VertexInput {
-    @location(0) c1_position: vec3<f32>, // first attribute is position (x, y, z)
-    @location(1) c1_color: vec3<f32>, // second attribute is color (r, g, b)
+// see scene::light::LightRaw. position.w is 0 for a directional light (xyz is the way to the light)
+// and 1 for a point light, color.a is a point light's range.
+struct Light {
+    position: vec4<f32>,
+    color: vec4<f32>,
 };
 
-struct ModelTranslation {
-    model: mat4x4<f32>,
+// scene::light::LightsUniform, the lights of the scene graph
+struct Lights {
+    ambient: vec3<f32>,
+    count: u32,
+    lights: array<Light, 8>,
 };
 
 @group(0) @binding(1)
-var<uniform> model_translation: ModelTranslation;
+var<uniform> lights: Lights;
+
+// the mesh's base color texture (geometry::model::Material), meshes without one get a white 1x1 texture
+@group(1) @binding(0)
+var base_color_texture: texture_2d<f32>;
+@group(1) @binding(1)
+var base_color_sampler: sampler;
+
+// @location is mapped out in the geometry::mesh::MeshVertex.desc()
+struct VertexInput {
+    @location(0) c1_position: vec3<f32>, // first attribute is position (x, y, z)
+    @location(1) c1_normal: vec3<f32>, // second attribute is the normal, pointing out of the surface
+    @location(2) c1_uv: vec2<f32>,
+    @location(3) c1_color: vec4<f32>,
+};
+
+// one per copy of the mesh, see render::instance::InstanceRaw. The model and normal matrices come in as their columns.
+struct InstanceInput {
+    @location(4) model_0: vec4<f32>,
+    @location(5) model_1: vec4<f32>,
+    @location(6) model_2: vec4<f32>,
+    @location(7) model_3: vec4<f32>,
+    @location(8) color: vec4<f32>,
+    @location(9) normal_0: vec3<f32>,
+    @location(10) normal_1: vec3<f32>,
+    @location(11) normal_2: vec3<f32>,
+};
 
 struct VertexOutput {
     @builtin(position) c1_clip_position: vec4<f32>, // a special value the GPU needs — the final screen position
-    @location(0) c1_color: vec3<f32>, 
-    
+    @location(0) c1_normal: vec3<f32>,
+    @location(1) c1_uv: vec2<f32>,
+    @location(2) c1_color: vec4<f32>,
+    @location(3) c1_world_position: vec3<f32>,
 };
 
 struct FragOutput {
@@ -36,19 +68,21 @@ struct FragOutput {
 // We are using @vertex to mark this function as a valid entry point for a vertex shader. 
 
 @vertex
-fn vs_main(model: VertexInput, @builtin(instance_index) instance: u32) -> VertexOutput {
+fn vs_main(model: VertexInput, instance: InstanceInput) -> VertexOutput {
     var out: VertexOutput;
 
-    var model_world_space = vec4<f32>(model.c1_position, 1.0); 
-
-    // Apply translation to instance 1 *before* view/projection
-    if (instance == 1u) {
-        model_world_space = model_translation.model * model_world_space;
-    }
+    let instance_model = mat4x4<f32>(instance.model_0, instance.model_1, instance.model_2, instance.model_3);
+    // the instance's transform goes *before* view/projection
+    let model_world_space = instance_model * vec4<f32>(model.c1_position, 1.0);
 
     let view_space = camera.cam * model_world_space;
     out.c1_clip_position = camera.projection * view_space;
-    out.c1_color = model.c1_color;
+    // the inverse transpose of the model matrix, so squashed models keep their normals square to the surface
+    let normal_matrix = mat3x3<f32>(instance.normal_0, instance.normal_1, instance.normal_2);
+    out.c1_normal = normal_matrix * model.c1_normal;
+    out.c1_uv = model.c1_uv;
+    out.c1_color = model.c1_color * instance.color;
+    out.c1_world_position = model_world_space.xyz;
 
     return out;
 }
@@ -56,7 +90,25 @@ fn vs_main(model: VertexInput, @builtin(instance_index) instance: u32) -> Vertex
 @fragment
 fn fs_main(in: VertexOutput) -> FragOutput  {
     var f_out: FragOutput;
-    f_out.c1 = vec4<f32>(in.c1_color, 1.0);
+    // sampled before the light loop, textureSample has to be in uniform control flow
+    let base = textureSample(base_color_texture, base_color_sampler, in.c1_uv);
+    let normal = normalize(in.c1_normal);
+    var light = lights.ambient;
+    for (var i = 0u; i < min(lights.count, 8u); i++) {
+        let current = lights.lights[i];
+        var to_light = current.position.xyz;
+        var falloff = 1.0;
+        if (current.position.w > 0.5) {
+            let offset = current.position.xyz - in.c1_world_position;
+            let distance = length(offset);
+            to_light = offset / max(distance, 0.0001);
+            // smooth down to zero at the range
+            let fade = clamp(1.0 - distance / current.color.a, 0.0, 1.0);
+            falloff = fade * fade;
+        }
+        light += current.color.rgb * max(dot(normal, to_light), 0.0) * falloff;
+    }
+    f_out.c1 = vec4<f32>(base.rgb * light, base.a) * in.c1_color;
     
     return f_out;
 }
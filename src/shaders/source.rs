@@ -0,0 +1,114 @@
+use std::path::{Path, PathBuf};
+use anyhow::Result;
+use crate::shaders::pipeline::{PipelineBuilder, PipelineCache};
+
+// A WGSL file that is baked into the binary with include_str! and also known by its path on disk.
+// Release builds only ever use the embedded copy. With the hot-reload feature the file is read from disk
+// so edits show up without rebuilding, see ShaderWatcher.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShaderFile {
+    // absolute, made with concat!(env!("CARGO_MANIFEST_DIR"), ...) so it works from any working directory
+    pub path: &'static str,
+    pub embedded: &'static str,
+}
+
+impl ShaderFile {
+    pub const SCENE: Self = Self {
+        path: concat!(env!("CARGO_MANIFEST_DIR"), "/src/shaders/shader.wgsl"),
+        embedded: include_str!("shader.wgsl"),
+    };
+
+    pub const UI: Self = Self {
+        path: concat!(env!("CARGO_MANIFEST_DIR"), "/src/shaders/ui.wgsl"),
+        embedded: include_str!("ui.wgsl"),
+    };
+
+    // just the file name, used as the label so naga errors read "ui.wgsl:12:5"
+    pub fn name(&self) -> &'static str {
+        Path::new(self.path).file_name().and_then(|name| name.to_str()).unwrap_or(self.path)
+    }
+
+    pub fn source(&self) -> String {
+        if cfg!(feature = "hot-reload") {
+            match self.read() {
+                Ok(source) => return source,
+                Err(err) => eprintln!("Couldn't read {}, using the built in copy: {}", self.path, err),
+            }
+        }
+        self.embedded.to_string()
+    }
+
+    // Hands source() to `build`. A file on disk that reads fine but doesn't build (an edit left half done when
+    // the app starts) gets its error printed and `build` runs again with the embedded copy, the way a reload
+    // keeps the last good pipeline.
+    pub fn build<T>(&self, mut build: impl FnMut(String) -> Result<T>) -> Result<T> {
+        let source = self.source();
+        if source == self.embedded {
+            return build(source);
+        }
+        build(source).or_else(|err| {
+            eprintln!("{}
+{} doesn't build, using the built in copy", err, self.path);
+            build(self.embedded.to_string())
+        })
+    }
+
+    pub fn read(&self) -> std::io::Result<String> {
+        std::fs::read_to_string(self.path)
+    }
+}
+
+// A pipeline that remembers how it was built so it can be rebuilt when its shader changes
+pub struct ShaderPipeline {
+    pub file: ShaderFile,
+    pub builder: PipelineBuilder,
+    pub pipeline: wgpu::RenderPipeline,
+}
+
+impl ShaderPipeline {
+    // `builder` should have been made with file.source()
+    pub fn new(device: &wgpu::Device, pipelines: &mut PipelineCache, file: ShaderFile, builder: PipelineBuilder) -> Result<Self> {
+        let pipeline = pipelines.get(device, builder.clone())?;
+        Ok(Self { file, builder, pipeline })
+    }
+
+    // Rebuilds with `source`. When it doesn't compile (or no longer fits the vertex buffers / bind groups)
+    // the error is returned and the last good pipeline stays in use.
+    pub fn reload(&mut self, device: &wgpu::Device, pipelines: &mut PipelineCache, source: String) -> Result<()> {
+        let mut builder = self.builder.clone();
+        builder.descriptor.shader = source;
+        self.pipeline = pipelines.get(device, builder.clone())?;
+        self.builder = builder;
+        Ok(())
+    }
+
+    // Reloads from disk if `changed` has this pipeline's file in it, errors are printed and not returned
+    // since a typo in the middle of an edit is expected. Returns true if the pipeline was swapped.
+    pub fn reload_changed(&mut self, device: &wgpu::Device, pipelines: &mut PipelineCache, changed: &[PathBuf]) -> bool {
+        if !changed.iter().any(|path| path == Path::new(self.file.path)) {
+            return false;
+        }
+
+        let source = match self.file.read() {
+            Ok(source) => source,
+            Err(err) => {
+                eprintln!("Couldn't read {}: {}", self.file.path, err);
+                return false;
+            }
+        };
+        if source == self.builder.descriptor.shader {
+            return false;
+        }
+
+        match self.reload(device, pipelines, source) {
+            Ok(()) => {
+                eprintln!("Reloaded {}", self.file.name());
+                true
+            }
+            Err(err) => {
+                eprintln!("{}\nkeeping the last working version of {}", err, self.file.name());
+                false
+            }
+        }
+    }
+}
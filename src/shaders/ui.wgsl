@@ -0,0 +1,107 @@
+// 2D shapes and text for the UI. Every rect / rounded rect / ellipse is one quad and the fragment shader
+// works out the shape from a signed distance field, which gives smooth edges without MSAA. Glyphs are
+// quads too, their coverage comes from the glyph atlas.
+// Positions are logical pixels, the camera here is CameraMatrix::pixel_space so the same CameraUniform
+// layout as shader.wgsl works.
+
+struct Camera {
+    cam: mat4x4<f32>,
+    projection: mat4x4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+// single channel, how much of each texel the glyph covers
+@group(1) @binding(0)
+var glyph_atlas: texture_2d<f32>;
+@group(1) @binding(1)
+var glyph_sampler: sampler;
+
+const KIND_RECT: f32 = 0.0;
+const KIND_ELLIPSE: f32 = 1.0;
+const KIND_GLYPH: f32 = 2.0;
+
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    // position relative to the middle of the shape
+    @location(1) local: vec2<f32>,
+    @location(2) half_size: vec2<f32>,
+    // x: corner radius, y: stroke width, z: kind
+    @location(3) params: vec4<f32>,
+    @location(4) fill: vec4<f32>,
+    @location(5) stroke: vec4<f32>,
+    @location(6) uv: vec2<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) local: vec2<f32>,
+    @location(1) half_size: vec2<f32>,
+    @location(2) params: vec4<f32>,
+    @location(3) fill: vec4<f32>,
+    @location(4) stroke: vec4<f32>,
+    @location(5) uv: vec2<f32>,
+};
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = camera.projection * camera.cam * vec4<f32>(in.position, 0.0, 1.0);
+    out.local = in.local;
+    out.half_size = in.half_size;
+    out.params = in.params;
+    out.fill = in.fill;
+    out.stroke = in.stroke;
+    out.uv = in.uv;
+    return out;
+}
+
+// negative inside, positive outside, 0 on the edge
+fn rounded_box(p: vec2<f32>, half_size: vec2<f32>, radius: f32) -> f32 {
+    let q = abs(p) - half_size + vec2<f32>(radius);
+    return length(max(q, vec2<f32>(0.0))) + min(max(q.x, q.y), 0.0) - radius;
+}
+
+// not exact away from the edge but close enough for a few pixels of stroke and anti aliasing
+fn ellipse(p: vec2<f32>, radii: vec2<f32>) -> f32 {
+    let k0 = length(p / radii);
+    let k1 = length(p / (radii * radii));
+    if (k1 == 0.0) {
+        return -min(radii.x, radii.y);
+    }
+    return k0 * (k0 - 1.0) / k1;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // sampled for every kind, the branches below aren't uniform control flow
+    let glyph_coverage = textureSampleLevel(glyph_atlas, glyph_sampler, in.uv, 0.0).r;
+
+    var distance: f32;
+    if (in.params.z == KIND_ELLIPSE) {
+        distance = ellipse(in.local, in.half_size);
+    } else {
+        distance = rounded_box(in.local, in.half_size, in.params.x);
+    }
+
+    // how many pixels the distance changes over one screen pixel, keeps the edge 1px soft at any scale
+    let pixel = max(fwidth(distance), 0.0001);
+    let coverage = clamp(0.5 - distance / pixel, 0.0, 1.0);
+
+    if (in.params.z == KIND_GLYPH) {
+        return vec4<f32>(in.fill.rgb * in.fill.a, in.fill.a) * glyph_coverage;
+    }
+
+    // the stroke sits on the inside of the edge, fill is whatever is left in the middle
+    let stroke_width = in.params.y;
+    var stroke_amount = 0.0;
+    if (stroke_width > 0.0) {
+        stroke_amount = clamp(0.5 + (distance + stroke_width) / pixel, 0.0, 1.0);
+    }
+
+    // premultiplied so a transparent fill doesn't darken the stroke where they blend
+    let fill = vec4<f32>(in.fill.rgb * in.fill.a, in.fill.a);
+    let stroke = vec4<f32>(in.stroke.rgb * in.stroke.a, in.stroke.a);
+    return mix(fill, stroke, stroke_amount) * coverage;
+}
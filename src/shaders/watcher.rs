@@ -0,0 +1,62 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Receiver, channel};
+use anyhow::Result;
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use crate::shaders::source::ShaderFile;
+
+// Watches shader files and reports which ones changed since the last poll(). The directories are watched
+// and not the files themselves, most editors save by writing a new file and renaming it over the old one
+// which would leave a watch on the file itself pointing at nothing.
+pub struct ShaderWatcher {
+    // has to stay alive for the events to keep coming
+    _watcher: RecommendedWatcher,
+    events: Receiver<notify::Result<notify::Event>>,
+    files: HashSet<PathBuf>,
+}
+
+impl ShaderWatcher {
+    pub fn new(files: &[ShaderFile]) -> Result<Self> {
+        let (sender, events) = channel();
+        let mut watcher = notify::recommended_watcher(move |event| {
+            // the receiver is gone when the window is, nothing to do about it then
+            let _ = sender.send(event);
+        })?;
+
+        let files: HashSet<PathBuf> = files.iter().map(|file| PathBuf::from(file.path)).collect();
+        let directories: HashSet<&Path> = files.iter().filter_map(|file| file.parent()).collect();
+        for directory in directories {
+            watcher.watch(directory, RecursiveMode::NonRecursive)?;
+        }
+
+        Ok(Self {
+            _watcher: watcher,
+            events,
+            files,
+        })
+    }
+
+    // Never blocks. One save usually comes in as several events, each file is only in here once.
+    pub fn poll(&self) -> Vec<PathBuf> {
+        let mut changed = Vec::new();
+        for event in self.events.try_iter() {
+            let event = match event {
+                Ok(event) => event,
+                Err(err) => {
+                    eprintln!("Shader watcher error: {}", err);
+                    continue;
+                }
+            };
+            if !matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                continue;
+            }
+
+            for path in event.paths {
+                if self.files.contains(&path) && !changed.contains(&path) {
+                    changed.push(path);
+                }
+            }
+        }
+        changed
+    }
+}
@@ -0,0 +1,96 @@
+use std::collections::HashMap;
+use fontdue::layout::GlyphRasterConfig;
+use crate::text::font::Font;
+use crate::textures::textures::ColorTexture;
+
+// where a glyph's bitmap is in the atlas, in texels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AtlasRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+// Every glyph that has been drawn, rasterized once on the CPU and packed into one single channel
+// texture so all text can be drawn with one bind group. Glyphs are packed in rows (shelves), when the
+// texture is full it gets cleared and refilled with whatever is on screen next frame.
+pub struct GlyphAtlas {
+    pub texture: ColorTexture,
+    entries: HashMap<GlyphRasterConfig, AtlasRegion>,
+    cursor_x: u32,
+    cursor_y: u32,
+    row_height: u32,
+}
+
+impl GlyphAtlas {
+    pub const SIZE: u32 = 1024;
+    pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R8Unorm;
+    // empty texels between glyphs so linear filtering doesn't pick up the neighbour
+    const PADDING: u32 = 1;
+
+    pub fn new(device: &wgpu::Device) -> Self {
+        Self {
+            texture: ColorTexture::new(device, "Glyph Atlas", Self::SIZE, Self::SIZE, Self::FORMAT),
+            entries: HashMap::new(),
+            cursor_x: 0,
+            cursor_y: 0,
+            row_height: 0,
+        }
+    }
+
+    pub fn get(&self, key: &GlyphRasterConfig) -> Option<AtlasRegion> {
+        self.entries.get(key).copied()
+    }
+
+    // Rasterizes and uploads the glyph if it isn't in the atlas yet. None when there is no room left.
+    pub fn insert(&mut self, queue: &wgpu::Queue, font: &Font, key: GlyphRasterConfig) -> Option<AtlasRegion> {
+        if let Some(region) = self.get(&key) {
+            return Some(region);
+        }
+
+        let (metrics, coverage) = font.fontdue().rasterize_config(key);
+        let (width, height) = (metrics.width as u32, metrics.height as u32);
+
+        if self.cursor_x + width + Self::PADDING > Self::SIZE {
+            self.cursor_x = 0;
+            self.cursor_y += self.row_height + Self::PADDING;
+            self.row_height = 0;
+        }
+        if self.cursor_x + width + Self::PADDING > Self::SIZE || self.cursor_y + height + Self::PADDING > Self::SIZE {
+            return None;
+        }
+
+        let region = AtlasRegion {
+            x: self.cursor_x + Self::PADDING,
+            y: self.cursor_y + Self::PADDING,
+            width,
+            height,
+        };
+        self.texture.write_region(queue, region.x, region.y, width, height, &coverage);
+
+        self.cursor_x += width + Self::PADDING;
+        self.row_height = self.row_height.max(height);
+        self.entries.insert(key, region);
+        Some(region)
+    }
+
+    // Forgets every glyph, the old texels just get written over
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.cursor_x = 0;
+        self.cursor_y = 0;
+        self.row_height = 0;
+    }
+
+    // (u0, v0, u1, v1) for sampling
+    pub fn uv(&self, region: AtlasRegion) -> [f32; 4] {
+        let size = Self::SIZE as f32;
+        [
+            region.x as f32 / size,
+            region.y as f32 / size,
+            (region.x + region.width) as f32 / size,
+            (region.y + region.height) as f32 / size,
+        ]
+    }
+}
@@ -0,0 +1,48 @@
+use std::path::Path;
+use std::sync::Arc;
+use anyhow::{Context, Result, anyhow};
+
+// A loaded TTF / OTF font. Cloning is cheap, every clone shares the same parsed font.
+#[derive(Clone)]
+pub struct Font {
+    inner: Arc<fontdue::Font>,
+}
+
+impl Font {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let font = fontdue::Font::from_bytes(bytes, fontdue::FontSettings::default())
+            .map_err(|err| anyhow!("couldn't parse font: {}", err))?;
+        Ok(Self { inner: Arc::new(font) })
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path).with_context(|| format!("couldn't read font {}", path.display()))?;
+        Self::from_bytes(&bytes).with_context(|| format!("couldn't load font {}", path.display()))
+    }
+
+    // distance from one baseline to the next at `size` px
+    pub fn line_height(&self, size: f32) -> f32 {
+        self.inner
+            .horizontal_line_metrics(size)
+            .map(|metrics| metrics.new_line_size)
+            .unwrap_or(size)
+    }
+
+    pub(crate) fn fontdue(&self) -> &fontdue::Font {
+        &self.inner
+    }
+}
+
+// two handles are the same font if they came from the same load
+impl PartialEq for Font {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+impl std::fmt::Debug for Font {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Font").field("glyphs", &self.inner.glyph_count()).finish()
+    }
+}
@@ -0,0 +1,3 @@
+pub mod text;
+pub mod font;
+pub mod atlas;
@@ -0,0 +1,204 @@
+use fontdue::layout::{
+    CoordinateSystem, GlyphPosition, GlyphRasterConfig, HorizontalAlign, Layout, LayoutSettings, VerticalAlign as FontdueVerticalAlign,
+    WrapStyle,
+};
+use crate::geometry::rect::{Rect, Size};
+use crate::render::painter::Color;
+use crate::text::font::Font;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TextAlign {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VerticalAlign {
+    #[default]
+    Top,
+    Middle,
+    Bottom,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextStyle {
+    pub font: Font,
+    // logical pixels, glyphs get rasterized at size * scale factor so they stay sharp on HiDPI screens
+    pub size: f32,
+    pub color: Color,
+    pub align: TextAlign,
+    pub vertical_align: VerticalAlign,
+    // break lines between words so the text fits the width of its rect
+    pub wrap: bool,
+    // multiplier on the font's own line spacing
+    pub line_height: f32,
+}
+
+impl TextStyle {
+    pub fn new(font: Font, size: f32) -> Self {
+        Self {
+            font,
+            size,
+            color: Color::BLACK,
+            align: TextAlign::Left,
+            vertical_align: VerticalAlign::Top,
+            wrap: false,
+            line_height: 1.0,
+        }
+    }
+
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn with_align(mut self, align: TextAlign) -> Self {
+        self.align = align;
+        self
+    }
+
+    pub fn with_vertical_align(mut self, vertical_align: VerticalAlign) -> Self {
+        self.vertical_align = vertical_align;
+        self
+    }
+
+    pub fn with_wrap(mut self, wrap: bool) -> Self {
+        self.wrap = wrap;
+        self
+    }
+
+    pub fn with_line_height(mut self, line_height: f32) -> Self {
+        self.line_height = line_height;
+        self
+    }
+}
+
+// One glyph ready to draw. `key` finds its bitmap in the GlyphAtlas, `rect` is where it goes in logical
+// pixels (snapped so the bitmap lands on whole physical pixels).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlacedGlyph {
+    pub key: GlyphRasterConfig,
+    pub rect: Rect,
+}
+
+// How much room `text` takes up. With wrapping on, lines break to fit `max_width`.
+pub fn measure_text(text: &str, style: &TextStyle, max_width: Option<f32>) -> Size {
+    let max_width = if style.wrap { max_width } else { None };
+    let layout = run_layout(text, style, 1.0, LayoutSettings {
+        max_width,
+        ..measure_settings(style)
+    });
+    Size::new(widest_line(&layout, style, 1.0), layout.height())
+}
+
+// Positions every glyph of `text` inside `bounds`. Shaping is simple left to right, no kerning, ligatures
+// or right to left scripts.
+pub fn layout_text(text: &str, style: &TextStyle, bounds: Rect, scale_factor: f32) -> Vec<PlacedGlyph> {
+    let scale = if scale_factor > 0.0 { scale_factor } else { 1.0 };
+    let left = bounds.x * scale;
+    let width = bounds.width * scale;
+
+    let mut layout_settings = LayoutSettings {
+        x: left,
+        y: bounds.y * scale,
+        max_height: Some(bounds.height * scale),
+        ..settings(style)
+    };
+    // fontdue can only align inside max_width and it always wraps there, so text that doesn't wrap is
+    // laid out from the left and each line gets moved over below
+    if style.wrap {
+        layout_settings.max_width = Some(width);
+    } else {
+        layout_settings.horizontal_align = HorizontalAlign::Left;
+    }
+    let layout = run_layout(text, style, scale, layout_settings);
+
+    let glyphs = layout.glyphs();
+    let mut shifts = vec![0.0; glyphs.len()];
+    let factor = match style.align {
+        TextAlign::Left => 0.0,
+        TextAlign::Center => 0.5,
+        TextAlign::Right => 1.0,
+    };
+    if !style.wrap && factor > 0.0 {
+        for line in layout.lines().into_iter().flatten().filter(|line| line.glyph_start < glyphs.len()) {
+            let line_glyphs = line.glyph_start..=line.glyph_end.min(glyphs.len() - 1);
+            let line_width = line_width(&glyphs[line_glyphs.clone()], style, scale);
+            shifts[line_glyphs].fill(((width - line_width) * factor).floor());
+        }
+    }
+
+    glyphs
+        .iter()
+        .zip(shifts)
+        .filter(|(glyph, _)| glyph.width > 0 && glyph.height > 0)
+        .map(|(glyph, shift)| PlacedGlyph {
+            key: glyph.key,
+            rect: Rect::new(
+                (glyph.x + shift).round() / scale,
+                glyph.y.round() / scale,
+                glyph.width as f32 / scale,
+                glyph.height as f32 / scale,
+            ),
+        })
+        .collect()
+}
+
+fn settings(style: &TextStyle) -> LayoutSettings {
+    LayoutSettings {
+        horizontal_align: match style.align {
+            TextAlign::Left => HorizontalAlign::Left,
+            TextAlign::Center => HorizontalAlign::Center,
+            TextAlign::Right => HorizontalAlign::Right,
+        },
+        vertical_align: match style.vertical_align {
+            VerticalAlign::Top => FontdueVerticalAlign::Top,
+            VerticalAlign::Middle => FontdueVerticalAlign::Middle,
+            VerticalAlign::Bottom => FontdueVerticalAlign::Bottom,
+        },
+        line_height: style.line_height,
+        wrap_style: WrapStyle::Word,
+        wrap_hard_breaks: true,
+        ..LayoutSettings::default()
+    }
+}
+
+// alignment doesn't change the size, and without a max_width fontdue would align around x = 0
+fn measure_settings(style: &TextStyle) -> LayoutSettings {
+    LayoutSettings {
+        horizontal_align: HorizontalAlign::Left,
+        vertical_align: FontdueVerticalAlign::Top,
+        ..settings(style)
+    }
+}
+
+fn run_layout(text: &str, style: &TextStyle, scale: f32, settings: LayoutSettings) -> Layout {
+    let mut layout = Layout::new(CoordinateSystem::PositiveYDown);
+    layout.reset(&settings);
+    layout.append(&[style.font.fontdue()], &fontdue::layout::TextStyle::new(text, style.size * scale, 0));
+    layout
+}
+
+// How far the pen moves over these glyphs. fontdue rounds every advance up, this has to do the same or
+// text measured at this width would wrap again when it gets laid out.
+fn line_width(glyphs: &[GlyphPosition], style: &TextStyle, scale: f32) -> f32 {
+    let font = style.font.fontdue();
+    glyphs
+        .iter()
+        .filter(|glyph| !glyph.char_data.is_control())
+        .map(|glyph| font.metrics_indexed(glyph.key.glyph_index, style.size * scale).advance_width.ceil())
+        .sum()
+}
+
+fn widest_line(layout: &Layout, style: &TextStyle, scale: f32) -> f32 {
+    let glyphs = layout.glyphs();
+    layout
+        .lines()
+        .into_iter()
+        .flatten()
+        .filter(|line| line.glyph_start < glyphs.len())
+        .map(|line| line_width(&glyphs[line.glyph_start..=line.glyph_end.min(glyphs.len() - 1)], style, scale))
+        .fold(0.0, f32::max)
+}
@@ -0,0 +1,134 @@
+use std::path::Path;
+use anyhow::{Context, Result};
+use image::RgbaImage;
+use crate::textures::textures::{ColorTexture, SamplerOptions};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextureOptions {
+    // Pictures, icons and albedo maps are stored as sRGB and should be true, the GPU then converts them to
+    // linear when sampling. Data textures (normal maps, roughness, masks) are already linear, use false.
+    pub srgb: bool,
+    // builds the whole mip chain on the CPU, textures drawn smaller than their size stop shimmering
+    pub mipmaps: bool,
+    pub sampler: SamplerOptions,
+}
+
+impl Default for TextureOptions {
+    fn default() -> Self {
+        Self {
+            srgb: true,
+            mipmaps: false,
+            sampler: SamplerOptions::default(),
+        }
+    }
+}
+
+impl TextureOptions {
+    pub fn with_mipmaps(mut self) -> Self {
+        self.mipmaps = true;
+        self
+    }
+
+    pub fn linear(mut self) -> Self {
+        self.srgb = false;
+        self
+    }
+
+    pub fn with_sampler(mut self, sampler: SamplerOptions) -> Self {
+        self.sampler = sampler;
+        self
+    }
+}
+
+impl ColorTexture {
+    // PNG or JPEG in memory, the format is worked out from the bytes
+    pub fn from_bytes(device: &wgpu::Device, queue: &wgpu::Queue, bytes: &[u8], options: &TextureOptions) -> Result<Self> {
+        let image = image::load_from_memory(bytes).context("couldn't decode image")?;
+        Ok(Self::from_image(device, queue, &image.to_rgba8(), "Image Texture", options))
+    }
+
+    pub fn from_path(device: &wgpu::Device, queue: &wgpu::Queue, path: impl AsRef<Path>, options: &TextureOptions) -> Result<Self> {
+        let path = path.as_ref();
+        let image = image::open(path).with_context(|| format!("couldn't load image {}", path.display()))?;
+        let label = path.file_name().and_then(|name| name.to_str()).unwrap_or("Image Texture");
+        Ok(Self::from_image(device, queue, &image.to_rgba8(), label, options))
+    }
+
+    pub fn from_image(device: &wgpu::Device, queue: &wgpu::Queue, image: &RgbaImage, label: &str, options: &TextureOptions) -> Self {
+        let format = if options.srgb {
+            wgpu::TextureFormat::Rgba8UnormSrgb
+        } else {
+            wgpu::TextureFormat::Rgba8Unorm
+        };
+        let (width, height) = (image.width().max(1), image.height().max(1));
+        let mip_level_count = if options.mipmaps { mip_level_count(width, height) } else { 1 };
+
+        let texture = Self::with_options(device, label, width, height, format, mip_level_count, &options.sampler);
+        texture.write_region(queue, 0, 0, image.width(), image.height(), image.as_raw());
+
+        let mut level = image.clone();
+        for mip_level in 1..mip_level_count {
+            level = downsample(&level, options.srgb);
+            texture.write_mip(queue, mip_level, 0, 0, level.width(), level.height(), level.as_raw());
+        }
+
+        texture
+    }
+}
+
+// halving until 1x1, 256x64 has 9 levels
+pub fn mip_level_count(width: u32, height: u32) -> u32 {
+    32 - width.max(height).max(1).leading_zeros()
+}
+
+// Half size (rounded down, at least 1px) with a 2x2 box filter. sRGB texels get averaged in linear space,
+// averaging the encoded values makes every mip darker than the one above it. sRGB textures are colors so
+// those are also weighted by alpha, fully transparent texels don't bleed their (usually black) color into
+// the edges. Linear textures are data (normals, roughness, masks) where the alpha channel means something
+// else, every channel there is a plain average.
+pub fn downsample(source: &RgbaImage, srgb: bool) -> RgbaImage {
+    let (width, height) = ((source.width() / 2).max(1), (source.height() / 2).max(1));
+
+    RgbaImage::from_fn(width, height, |x, y| {
+        let mut color = [0.0f32; 3];
+        let mut alpha = 0.0;
+        for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+            let sx = (x * 2 + dx).min(source.width() - 1);
+            let sy = (y * 2 + dy).min(source.height() - 1);
+            let texel = source.get_pixel(sx, sy).0;
+            let a = texel[3] as f32 / 255.0;
+            for (channel, value) in color.iter_mut().zip(texel) {
+                *channel += if srgb { srgb_to_linear(value) * a } else { value as f32 / 255.0 };
+            }
+            alpha += a;
+        }
+
+        let rgb = if !srgb {
+            color.map(|channel| (channel / 4.0 * 255.0).round() as u8)
+        } else if alpha > 0.0 {
+            color.map(|channel| linear_to_srgb(channel / alpha))
+        } else {
+            [0; 3]
+        };
+        image::Rgba([rgb[0], rgb[1], rgb[2], (alpha / 4.0 * 255.0).round() as u8])
+    })
+}
+
+fn srgb_to_linear(value: u8) -> f32 {
+    let c = value as f32 / 255.0;
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(value: f32) -> u8 {
+    let c = value.clamp(0.0, 1.0);
+    let encoded = if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    };
+    (encoded * 255.0).round() as u8
+}
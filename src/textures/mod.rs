@@ -1 +1,3 @@
-pub mod textures;
\ No newline at end of file
+pub mod textures;
+pub mod readback;
+pub mod loader;
@@ -0,0 +1,103 @@
+use anyhow::{Result, anyhow, bail};
+use image::RgbaImage;
+use std::path::Path;
+use std::sync::mpsc;
+
+// Copies a rendered texture back to the CPU as tightly packed RGBA8.
+//
+// The GPU copy needs every row to be padded to COPY_BYTES_PER_ROW_ALIGNMENT (256 bytes), so a 100px wide
+// texture is 400 bytes of pixels + 112 bytes of padding per row in the staging buffer. The padding gets
+// stripped here so the image comes out width * 4 bytes per row.
+//
+// configure_surface prefers an sRGB format and on most platforms that ends up being Bgra8UnormSrgb.
+// The bytes in an sRGB texture are already sRGB encoded which is what PNG expects, so they are copied as is,
+// BGRA just gets swizzled to RGBA. A plain Unorm surface is shown on screen as is too so same deal.
+pub fn read_texture_rgba8(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+) -> Result<RgbaImage> {
+    let format = texture.format();
+    let swizzle_bgra = match format {
+        wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+        wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+        other => bail!("can't read back {:?}, only 8 bit RGBA/BGRA textures are supported", other),
+    };
+
+    let width = texture.width();
+    let height = texture.height();
+    let unpadded_bytes_per_row = width * 4;
+    let padded_bytes_per_row = unpadded_bytes_per_row.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+        * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+
+    let staging = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Readback Buffer"),
+        size: (padded_bytes_per_row * height) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Readback Encoder"),
+    });
+
+    encoder.copy_texture_to_buffer(
+        wgpu::TexelCopyTextureInfo {
+            texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All,
+        },
+        wgpu::TexelCopyBufferInfo {
+            buffer: &staging,
+            layout: wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_bytes_per_row),
+                rows_per_image: Some(height),
+            },
+        },
+        wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+    );
+
+    queue.submit(std::iter::once(encoder.finish()));
+
+    // map_async only fires its callback when the device gets polled, so block on the poll and then on the channel
+    let slice = staging.slice(..);
+    let (sender, receiver) = mpsc::channel();
+    slice.map_async(wgpu::MapMode::Read, move |result| {
+        let _ = sender.send(result);
+    });
+    device.poll(wgpu::PollType::Wait)?;
+    receiver
+        .recv()
+        .map_err(|_| anyhow!("readback buffer was dropped before it was mapped"))??;
+
+    let mut pixels = Vec::with_capacity((unpadded_bytes_per_row * height) as usize);
+    {
+        let mapped = slice.get_mapped_range();
+        for row in mapped.chunks(padded_bytes_per_row as usize) {
+            pixels.extend_from_slice(&row[..unpadded_bytes_per_row as usize]);
+        }
+    }
+    staging.unmap();
+
+    if swizzle_bgra {
+        for pixel in pixels.chunks_exact_mut(4) {
+            pixel.swap(0, 2);
+        }
+    }
+
+    RgbaImage::from_raw(width, height, pixels)
+        .ok_or_else(|| anyhow!("readback returned the wrong number of bytes for {}x{}", width, height))
+}
+
+pub fn save_png(image: &RgbaImage, path: impl AsRef<Path>) -> Result<()> {
+    let path = path.as_ref();
+    image
+        .save_with_format(path, image::ImageFormat::Png)
+        .map_err(|e| anyhow!("failed to write {}: {}", path.display(), e))
+}
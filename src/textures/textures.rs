@@ -1,4 +1,3 @@
-use wgpu::Color;
 // 2D Color Texture	Regular images or UI textures
 // Depth Texture	Used for depth testing during rendering
 // Cube Map	Skyboxes, reflections
@@ -7,9 +6,12 @@ use wgpu::Color;
 // Storage Texture	Read/write access in compute shaders
 // Multisampled Texture	For anti-aliased rendering
 // Stencil/Depth-Stencil
+// Offscreen Texture	Render target that is not a window surface (headless / tests)
 use wgpu::TextureDescriptor;
-use wgpu::TextureView;
 use std::sync::Arc;
+use anyhow::Result;
+use image::RgbaImage;
+use crate::textures::readback::read_texture_rgba8;
 
 pub enum TextureType {
     Color(ColorTexture),
@@ -19,7 +21,7 @@ pub enum TextureType {
     Storage(StorageTexture),
     Multisampled(MultisampledTexture),
     StencilDepth(StencilDepthTexture),
-
+    Offscreen(OffscreenTexture),
 }
 
 // pub trait TextureProperties {
@@ -144,6 +146,16 @@ impl TextureProperties for StencilDepthTexture {
     }
 }   
 
+impl TextureProperties for OffscreenTexture {
+    fn view(&self) -> Arc<wgpu::TextureView> {
+        self.view.clone()
+    }
+
+    fn format(&self) -> wgpu::TextureFormat {
+        self.format
+    }
+}
+
 impl TextureType {
     pub fn info(&self) -> &dyn TextureProperties {
         match self {
@@ -154,14 +166,173 @@ impl TextureType {
             TextureType::Storage(t) => t,
             TextureType::Multisampled(t) => t,
             TextureType::StencilDepth(t) => t,
+            TextureType::Offscreen(t) => t,
+        }
+    }
+}
+
+// How a texture gets filtered and what happens outside 0..1
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SamplerOptions {
+    pub address_mode: wgpu::AddressMode,
+    pub mag_filter: wgpu::FilterMode,
+    pub min_filter: wgpu::FilterMode,
+    pub mipmap_filter: wgpu::FilterMode,
+    // 1 is off, only works when all three filters are Linear
+    pub anisotropy: u16,
+}
+
+impl Default for SamplerOptions {
+    fn default() -> Self {
+        Self {
+            address_mode: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            anisotropy: 1,
+        }
+    }
+}
+
+impl SamplerOptions {
+    // blocky, for pixel art and icons drawn at exactly their size
+    pub fn nearest() -> Self {
+        Self {
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
         }
     }
+
+    // tiles the texture, for meshes with uvs outside 0..1
+    pub fn repeat() -> Self {
+        Self {
+            address_mode: wgpu::AddressMode::Repeat,
+            ..Default::default()
+        }
+    }
+
+    pub fn create(&self, device: &wgpu::Device, label: &str) -> wgpu::Sampler {
+        let all_linear = [self.mag_filter, self.min_filter, self.mipmap_filter]
+            .iter()
+            .all(|filter| *filter == wgpu::FilterMode::Linear);
+        // wgpu rejects anisotropy with anything but linear filtering
+        let anisotropy_clamp = if all_linear { self.anisotropy.clamp(1, 16) } else { 1 };
+
+        device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some(label),
+            address_mode_u: self.address_mode,
+            address_mode_v: self.address_mode,
+            address_mode_w: self.address_mode,
+            mag_filter: self.mag_filter,
+            min_filter: self.min_filter,
+            mipmap_filter: self.mipmap_filter,
+            anisotropy_clamp,
+            ..Default::default()
+        })
+    }
 }
 
+// A texture to sample in shaders (images, the glyph atlas). Comes with its own sampler so binding it
+// is one call, see BindGrouping::texture. Images get loaded with ColorTexture::from_bytes / from_path.
 pub struct ColorTexture {
+    pub texture: wgpu::Texture,
     pub view: Arc<wgpu::TextureView>,
+    pub sampler: wgpu::Sampler,
     pub format: wgpu::TextureFormat,
+    pub width: u32,
+    pub height: u32,
+    pub mip_level_count: u32,
+}
+
+impl ColorTexture {
+    // An empty texture with one mip level and the default sampler, fill it with write_region
+    pub fn new(device: &wgpu::Device, label: &str, width: u32, height: u32, format: wgpu::TextureFormat) -> Self {
+        Self::with_options(device, label, width, height, format, 1, &SamplerOptions::default())
+    }
+
+    pub fn with_options(
+        device: &wgpu::Device,
+        label: &str,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        mip_level_count: u32,
+        sampler: &SamplerOptions,
+    ) -> Self {
+        let texture = device.create_texture(&TextureDescriptor {
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            // COPY_SRC so it can be read back like a frame
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST | wgpu::TextureUsages::COPY_SRC,
+            label: Some(label),
+            view_formats: &[],
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        Self {
+            texture,
+            view: Arc::new(view),
+            sampler: sampler.create(device, label),
+            format,
+            width,
+            height,
+            mip_level_count,
+        }
+    }
+
+    // Bind groups made before this still have the old sampler, make them again
+    pub fn set_sampler(&mut self, device: &wgpu::Device, sampler: &SamplerOptions) {
+        self.sampler = sampler.create(device, "Color Texture Sampler");
+    }
+
+    // Uploads tightly packed pixels into the rectangle at (x, y). The queue takes care of row padding.
+    pub fn write_region(&self, queue: &wgpu::Queue, x: u32, y: u32, width: u32, height: u32, pixels: &[u8]) {
+        self.write_mip(queue, 0, x, y, width, height, pixels);
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn write_mip(&self, queue: &wgpu::Queue, mip_level: u32, x: u32, y: u32, width: u32, height: u32, pixels: &[u8]) {
+        if width == 0 || height == 0 {
+            return;
+        }
+        let bytes_per_pixel = self.format.block_copy_size(None).unwrap_or(4);
+        queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture: &self.texture,
+                mip_level,
+                origin: wgpu::Origin3d { x, y, z: 0 },
+                aspect: wgpu::TextureAspect::All,
+            },
+            pixels,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(width * bytes_per_pixel),
+                rows_per_image: Some(height),
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
+    // Copies mip level 0 back to the CPU
+    pub fn read_rgba8(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> Result<RgbaImage> {
+        read_texture_rgba8(device, queue, &self.texture)
+    }
 }
+
 pub struct DepthTexture {
      pub view: Arc<wgpu::TextureView>,
     pub format: wgpu::TextureFormat,
@@ -185,6 +356,36 @@ pub struct StorageTexture {
 pub struct MultisampledTexture  {
     pub view: Arc<wgpu::TextureView>,
     pub format: wgpu::TextureFormat,
+    pub sample_count: u32,
+}
+
+impl MultisampledTexture {
+    // The color target for MSAA. The pass draws into this and resolves into the single sampled view
+    // (swapchain / offscreen) so it has to match that view's size and format.
+    pub fn new(device: &wgpu::Device, width: u32, height: u32, format: wgpu::TextureFormat, sample_count: u32) -> Self {
+        let texture = device.create_texture(&TextureDescriptor {
+            size: wgpu::Extent3d {
+                width: width.max(1),
+                height: height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            label: Some("Multisampled Texture"),
+            view_formats: &[],
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        Self {
+            view: Arc::new(view),
+            format,
+            sample_count,
+        }
+    }
 }
 
 pub struct StencilDepthTexture {
@@ -193,19 +394,31 @@ pub struct StencilDepthTexture {
 }
 
 impl StencilDepthTexture {
+    pub const DEFAULT_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
     pub fn new(config:&wgpu::SurfaceConfiguration, device: &wgpu::Device) -> Self {
+        Self::with_format(device, config.width, config.height, Self::DEFAULT_FORMAT, 1)
+    }
 
-        let frmt = wgpu::TextureFormat::Depth32Float;
+    // Depth32Float for depth only, Depth24PlusStencil8 (or Depth32FloatStencil8) when a stencil is wanted too.
+    // It has to be the same size and sample count as the color target so it gets remade every resize.
+    pub fn with_format(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> Self {
         let depth_texture = device.create_texture(&TextureDescriptor {
         size: wgpu::Extent3d {
-            width: config.width,
-            height: config.height,
+            width: width.max(1),
+            height: height.max(1),
             depth_or_array_layers: 1,
             },
         mip_level_count: 1,
-        sample_count: 1,
+        sample_count,
         dimension: wgpu::TextureDimension::D2,
-        format: frmt,
+        format,
         usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
         label: Some("Depth Texture"),
         view_formats: &[],
@@ -215,8 +428,82 @@ impl StencilDepthTexture {
 
         Self {
             view: Arc::new(depth_view),
-            format: frmt,
+            format,
         }
- 
+
     }
-}
\ No newline at end of file
+
+    // The attachment for a render pass. Depth gets cleared to the far plane, stencil to 0 when the format has one.
+    pub fn attachment(&self) -> wgpu::RenderPassDepthStencilAttachment<'_> {
+        wgpu::RenderPassDepthStencilAttachment {
+            view: &self.view,
+            depth_ops: Some(wgpu::Operations {
+                // so if LoadOp::Load is set then the vertex and indices do not get drawn to the screen
+                // telling the gpu “Preserve whatever was already in this texture from the last frame — do not clear it, and do not initialize it.”
+                load: wgpu::LoadOp::Clear(1.0),
+                store: wgpu::StoreOp::Store,
+            }),
+            stencil_ops: self.format.has_stencil_aspect().then_some(wgpu::Operations {
+                load: wgpu::LoadOp::Clear(0),
+                store: wgpu::StoreOp::Store,
+            }),
+        }
+    }
+}
+
+// What a pipeline puts in its `depth_stencil` field. Every pipeline drawn in a pass with a depth attachment
+// needs one with the attachment's format, `write` and `compare` are what actually change per pipeline
+// (opaque geometry writes + Less, UI on top might be no write + Always).
+pub fn depth_stencil_state(format: wgpu::TextureFormat, write: bool, compare: wgpu::CompareFunction) -> wgpu::DepthStencilState {
+    wgpu::DepthStencilState {
+        format,
+        depth_write_enabled: write,
+        depth_compare: compare,
+        stencil: wgpu::StencilState::default(),
+        bias: wgpu::DepthBiasState::default(),
+    }
+}
+
+// A color target that lives on the GPU only, there is no surface behind it so it works without a window.
+// The texture is kept (not just the view) so the rendered frame can be copied out of it.
+pub struct OffscreenTexture {
+    pub texture: wgpu::Texture,
+    pub view: Arc<wgpu::TextureView>,
+    pub format: wgpu::TextureFormat,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl OffscreenTexture {
+    pub fn new(device: &wgpu::Device, width: u32, height: u32, format: wgpu::TextureFormat) -> Self {
+        let texture = device.create_texture(&TextureDescriptor {
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            label: Some("Offscreen Texture"),
+            view_formats: &[],
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        Self {
+            texture,
+            view: Arc::new(view),
+            format,
+            width,
+            height,
+        }
+    }
+
+    // Copies whatever was last rendered into this target back to the CPU
+    pub fn read_rgba8(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> Result<RgbaImage> {
+        read_texture_rgba8(device, queue, &self.texture)
+    }
+}
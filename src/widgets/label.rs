@@ -0,0 +1,35 @@
+use crate::geometry::rect::Size;
+use crate::render::painter::Painter;
+use crate::text::text::{TextStyle, measure_text};
+use crate::widgets::widget::{Widget, WidgetContext};
+
+// A piece of text. It measures to the size of the text, wrapped to the available width when the style
+// wraps.
+#[derive(Debug, Clone)]
+pub struct Label {
+    pub text: String,
+    pub style: TextStyle,
+}
+
+impl Label {
+    pub fn new(text: impl Into<String>, style: TextStyle) -> Self {
+        Self {
+            text: text.into(),
+            style,
+        }
+    }
+
+    pub fn set_text(&mut self, text: impl Into<String>) {
+        self.text = text.into();
+    }
+}
+
+impl Widget for Label {
+    fn measure(&mut self, available: Size) -> Size {
+        measure_text(&self.text, &self.style, Some(available.width))
+    }
+
+    fn paint(&self, ctx: &WidgetContext, painter: &mut Painter) {
+        painter.text(ctx.bounds, self.text.clone(), &self.style);
+    }
+}
@@ -0,0 +1,5 @@
+pub mod widget;
+pub mod tree;
+pub mod panel;
+pub mod toggle;
+pub mod label;
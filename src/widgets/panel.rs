@@ -0,0 +1,36 @@
+use crate::geometry::rect::Size;
+use crate::render::painter::{Color, Painter};
+use crate::widgets::widget::{Widget, WidgetContext};
+
+// A plain container. It takes up all the space it is given and optionally fills it with a color,
+// its children are what actually show up.
+#[derive(Debug, Clone, Default)]
+pub struct Panel {
+    pub background: Option<Color>,
+    pub corner_radius: f32,
+}
+
+impl Panel {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_background(background: Color) -> Self {
+        Self {
+            background: Some(background),
+            corner_radius: 0.0,
+        }
+    }
+}
+
+impl Widget for Panel {
+    fn measure(&mut self, available: Size) -> Size {
+        available
+    }
+
+    fn paint(&self, ctx: &WidgetContext, painter: &mut Painter) {
+        if let Some(background) = self.background {
+            painter.fill_rounded_rect(ctx.bounds, self.corner_radius, background);
+        }
+    }
+}
@@ -0,0 +1,224 @@
+use std::cell::Cell;
+use std::rc::Rc;
+use winit::event::MouseButton;
+use winit::keyboard::KeyCode;
+use crate::geometry::rect::{Rect, Size};
+use crate::render::painter::{Color, Painter};
+use crate::widgets::widget::{EventResult, Widget, WidgetContext, WidgetEvent};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ToggleStyle {
+    pub width: f32,
+    pub height: f32,
+    pub track_off: Color,
+    pub track_on: Color,
+    pub knob: Color,
+    pub focus_ring: Color,
+    // how far the track gets mixed towards white while hovered
+    pub hover_highlight: f32,
+    // seconds for the knob to go from one end to the other
+    pub animation_time: f32,
+}
+
+impl Default for ToggleStyle {
+    fn default() -> Self {
+        Self {
+            width: 44.0,
+            height: 24.0,
+            track_off: Color::from_rgba8(120, 120, 128, 255),
+            track_on: Color::from_rgba8(52, 199, 89, 255),
+            knob: Color::WHITE,
+            focus_ring: Color::from_rgba8(10, 132, 255, 255),
+            hover_highlight: 0.15,
+            animation_time: 0.15,
+        }
+    }
+}
+
+// On / off switch. Click it or press and release Space / Enter while it has focus.
+//
+// let dark_mode = Rc::new(Cell::new(false));
+// tree.add_child(root, Toggle::new(false).bind(dark_mode.clone()).on_change(|on| println!("{on}")));
+pub struct Toggle {
+    on: bool,
+    disabled: bool,
+    // where the knob is drawn, 0 is all the way left (off) and 1 all the way right (on)
+    knob: f32,
+    // Space / Enter went down while focused, it flips when the key comes back up so key repeat can't flip it again
+    key_held: bool,
+    on_change: Option<Box<dyn FnMut(bool)>>,
+    bound: Option<Rc<Cell<bool>>>,
+    pub style: ToggleStyle,
+}
+
+impl Toggle {
+    pub fn new(on: bool) -> Self {
+        Self {
+            on,
+            disabled: false,
+            knob: if on { 1.0 } else { 0.0 },
+            key_held: false,
+            on_change: None,
+            bound: None,
+            style: ToggleStyle::default(),
+        }
+    }
+
+    // called with the new value every time the user flips the switch
+    pub fn on_change(mut self, callback: impl FnMut(bool) + 'static) -> Self {
+        self.on_change = Some(Box::new(callback));
+        self
+    }
+
+    // Keeps the switch and `value` the same both ways. The toggle starts out at whatever `value` holds.
+    pub fn bind(mut self, value: Rc<Cell<bool>>) -> Self {
+        self.on = value.get();
+        self.knob = if self.on { 1.0 } else { 0.0 };
+        self.bound = Some(value);
+        self
+    }
+
+    pub fn disabled(mut self, disabled: bool) -> Self {
+        self.set_disabled(disabled);
+        self
+    }
+
+    pub fn with_style(mut self, style: ToggleStyle) -> Self {
+        self.style = style;
+        self
+    }
+
+    pub fn is_on(&self) -> bool {
+        self.on
+    }
+
+    pub fn is_disabled(&self) -> bool {
+        self.disabled
+    }
+
+    // a key that was down when the toggle got disabled shouldn't flip it once it's enabled again
+    pub fn set_disabled(&mut self, disabled: bool) {
+        self.disabled = disabled;
+        if disabled {
+            self.key_held = false;
+        }
+    }
+
+    // Changes the value from code. The knob still animates over but on_change isn't called.
+    pub fn set_on(&mut self, on: bool) {
+        self.on = on;
+        if let Some(bound) = &self.bound {
+            bound.set(on);
+        }
+    }
+
+    fn flip(&mut self) {
+        self.set_on(!self.on);
+        if let Some(on_change) = self.on_change.as_mut() {
+            on_change(self.on);
+        }
+    }
+
+    // the switch is drawn at its style size in the top left of whatever bounds layout gave it
+    fn track(&self, bounds: Rect) -> Rect {
+        Rect::new(
+            bounds.x,
+            bounds.y,
+            self.style.width.min(bounds.width),
+            self.style.height.min(bounds.height),
+        )
+    }
+}
+
+impl Widget for Toggle {
+    fn measure(&mut self, available: Size) -> Size {
+        Size::new(
+            self.style.width.min(available.width),
+            self.style.height.min(available.height),
+        )
+    }
+
+    fn paint(&self, ctx: &WidgetContext, painter: &mut Painter) {
+        let track = self.track(ctx.bounds);
+        let radius = track.height * 0.5;
+
+        if ctx.focused && !self.disabled {
+            painter.fill_rounded_rect(track.inset(-2.0), radius + 2.0, self.style.focus_ring);
+        }
+
+        let mut track_color = self.style.track_off.lerp(self.style.track_on, self.knob);
+        if ctx.hovered && !self.disabled {
+            track_color = track_color.lerp(Color::WHITE, self.style.hover_highlight);
+        }
+
+        // the knob stretches a little towards the middle while it is held down
+        let padding = 2.0;
+        let knob_height = track.height - padding * 2.0;
+        let knob_width = if ctx.pressed && !self.disabled { knob_height * 1.25 } else { knob_height };
+        let travel = track.width - padding * 2.0 - knob_width;
+        let knob = Rect::new(track.x + padding + travel * self.knob, track.y + padding, knob_width, knob_height);
+
+        // disabled switches are drawn faded
+        let alpha = if self.disabled { 0.4 } else { 1.0 };
+        painter.fill_rounded_rect(track, radius, track_color.with_alpha(track_color.a * alpha));
+        painter.fill_rounded_rect(knob, knob_height * 0.5, self.style.knob.with_alpha(self.style.knob.a * alpha));
+    }
+
+    fn event(&mut self, _ctx: &WidgetContext, event: &WidgetEvent) -> EventResult {
+        // the release goes to whatever has focus now, coming back later mustn't flip on an old press
+        if let WidgetEvent::FocusLost = event {
+            self.key_held = false;
+        }
+        if self.disabled {
+            return EventResult::Ignored;
+        }
+
+        match *event {
+            WidgetEvent::PointerDown { button: MouseButton::Left, .. } => EventResult::Handled,
+            WidgetEvent::PointerUp { button: MouseButton::Left, inside, .. } => {
+                // dragging off the switch before letting go cancels the click
+                if inside {
+                    self.flip();
+                }
+                EventResult::Handled
+            }
+            WidgetEvent::Key { code: KeyCode::Space | KeyCode::Enter | KeyCode::NumpadEnter, pressed } => {
+                if pressed {
+                    self.key_held = true;
+                } else if self.key_held {
+                    self.key_held = false;
+                    self.flip();
+                }
+                EventResult::Handled
+            }
+            _ => EventResult::Ignored,
+        }
+    }
+
+    fn update(&mut self, dt: f32) -> bool {
+        // something else might have changed the bound value
+        if let Some(bound) = &self.bound {
+            self.on = bound.get();
+        }
+
+        let target = if self.on { 1.0 } else { 0.0 };
+        let step = if self.style.animation_time > 0.0 { dt / self.style.animation_time } else { 1.0 };
+        if self.knob < target {
+            self.knob = (self.knob + step).min(target);
+        } else if self.knob > target {
+            self.knob = (self.knob - step).max(target);
+        }
+        self.knob != target
+    }
+
+    fn focusable(&self) -> bool {
+        !self.disabled
+    }
+}
+
+impl Default for Toggle {
+    fn default() -> Self {
+        Self::new(false)
+    }
+}
+
@@ -0,0 +1,630 @@
+use std::any::Any;
+use winit::event::MouseButton;
+use winit::keyboard::KeyCode;
+use crate::geometry::rect::{Point, Rect, Size};
+use crate::layout::flex::{LayoutTree, compute_layout};
+use crate::layout::style::Style;
+use crate::render::painter::Painter;
+use crate::widgets::widget::{EventResult, Widget, WidgetContext, WidgetEvent, WidgetId};
+
+struct Node {
+    widget: Box<dyn Widget>,
+    parent: Option<WidgetId>,
+    children: Vec<WidgetId>,
+    style: Style,
+    bounds: Rect,
+}
+
+struct Slot {
+    generation: u32,
+    node: Option<Node>,
+}
+
+// The retained widget tree. Widgets live here between frames and are addressed by WidgetId.
+// The window feeds it winit input (in logical pixels) and once a frame it lays itself out and
+// paints into a Painter.
+#[derive(Default)]
+pub struct WidgetTree {
+    slots: Vec<Slot>,
+    free: Vec<u32>,
+    root: Option<WidgetId>,
+    viewport: Size,
+    needs_layout: bool,
+    pointer: Option<Point>,
+    hovered: Option<WidgetId>,
+    pressed: Option<WidgetId>,
+    focused: Option<WidgetId>,
+    // either Shift key is down, Tab goes backwards
+    shift_held: bool,
+}
+
+impl WidgetTree {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn root(&self) -> Option<WidgetId> {
+        self.root
+    }
+
+    // Replaces the whole tree with a single widget
+    pub fn set_root(&mut self, widget: impl Widget) -> WidgetId {
+        if let Some(root) = self.root {
+            self.remove(root);
+        }
+        let id = self.insert(Box::new(widget), None);
+        self.root = Some(id);
+        id
+    }
+
+    // None if the parent has been removed
+    pub fn add_child(&mut self, parent: WidgetId, widget: impl Widget) -> Option<WidgetId> {
+        self.node(parent)?;
+        let id = self.insert(Box::new(widget), Some(parent));
+        self.node_mut(parent)?.children.push(id);
+        Some(id)
+    }
+
+    // Removes the widget and everything under it
+    pub fn remove(&mut self, id: WidgetId) {
+        let Some(node) = self.node(id) else {
+            return;
+        };
+        let parent = node.parent;
+        let children = node.children.clone();
+
+        for child in children {
+            self.remove(child);
+        }
+
+        if let Some(parent) = parent.and_then(|parent| self.node_mut(parent)) {
+            parent.children.retain(|child| *child != id);
+        }
+        if self.root == Some(id) {
+            self.root = None;
+        }
+        for tracked in [&mut self.hovered, &mut self.pressed, &mut self.focused] {
+            if *tracked == Some(id) {
+                *tracked = None;
+            }
+        }
+
+        let slot = &mut self.slots[id.index as usize];
+        slot.node = None;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(id.index);
+        self.needs_layout = true;
+    }
+
+    pub fn contains(&self, id: WidgetId) -> bool {
+        self.node(id).is_some()
+    }
+
+    pub fn get<T: Widget>(&self, id: WidgetId) -> Option<&T> {
+        let widget: &dyn Any = self.node(id)?.widget.as_ref();
+        widget.downcast_ref::<T>()
+    }
+
+    // Changing a widget might change its size so the next frame lays the tree out again
+    pub fn get_mut<T: Widget>(&mut self, id: WidgetId) -> Option<&mut T> {
+        self.needs_layout = true;
+        let widget: &mut dyn Any = self.node_mut(id)?.widget.as_mut();
+        widget.downcast_mut::<T>()
+    }
+
+    pub fn parent(&self, id: WidgetId) -> Option<WidgetId> {
+        self.node(id)?.parent
+    }
+
+    pub fn children(&self, id: WidgetId) -> &[WidgetId] {
+        self.node(id).map(|node| node.children.as_slice()).unwrap_or(&[])
+    }
+
+    pub fn bounds(&self, id: WidgetId) -> Option<Rect> {
+        Some(self.node(id)?.bounds)
+    }
+
+    pub fn style(&self, id: WidgetId) -> Option<&Style> {
+        self.node(id).map(|node| &node.style)
+    }
+
+    // how the widget is sized and placed inside its parent, and how it lays out its own children
+    pub fn set_style(&mut self, id: WidgetId, style: Style) {
+        if let Some(node) = self.node_mut(id) {
+            node.style = style;
+            self.needs_layout = true;
+        }
+    }
+
+    pub fn focused(&self) -> Option<WidgetId> {
+        self.focused
+    }
+
+    pub fn hovered(&self) -> Option<WidgetId> {
+        self.hovered
+    }
+
+    // logical size of the window, called on resize
+    pub fn set_viewport(&mut self, viewport: Size) {
+        if self.viewport != viewport {
+            self.viewport = viewport;
+            self.needs_layout = true;
+        }
+    }
+
+    pub fn viewport(&self) -> Size {
+        self.viewport
+    }
+
+    pub fn mark_needs_layout(&mut self) {
+        self.needs_layout = true;
+    }
+
+    pub fn layout_if_needed(&mut self) {
+        if self.needs_layout {
+            self.layout();
+        }
+    }
+
+    // Runs the flex layout over the whole tree, the root fills the viewport
+    pub fn layout(&mut self) {
+        self.needs_layout = false;
+        if let Some(root) = self.root {
+            compute_layout(self, root, self.viewport);
+        }
+    }
+
+    // Once a frame, returns true if any widget is still animating
+    pub fn update(&mut self, dt: f32) -> bool {
+        let mut animating = false;
+        for slot in &mut self.slots {
+            if let Some(node) = slot.node.as_mut() {
+                animating |= node.widget.update(dt);
+            }
+        }
+        animating
+    }
+
+    // Parents paint before their children so children end up on top
+    pub fn paint(&self, painter: &mut Painter) {
+        if let Some(root) = self.root {
+            self.paint_node(root, painter);
+        }
+    }
+
+    fn paint_node(&self, id: WidgetId, painter: &mut Painter) {
+        let Some(node) = self.node(id) else {
+            return;
+        };
+        node.widget.paint(&self.context(id, node), painter);
+        for child in &node.children {
+            self.paint_node(*child, painter);
+        }
+    }
+
+    // The topmost widget under `point`, children win over their parents and later siblings over earlier ones
+    pub fn hit_test(&self, point: Point) -> Option<WidgetId> {
+        self.hit_test_node(self.root?, point)
+    }
+
+    fn hit_test_node(&self, id: WidgetId, point: Point) -> Option<WidgetId> {
+        let node = self.node(id)?;
+        if !node.bounds.contains(point) {
+            return None;
+        }
+        node.children
+            .iter()
+            .rev()
+            .find_map(|child| self.hit_test_node(*child, point))
+            .or(Some(id))
+    }
+
+    // Input from the window. Positions are logical pixels. They return true when a widget handled the
+    // event, the window then doesn't pass it on to the camera.
+
+    pub fn pointer_moved(&mut self, position: Point) -> bool {
+        self.pointer = Some(position);
+        let hit = self.hit_test(position);
+
+        if hit != self.hovered {
+            if let Some(old) = self.hovered {
+                self.send(old, &WidgetEvent::PointerLeave);
+            }
+            self.hovered = hit;
+            if let Some(new) = hit {
+                self.send(new, &WidgetEvent::PointerEnter);
+            }
+        }
+
+        // while a button is held the pressed widget keeps getting the moves (dragging a slider off its track)
+        match self.pressed.or(hit) {
+            Some(target) => self.bubble(target, &WidgetEvent::PointerMove { position }) == EventResult::Handled,
+            None => false,
+        }
+    }
+
+    pub fn pointer_left(&mut self) {
+        self.pointer = None;
+        if let Some(old) = self.hovered.take() {
+            self.send(old, &WidgetEvent::PointerLeave);
+        }
+    }
+
+    pub fn pointer_button(&mut self, button: MouseButton, pressed: bool) -> bool {
+        let Some(position) = self.pointer else {
+            return false;
+        };
+
+        if pressed {
+            let hit = self.hit_test(position);
+            self.pressed = hit;
+
+            // clicking moves focus, clicking on nothing focusable clears it
+            let focus = hit.and_then(|hit| self.focusable_ancestor(hit));
+            self.set_focus(focus);
+
+            match hit {
+                Some(hit) => self.bubble(hit, &WidgetEvent::PointerDown { position, button }) == EventResult::Handled,
+                None => false,
+            }
+        } else {
+            let Some(target) = self.pressed.take() else {
+                return false;
+            };
+            let inside = self.bounds(target).is_some_and(|bounds| bounds.contains(position));
+            self.bubble(target, &WidgetEvent::PointerUp { position, button, inside }) == EventResult::Handled
+        }
+    }
+
+    pub fn key(&mut self, code: KeyCode, pressed: bool) -> bool {
+        if matches!(code, KeyCode::ShiftLeft | KeyCode::ShiftRight) {
+            self.shift_held = pressed;
+        }
+        let handled = match self.focused {
+            Some(focused) => self.bubble(focused, &WidgetEvent::Key { code, pressed }) == EventResult::Handled,
+            None => false,
+        };
+
+        if !handled && code == KeyCode::Tab && pressed {
+            return if self.shift_held { self.focus_previous() } else { self.focus_next() };
+        }
+        handled
+    }
+
+    pub fn set_focus(&mut self, id: Option<WidgetId>) {
+        if id == self.focused {
+            return;
+        }
+        if let Some(old) = self.focused {
+            self.send(old, &WidgetEvent::FocusLost);
+        }
+        self.focused = id;
+        if let Some(new) = id {
+            self.send(new, &WidgetEvent::FocusGained);
+        }
+    }
+
+    // Tab order is the order widgets were added in (depth first). Returns false when nothing can take focus.
+    pub fn focus_next(&mut self) -> bool {
+        self.step_focus(true)
+    }
+
+    // Shift-Tab, the same order backwards
+    pub fn focus_previous(&mut self) -> bool {
+        self.step_focus(false)
+    }
+
+    fn step_focus(&mut self, forward: bool) -> bool {
+        let mut order = Vec::new();
+        if let Some(root) = self.root {
+            self.collect_focusable(root, &mut order);
+        }
+        if order.is_empty() {
+            return false;
+        }
+
+        let len = order.len();
+        let next = match self.focused.and_then(|focused| order.iter().position(|id| *id == focused)) {
+            Some(current) if forward => order[(current + 1) % len],
+            Some(current) => order[(current + len - 1) % len],
+            None if forward => order[0],
+            None => order[len - 1],
+        };
+        self.set_focus(Some(next));
+        true
+    }
+
+    fn collect_focusable(&self, id: WidgetId, order: &mut Vec<WidgetId>) {
+        let Some(node) = self.node(id) else {
+            return;
+        };
+        if node.widget.focusable() {
+            order.push(id);
+        }
+        for child in &node.children {
+            self.collect_focusable(*child, order);
+        }
+    }
+
+    fn focusable_ancestor(&self, mut id: WidgetId) -> Option<WidgetId> {
+        loop {
+            let node = self.node(id)?;
+            if node.widget.focusable() {
+                return Some(id);
+            }
+            id = node.parent?;
+        }
+    }
+
+    // sends to `id` and then up through its parents until one handles it
+    fn bubble(&mut self, id: WidgetId, event: &WidgetEvent) -> EventResult {
+        let mut current = Some(id);
+        while let Some(id) = current {
+            if self.send(id, event) == EventResult::Handled {
+                return EventResult::Handled;
+            }
+            current = self.parent(id);
+        }
+        EventResult::Ignored
+    }
+
+    fn send(&mut self, id: WidgetId, event: &WidgetEvent) -> EventResult {
+        let ctx = match self.node(id) {
+            Some(node) => self.context(id, node),
+            None => return EventResult::Ignored,
+        };
+        let result = match self.node_mut(id) {
+            Some(node) => node.widget.event(&ctx, event),
+            None => EventResult::Ignored,
+        };
+        // a handled event usually changed something that can move things around
+        if result == EventResult::Handled {
+            self.needs_layout = true;
+        }
+        result
+    }
+
+    fn context(&self, id: WidgetId, node: &Node) -> WidgetContext {
+        WidgetContext {
+            id,
+            bounds: node.bounds,
+            hovered: self.hovered == Some(id),
+            pressed: self.pressed == Some(id),
+            focused: self.focused == Some(id),
+        }
+    }
+
+    fn insert(&mut self, widget: Box<dyn Widget>, parent: Option<WidgetId>) -> WidgetId {
+        self.needs_layout = true;
+        let node = Node {
+            widget,
+            parent,
+            children: Vec::new(),
+            style: Style::default(),
+            bounds: Rect::default(),
+        };
+
+        match self.free.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+                slot.node = Some(node);
+                WidgetId { index, generation: slot.generation }
+            }
+            None => {
+                self.slots.push(Slot { generation: 0, node: Some(node) });
+                WidgetId { index: self.slots.len() as u32 - 1, generation: 0 }
+            }
+        }
+    }
+
+    fn node(&self, id: WidgetId) -> Option<&Node> {
+        let slot = self.slots.get(id.index as usize)?;
+        if slot.generation != id.generation {
+            return None;
+        }
+        slot.node.as_ref()
+    }
+
+    fn node_mut(&mut self, id: WidgetId) -> Option<&mut Node> {
+        let slot = self.slots.get_mut(id.index as usize)?;
+        if slot.generation != id.generation {
+            return None;
+        }
+        slot.node.as_mut()
+    }
+}
+
+impl LayoutTree for WidgetTree {
+    type Id = WidgetId;
+
+    fn style(&self, id: WidgetId) -> Style {
+        self.node(id).map(|node| node.style).unwrap_or_default()
+    }
+
+    fn children(&self, id: WidgetId) -> Vec<WidgetId> {
+        self.node(id).map(|node| node.children.clone()).unwrap_or_default()
+    }
+
+    fn measure(&mut self, id: WidgetId, available: Size) -> Size {
+        match self.node_mut(id) {
+            Some(node) => node.widget.measure(available),
+            None => Size::ZERO,
+        }
+    }
+
+    fn set_bounds(&mut self, id: WidgetId, bounds: Rect) {
+        if let Some(node) = self.node_mut(id) {
+            node.bounds = bounds;
+            node.widget.layout(bounds);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use crate::layout::style::{Dimension, Inset};
+
+    type Log = Rc<RefCell<Vec<(&'static str, WidgetEvent)>>>;
+
+    // writes down every event it gets, handles the ones `handles` says yes to
+    struct Probe {
+        name: &'static str,
+        focusable: bool,
+        handles: fn(&WidgetEvent) -> bool,
+        log: Log,
+    }
+
+    impl Widget for Probe {
+        fn measure(&mut self, _available: Size) -> Size {
+            Size::ZERO
+        }
+
+        fn paint(&self, _ctx: &WidgetContext, _painter: &mut Painter) {}
+
+        fn event(&mut self, _ctx: &WidgetContext, event: &WidgetEvent) -> EventResult {
+            self.log.borrow_mut().push((self.name, *event));
+            if (self.handles)(event) { EventResult::Handled } else { EventResult::Ignored }
+        }
+
+        fn focusable(&self) -> bool {
+            self.focusable
+        }
+    }
+
+    fn probe(name: &'static str, log: &Log) -> Probe {
+        Probe { name, focusable: false, handles: |_| false, log: log.clone() }
+    }
+
+    fn focusable(name: &'static str, log: &Log) -> Probe {
+        Probe { focusable: true, ..probe(name, log) }
+    }
+
+    fn sized(width: f32, height: f32) -> Style {
+        Style { width: Dimension::Px(width), height: Dimension::Px(height), ..Style::default() }
+    }
+
+    fn tree_with_root(log: &Log) -> (WidgetTree, WidgetId) {
+        let mut tree = WidgetTree::new();
+        tree.set_viewport(Size::new(200.0, 100.0));
+        let root = tree.set_root(probe("root", log));
+        (tree, root)
+    }
+
+    fn names(log: &Log) -> Vec<&'static str> {
+        log.borrow_mut().drain(..).map(|(name, _)| name).collect()
+    }
+
+    #[test]
+    fn removed_ids_stay_dead_when_the_slot_is_reused() {
+        let log = Log::default();
+        let (mut tree, root) = tree_with_root(&log);
+        let old = tree.add_child(root, probe("old", &log)).unwrap();
+        tree.set_focus(Some(old));
+        tree.remove(old);
+        assert_eq!(tree.focused(), None);
+
+        let new = tree.add_child(root, probe("new", &log)).unwrap();
+        assert_eq!(new.index, old.index);
+        assert_ne!(new, old);
+        assert!(!tree.contains(old));
+        assert!(tree.get::<Probe>(old).is_none());
+        assert!(tree.add_child(old, probe("orphan", &log)).is_none());
+        assert_eq!(tree.get::<Probe>(new).unwrap().name, "new");
+        assert_eq!(tree.children(root), [new]);
+
+        // removing the stale id again mustn't take the new widget with it
+        tree.remove(old);
+        assert!(tree.contains(new));
+    }
+
+    #[test]
+    fn hit_test_prefers_children_and_later_siblings() {
+        let log = Log::default();
+        let (mut tree, root) = tree_with_root(&log);
+        let panel = tree.add_child(root, probe("panel", &log)).unwrap();
+        tree.set_style(panel, sized(100.0, 50.0));
+        let button = tree.add_child(panel, probe("button", &log)).unwrap();
+        tree.set_style(button, sized(40.0, 20.0));
+        // on top of the button's right half
+        let badge = tree.add_child(panel, probe("badge", &log)).unwrap();
+        tree.set_style(badge, Style { width: Dimension::Px(20.0), height: Dimension::Px(20.0), ..Style::absolute(Inset { left: Some(20.0), top: Some(0.0), ..Inset::default() }) });
+        tree.layout();
+
+        assert_eq!(tree.hit_test(Point::new(10.0, 10.0)), Some(button));
+        assert_eq!(tree.hit_test(Point::new(30.0, 10.0)), Some(badge));
+        assert_eq!(tree.hit_test(Point::new(80.0, 40.0)), Some(panel));
+        assert_eq!(tree.hit_test(Point::new(150.0, 80.0)), Some(root));
+        assert_eq!(tree.hit_test(Point::new(250.0, 10.0)), None);
+    }
+
+    #[test]
+    fn tab_and_shift_tab_walk_the_focus_order() {
+        let log = Log::default();
+        let (mut tree, root) = tree_with_root(&log);
+        let a = tree.add_child(root, focusable("a", &log)).unwrap();
+        let group = tree.add_child(root, probe("group", &log)).unwrap();
+        let b = tree.add_child(group, focusable("b", &log)).unwrap();
+        let c = tree.add_child(root, focusable("c", &log)).unwrap();
+
+        // depth first, b comes before c even though c is closer to the root
+        let mut order = Vec::new();
+        for _ in 0..4 {
+            assert!(tree.key(KeyCode::Tab, true));
+            order.push(tree.focused().unwrap());
+        }
+        assert_eq!(order, [a, b, c, a]);
+
+        tree.key(KeyCode::ShiftLeft, true);
+        tree.key(KeyCode::Tab, true);
+        assert_eq!(tree.focused(), Some(c));
+        tree.key(KeyCode::Tab, true);
+        assert_eq!(tree.focused(), Some(b));
+        tree.key(KeyCode::ShiftLeft, false);
+        tree.key(KeyCode::Tab, true);
+        assert_eq!(tree.focused(), Some(c));
+
+        // Shift-Tab with nothing focused starts from the end
+        tree.set_focus(None);
+        assert!(tree.focus_previous());
+        assert_eq!(tree.focused(), Some(c));
+
+        // the old one hears about it before the new one
+        log.borrow_mut().clear();
+        tree.focus_next();
+        assert_eq!(*log.borrow(), [("c", WidgetEvent::FocusLost), ("a", WidgetEvent::FocusGained)]);
+    }
+
+    #[test]
+    fn tab_with_nothing_focusable() {
+        let log = Log::default();
+        let (mut tree, root) = tree_with_root(&log);
+        tree.add_child(root, probe("label", &log)).unwrap();
+        assert!(!tree.key(KeyCode::Tab, true));
+        assert_eq!(tree.focused(), None);
+    }
+
+    #[test]
+    fn events_bubble_until_handled() {
+        let log = Log::default();
+        let (mut tree, root) = tree_with_root(&log);
+        let form = tree.add_child(root, Probe { handles: |event| matches!(event, WidgetEvent::Key { code: KeyCode::Enter, .. }), ..probe("form", &log) }).unwrap();
+        let field = tree.add_child(form, focusable("field", &log)).unwrap();
+        tree.set_focus(Some(field));
+        log.borrow_mut().clear();
+
+        // the form takes Enter, the root never sees it
+        assert!(tree.key(KeyCode::Enter, true));
+        assert_eq!(names(&log), ["field", "form"]);
+
+        // nobody wants Escape, it goes all the way up and comes back unhandled
+        assert!(!tree.key(KeyCode::Escape, true));
+        assert_eq!(names(&log), ["field", "form", "root"]);
+
+        // focus changes only go to the widget itself
+        tree.set_focus(None);
+        assert_eq!(names(&log), ["field"]);
+    }
+}
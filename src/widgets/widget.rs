@@ -0,0 +1,71 @@
+use std::any::Any;
+use winit::event::MouseButton;
+use winit::keyboard::KeyCode;
+use crate::geometry::rect::{Point, Rect, Size};
+use crate::render::painter::Painter;
+
+// Stable handle to a node in a WidgetTree. The generation changes when a slot is reused,
+// so an id for a removed widget never points at whatever took its place.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WidgetId {
+    pub(crate) index: u32,
+    pub(crate) generation: u32,
+}
+
+// What the tree knows about a widget when it asks it to paint or handle an event
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WidgetContext {
+    pub id: WidgetId,
+    // where layout put the widget, logical pixels
+    pub bounds: Rect,
+    pub hovered: bool,
+    // the pointer went down on this widget and hasn't come back up yet
+    pub pressed: bool,
+    pub focused: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WidgetEvent {
+    PointerEnter,
+    PointerLeave,
+    PointerMove { position: Point },
+    PointerDown { position: Point, button: MouseButton },
+    // goes to the widget the pointer went down on even if it was released somewhere else, `inside` says which
+    PointerUp { position: Point, button: MouseButton, inside: bool },
+    Key { code: KeyCode, pressed: bool },
+    FocusGained,
+    FocusLost,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventResult {
+    Handled,
+    // passes the event on to the parent
+    Ignored,
+}
+
+// Everything on screen that isn't the 3D scene. Widgets never touch the GPU, they describe
+// themselves with the Painter and the tree takes care of the rest.
+pub trait Widget: Any {
+    // the size the widget wants, `available` is the most it can have
+    fn measure(&mut self, available: Size) -> Size;
+
+    // where it ended up after layout
+    fn layout(&mut self, _bounds: Rect) {}
+
+    fn paint(&self, ctx: &WidgetContext, painter: &mut Painter);
+
+    fn event(&mut self, _ctx: &WidgetContext, _event: &WidgetEvent) -> EventResult {
+        EventResult::Ignored
+    }
+
+    // once a frame for animations. Returns true while it still has something moving.
+    fn update(&mut self, _dt: f32) -> bool {
+        false
+    }
+
+    // if it can take keyboard focus (clicking it or Tab)
+    fn focusable(&self) -> bool {
+        false
+    }
+}
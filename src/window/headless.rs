@@ -0,0 +1,172 @@
+use std::path::{Path, PathBuf};
+use anyhow::Result;
+use image::RgbaImage;
+use crate::gpu::gpu::GPUDevice;
+use crate::shaders::pipeline::PipelineCache;
+use crate::geometry::mesh::MeshData;
+use crate::geometry::model::Model;
+use crate::render::instance::InstanceBuffer;
+use crate::scene::scene::{MeshId, NodeId, SceneGraph};
+use crate::shaders::shader::VertexShaders;
+use crate::camera::camera::{CameraBuffer, CameraMatrix, aspect_ratio};
+use crate::render::render::{FrameAttachments, RenderSettings, draw_scene};
+use crate::textures::readback::save_png;
+use crate::textures::textures::{ColorTexture, OffscreenTexture};
+use crate::render::painter::Painter;
+use crate::render::ui::UiRenderer;
+use crate::widgets::tree::WidgetTree;
+use crate::window::window::logical_size;
+
+// The same thing as WindowState but it draws into an OffscreenTexture instead of a window surface.
+// No winit Window and no display is needed, so this is what runs on CI machines without a GPU.
+pub struct HeadlessState {
+    pub gpu: GPUDevice,
+    vertex_shaders: VertexShaders,
+    // every pipeline this target draws with, shared by the scene and the UI
+    pub pipelines: PipelineCache,
+    pub camera: CameraMatrix,
+    camera_buffer: CameraBuffer,
+    pub target: OffscreenTexture,
+    attachments: FrameAttachments,
+    pub ui: WidgetTree,
+    ui_renderer: UiRenderer,
+    painter: Painter,
+    // there is no monitor, this stands in for the window's DPI scale (matters for Projection::Pixels)
+    pub scale_factor: f32,
+}
+
+impl HeadlessState {
+    // sRGB so the offscreen frame matches what configure_surface picks for a window on most platforms
+    pub const DEFAULT_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+    pub async fn new(width: u32, height: u32) -> Result<Self> {
+        Self::with_format(width, height, Self::DEFAULT_FORMAT).await
+    }
+
+    pub async fn with_format(width: u32, height: u32, format: wgpu::TextureFormat) -> Result<Self> {
+        Self::with_settings(width, height, format, RenderSettings::default()).await
+    }
+
+    pub async fn with_settings(
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        settings: RenderSettings,
+    ) -> Result<Self> {
+        if width == 0 || height == 0 {
+            anyhow::bail!("offscreen target must be at least 1x1, got {}x{}", width, height);
+        }
+
+        let gpu = GPUDevice::new_headless(GPUDevice::headless_instance()).await?;
+
+        let target = OffscreenTexture::new(&gpu.device, width, height, format);
+
+        let camera = CameraMatrix::new(aspect_ratio(width, height));
+        let camera_buffer = CameraBuffer::new(&gpu.device, &camera);
+
+        let settings = settings.supported(&gpu.adapter, format);
+        let attachments = FrameAttachments::new(&gpu.device, width, height, format, settings);
+
+        let mut pipelines = PipelineCache::new();
+        let vertex_shaders = VertexShaders::new(&gpu.device, &gpu.queue, &mut pipelines, format, &settings, &camera_buffer.buffer)?;
+
+        let ui_renderer = UiRenderer::new(&gpu.device, &mut pipelines, format, &settings, width, height, 1.0)?;
+        let mut ui = WidgetTree::new();
+        ui.set_viewport(logical_size(width, height, 1.0));
+
+        Ok(Self {
+            gpu,
+            vertex_shaders,
+            pipelines,
+            camera,
+            camera_buffer,
+            target,
+            attachments,
+            ui,
+            ui_renderer,
+            painter: Painter::new(),
+            scale_factor: 1.0,
+        })
+    }
+
+    pub fn render(&mut self) {
+        self.vertex_shaders.prepare(&self.gpu.device, &self.gpu.queue, &mut self.camera);
+        self.camera_buffer.update(&self.gpu.queue, &self.camera);
+
+        self.ui.layout_if_needed();
+        self.painter.clear();
+        self.ui.paint(&mut self.painter);
+        self.ui_renderer.prepare(&self.gpu.device, &self.gpu.queue, &self.painter);
+
+        let mut encoder = self.gpu.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Headless Render Encoder"),
+        });
+
+        draw_scene(
+            &mut encoder,
+            &self.attachments.target(&self.target.view),
+            &[&self.vertex_shaders, &self.ui_renderer],
+        );
+
+        self.gpu.queue.submit(std::iter::once(encoder.finish()));
+    }
+
+    // the nodes that get drawn, changes get uploaded by the next render()
+    pub fn scene(&mut self) -> &mut SceneGraph {
+        &mut self.vertex_shaders.scene
+    }
+
+    // a mesh scene nodes can show with Attachment::Mesh
+    pub fn add_mesh(&mut self, label: &str, data: MeshData) -> Result<MeshId> {
+        self.vertex_shaders.add_mesh(&self.gpu.device, label, data)
+    }
+
+    // every mesh of a loaded model with its textures, under one root node to move it around with
+    pub fn add_model(&mut self, name: &str, model: &Model) -> Result<NodeId> {
+        self.vertex_shaders.add_model(&self.gpu.device, &self.gpu.queue, name, model)
+    }
+
+    // the base color texture `mesh` gets drawn with, instead of plain white
+    pub fn set_mesh_texture(&mut self, mesh: MeshId, texture: &ColorTexture) -> Result<()> {
+        self.vertex_shaders.set_texture(&self.gpu.device, mesh, texture)
+    }
+
+    // copies of the cube placed by hand, outside the scene graph. Changes get uploaded by the next render().
+    pub fn instances(&mut self) -> &mut InstanceBuffer {
+        &mut self.vertex_shaders.meshes[VertexShaders::CUBE.0].instances
+    }
+
+    // Reads back whatever the last render() left in the target
+    pub fn capture_frame(&self) -> Result<RgbaImage> {
+        self.target.read_rgba8(&self.gpu.device, &self.gpu.queue)
+    }
+
+    // the settings actually in use, sample_count might be lower than what was asked for
+    pub fn render_settings(&self) -> RenderSettings {
+        self.attachments.settings
+    }
+
+    // see WindowState::reload_shaders, there is no watcher here so the caller says what changed
+    pub fn reload_shaders(&mut self, changed: &[PathBuf]) {
+        let device = &self.gpu.device;
+        self.vertex_shaders.reload_changed(device, &mut self.pipelines, changed);
+        self.ui_renderer.render_pipeline.reload_changed(device, &mut self.pipelines, changed);
+    }
+
+    pub fn save_screenshot(&self, path: impl AsRef<Path>) -> Result<()> {
+        save_png(&self.capture_frame()?, path)
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if width > 0 && height > 0 {
+            self.target = OffscreenTexture::new(&self.gpu.device, width, height, self.target.format);
+            self.attachments.resize(&self.gpu.device, width, height);
+            self.camera.resize(width, height, self.scale_factor);
+            self.ui_renderer.resize(width, height, self.scale_factor);
+            self.ui.set_viewport(logical_size(width, height, self.scale_factor));
+            self.ui.layout_if_needed();
+        } else {
+            eprintln!("Offscreen target can't be resized to {}x{}", width, height);
+        }
+    }
+}
@@ -1,30 +1,56 @@
-use std::{sync::Arc};
-use anyhow::Result;
+use std::{path::PathBuf, sync::Arc};
+use anyhow::{Context, Result};
+use image::RgbaImage;
 use crate::gpu::gpu::GPUDevice;
 use wgpu::SurfaceConfiguration;
+use crate::shaders::pipeline::PipelineCache;
+use crate::geometry::mesh::MeshData;
+use crate::geometry::model::Model;
+use crate::render::instance::InstanceBuffer;
+use crate::scene::scene::{MeshId, NodeId, SceneGraph};
 use crate::shaders::shader::VertexShaders;
 use winit::{
-    event_loop::ActiveEventLoop,
+    event::{MouseButton, MouseScrollDelta},
     keyboard::KeyCode,
     window::Window,
 };
-use crate::camera::camera::CameraMatrix;
+use crate::camera::camera::{CameraBuffer, CameraMatrix, Projection, aspect_ratio};
+use crate::camera::controller::{CameraController, OrbitController};
+use crate::render::render::{FrameAttachments, RenderSettings, draw_scene};
+use crate::render::painter::Painter;
+use crate::render::ui::UiRenderer;
+use crate::geometry::rect::{Point, Size};
+use crate::widgets::tree::WidgetTree;
+use crate::textures::readback::{read_texture_rgba8, save_png};
+use crate::textures::textures::{ColorTexture, OffscreenTexture};
 
 pub struct WindowState {
     surface: wgpu::Surface<'static>,
     config: wgpu::SurfaceConfiguration,
     is_surface_configured: bool,
     pub window: Arc<Window>,
-    gpu: GPUDevice,
+    gpu: Arc<GPUDevice>,
     vertex_shaders: VertexShaders,
+    // every pipeline this target draws with, shared by the scene and the UI
+    pub pipelines: PipelineCache,
     pub camera: CameraMatrix,
-
-    //stencil_depth: Arc<TextureView>,
-
+    camera_buffer: CameraBuffer,
+    controller: Option<Box<dyn CameraController>>,
+    last_cursor: Option<(f64, f64)>,
+    attachments: FrameAttachments,
+    pub ui: WidgetTree,
+    ui_renderer: UiRenderer,
+    painter: Painter,
+    // a press that landed on a widget, the drag and the release stay with the UI and never reach the camera
+    ui_captured: bool,
+    // where the next frame gets saved, see save_screenshot
+    screenshot: Option<PathBuf>,
+    #[cfg(feature = "hot-reload")]
+    shader_watcher: Option<crate::shaders::watcher::ShaderWatcher>,
 }
 
 impl WindowState {
-    pub async fn new(window: Arc<Window>) -> Result<Self> {
+    pub async fn new(window: Arc<Window>, settings: RenderSettings) -> Result<Self> {
         // The instance creates the backend for the GPU. Backends::PRIMARY; this includes Metal 
         let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
             #[cfg(not(target_arch = "wasm32"))]
@@ -34,16 +60,40 @@ impl WindowState {
 
         let surface = instance.create_surface(window.clone()).unwrap();
 
-        let gpu = GPUDevice::new(&surface, instance).await?;
+        let gpu = Arc::new(GPUDevice::new(&surface, instance).await?);
+
+        Self::from_surface(window, surface, gpu, settings)
+    }
+
+    // For every window after the first one. The surface comes from the instance the GPUDevice was made with,
+    // so all the windows share one adapter, device and queue.
+    pub fn with_gpu(window: Arc<Window>, gpu: Arc<GPUDevice>, settings: RenderSettings) -> Result<Self> {
+        let surface = gpu.instance.create_surface(window.clone())?;
 
+        Self::from_surface(window, surface, gpu, settings)
+    }
+
+    fn from_surface(
+        window: Arc<Window>,
+        surface: wgpu::Surface<'static>,
+        gpu: Arc<GPUDevice>,
+        settings: RenderSettings,
+    ) -> Result<Self> {
         let config = configure_surface(&gpu.adapter, &window, &surface);
 
-        let aspect_ratio= config.width / config.height;
-        let camera = CameraMatrix::new(aspect_ratio as f32);
+        let camera = CameraMatrix::new(aspect_ratio(config.width, config.height));
+        let camera_buffer = CameraBuffer::new(&gpu.device, &camera);
+
+        let settings = settings.supported(&gpu.adapter, config.format);
+        let attachments = FrameAttachments::new(&gpu.device, config.width, config.height, config.format, settings);
 
-        let vertex_shaders = VertexShaders::new(&gpu.device, config.clone())?;
+        let mut pipelines = PipelineCache::new();
+        let vertex_shaders = VertexShaders::new(&gpu.device, &gpu.queue, &mut pipelines, config.format, &settings, &camera_buffer.buffer)?;
 
-        //let stencil_depth = TextureType::StencilDepth(StencilDepthTexture::new(&config, &gpu.device)).info().view();
+        let scale_factor = window.scale_factor() as f32;
+        let ui_renderer = UiRenderer::new(&gpu.device, &mut pipelines, config.format, &settings, config.width, config.height, scale_factor)?;
+        let mut ui = WidgetTree::new();
+        ui.set_viewport(logical_size(config.width, config.height, scale_factor));
 
         Ok(Self {
             surface,
@@ -52,20 +102,93 @@ impl WindowState {
             window,
             gpu,
             vertex_shaders,
-            camera, 
-            //stencil_depth,
+            pipelines,
+            camera,
+            camera_buffer,
+            // orbit by default so the arrow keys / mouse move the camera, swap it with set_camera_controller
+            controller: Some(Box::new(OrbitController::default())),
+            last_cursor: None,
+            attachments,
+            ui,
+            ui_renderer,
+            painter: Painter::new(),
+            ui_captured: false,
+            screenshot: None,
+            // a watcher that can't start (no inotify watches left, ...) just means no reloading
+            #[cfg(feature = "hot-reload")]
+            shader_watcher: crate::shaders::watcher::ShaderWatcher::new(&[
+                crate::shaders::source::ShaderFile::SCENE,
+                crate::shaders::source::ShaderFile::UI,
+            ])
+                .inspect_err(|err| eprintln!("Shader hot reload is off: {}", err))
+                .ok(),
         })
     }
 
-    pub fn render(&self) -> Result<(), wgpu::SurfaceError> {
+    // the settings actually in use, sample_count might be lower than what was asked for
+    pub fn render_settings(&self) -> RenderSettings {
+        self.attachments.settings
+    }
+
+    pub fn gpu(&self) -> &GPUDevice {
+        &self.gpu
+    }
+
+    // a handle to the shared device so another window can be opened on it
+    pub fn shared_gpu(&self) -> Arc<GPUDevice> {
+        self.gpu.clone()
+    }
+
+    pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
+        self.render_with(|_, _, _| {})
+    }
+
+    // Same as render() but `extra` gets to record its own commands into the frame after the scene
+    pub fn render_with(
+        &mut self,
+        mut extra: impl FnMut(&GPUDevice, &mut wgpu::CommandEncoder, &wgpu::TextureView),
+    ) -> Result<(), wgpu::SurfaceError> {
+        let Some(output) = self.draw_frame(&mut extra)? else {
+            return Ok(());
+        };
+
+        // a screenshot asked for with save_screenshot gets this frame, extra's draws and all
+        if let Some(path) = self.screenshot.take()
+            && let Err(e) = self.read_frame(&output.texture, &mut extra).and_then(|image| save_png(&image, &path))
+        {
+            eprintln!("Unable to save screenshot {}", e);
+        }
+
+        output.present();
+        Ok(())
+    }
+
+    // Draws and submits a frame but doesn't present it yet, so it can still be read back. None when the
+    // surface isn't configured.
+    fn draw_frame(
+        &mut self,
+        extra: &mut impl FnMut(&GPUDevice, &mut wgpu::CommandEncoder, &wgpu::TextureView),
+    ) -> Result<Option<wgpu::SurfaceTexture>, wgpu::SurfaceError> {
         // remember this is a continous loop so everything in here is being looped
         self.window.request_redraw();
   
         // Remember render() is called every time the window is redrawn, so we need to check if the surface is configured before proceeding. so if 
         // it cant draw a frame it will just exit with an Ok(()) instead of throwing an error.
         if !self.is_surface_configured {
-            return Ok(());
+            return Ok(None);
+        }
+
+        #[cfg(feature = "hot-reload")]
+        if let Some(watcher) = &self.shader_watcher {
+            let changed = watcher.poll();
+            self.reload_shaders(&changed);
         }
+
+        // the scene goes first, a camera node in it moves the camera. Anything that moved the camera since
+        // the last frame (controller, user hooks, the scene) gets uploaded by sync_camera.
+        self.vertex_shaders.prepare(&self.gpu.device, &self.gpu.queue, &mut self.camera);
+        self.sync_camera();
+        self.prepare_ui();
         
         let output = self.surface.get_current_texture()?;
         
@@ -78,55 +201,68 @@ impl WindowState {
         let mut encoder = self.gpu.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
         label: Some("Render Encoder"),});
 
-        // Now we can get to clearing the screen a long time coming. We need to use the encoder to create a RenderPass. The RenderPass has all the methods for the actual drawing. 
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Render Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        // THIS IS JUST FOR THE BACKGROUND COLOR
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.1,
-                            g: 0.2,
-                            b: 1.0,
-                            a: 1.0,
-                        }),
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: None,
-                
-                // Some(RenderPassDepthStencilAttachment {
-                //     view: &self.stencil_depth,
-                //     depth_ops:Some(wgpu::Operations {
-                //         // so if LoadOp::Load is set then the vertex and indices do not get drawn to the screen
-                //         // telling the gpu “Preserve whatever was alreLinein this texture from the last frame — do not clear it, and do not initialize it.”
-                //         load: wgpu::LoadOp::Clear(1.0),
-                //         store: wgpu::StoreOp::Store,
-                //         }),
-                //     stencil_ops: None,
-                // }),
-                occlusion_query_set: None,
-                timestamp_writes: None,
-            });
-
-            render_pass.set_pipeline(&self.vertex_shaders.render_pipeline); // 2.
-            render_pass.set_bind_group(0, Some(&self.vertex_shaders.bind_group), &[]);
-
-            render_pass.set_vertex_buffer(0, self.vertex_shaders.vertex_buffer.slice(..));
-            render_pass.set_index_buffer(self.vertex_shaders.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
-
-            render_pass.draw_indexed(0..12, 0, 0..2);
-            //render_pass.draw(0..self.vertex_shaders.num_vertices, 0..1); 
-
-        drop(render_pass);
-
-    // submit will accept anything that implements IntoIter
-    self.gpu.queue.submit(std::iter::once(encoder.finish()));
-    output.present();
-
-    Ok(())
+        draw_scene(&mut encoder, &self.attachments.target(&view), &[&self.vertex_shaders, &self.ui_renderer]);
+        extra(&self.gpu, &mut encoder, &view);
+
+        // submit will accept anything that implements IntoIter
+        self.gpu.queue.submit(std::iter::once(encoder.finish()));
+
+        Ok(Some(output))
+    }
+
+    // Draws a frame, reads it back and then presents it. See render_with for `extra`.
+    pub fn capture_frame_with(
+        &mut self,
+        mut extra: impl FnMut(&GPUDevice, &mut wgpu::CommandEncoder, &wgpu::TextureView),
+    ) -> Result<RgbaImage> {
+        let output = self.draw_frame(&mut extra)?.context("the surface isn't configured yet")?;
+        let image = self.read_frame(&output.texture, &mut extra);
+        output.present();
+        image
+    }
+
+    // capture_frame_with without any extra draws
+    pub fn capture_frame(&mut self) -> Result<RgbaImage> {
+        self.capture_frame_with(|_, _, _| {})
+    }
+
+    // Copies the swapchain texture of a frame that was submitted but not presented yet. Not every platform
+    // lets you copy out of the surface (configure_surface only asks for COPY_SRC where it's there), then the
+    // frame is drawn again into an offscreen copy of it (same size, same format) with the same extra draws.
+    fn read_frame(
+        &mut self,
+        texture: &wgpu::Texture,
+        extra: &mut impl FnMut(&GPUDevice, &mut wgpu::CommandEncoder, &wgpu::TextureView),
+    ) -> Result<RgbaImage> {
+        if texture.usage().contains(wgpu::TextureUsages::COPY_SRC) {
+            return read_texture_rgba8(&self.gpu.device, &self.gpu.queue, texture);
+        }
+
+        let target = OffscreenTexture::new(&self.gpu.device, self.config.width, self.config.height, self.config.format);
+        let mut encoder = self.gpu.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Capture Encoder"),
+        });
+        draw_scene(&mut encoder, &self.attachments.target(&target.view), &[&self.vertex_shaders, &self.ui_renderer]);
+        extra(&self.gpu, &mut encoder, &target.view);
+        self.gpu.queue.submit(std::iter::once(encoder.finish()));
+
+        target.read_rgba8(&self.gpu.device, &self.gpu.queue)
+    }
+
+    // Rebuilds the pipelines whose shader file is in `changed`. A shader that doesn't compile gets its
+    // error printed and the pipeline it replaces keeps drawing. With the hot-reload feature render() calls
+    // this with whatever the watcher saw.
+    pub fn reload_shaders(&mut self, changed: &[PathBuf]) {
+        let device = &self.gpu.device;
+        self.vertex_shaders.reload_changed(device, &mut self.pipelines, changed);
+        self.ui_renderer.render_pipeline.reload_changed(device, &mut self.pipelines, changed);
+    }
+
+    // Saves the next frame render_with draws as a png, so whatever the on_render hook draws is in it too.
+    // No key is bound to this, an app that wants one calls it from its on_event hook (ctx.state.save_screenshot).
+    // Errors are printed when the frame is written.
+    pub fn save_screenshot(&mut self, path: impl Into<PathBuf>) {
+        self.screenshot = Some(path.into());
     }
 
     pub fn resize(&mut self, width: u32, height: u32) {
@@ -137,34 +273,162 @@ impl WindowState {
             self.config.height = height;
             self.surface.configure(&self.gpu.device, &self.config);
             self.is_surface_configured = true;
+
+            // the depth buffer and the MSAA texture have to be the same size as the surface
+            self.attachments.resize(&self.gpu.device, width, height);
+
+            let scale_factor = self.window.scale_factor() as f32;
+            self.camera.resize(width, height, scale_factor);
+            self.sync_camera();
+
+            self.ui_renderer.resize(width, height, scale_factor);
+            self.ui.set_viewport(logical_size(width, height, scale_factor));
+            // lay out again right away so hit testing is right before the next frame is drawn
+            self.ui.layout_if_needed();
         } else {
             eprintln!("Surface is not configured yet, cannot resize.");
         }
     }
 
-    pub fn handle_key(&self, event_loop: &ActiveEventLoop, code: KeyCode, is_pressed: bool) {
-    match (code, is_pressed) {
-        (KeyCode::Escape, true) => event_loop.exit(),
-        (KeyCode::KeyW, true) => self.camera.handle_key_press(KeyCode::KeyW),
-        
+    // lays the widget tree out if something changed and turns it into this frame's UI vertices
+    fn prepare_ui(&mut self) {
+        self.ui.layout_if_needed();
+        self.painter.clear();
+        self.ui.paint(&mut self.painter);
+        self.ui_renderer.prepare(&self.gpu.device, &self.gpu.queue, &self.painter);
+    }
 
-        _ => {}
-        
+    // the nodes that get drawn, changes get uploaded with the next frame
+    pub fn scene(&mut self) -> &mut SceneGraph {
+        &mut self.vertex_shaders.scene
+    }
+
+    // a mesh scene nodes can show with Attachment::Mesh
+    pub fn add_mesh(&mut self, label: &str, data: MeshData) -> Result<MeshId> {
+        self.vertex_shaders.add_mesh(&self.gpu.device, label, data)
+    }
+
+    // every mesh of a loaded model with its textures, under one root node to move it around with
+    pub fn add_model(&mut self, name: &str, model: &Model) -> Result<NodeId> {
+        self.vertex_shaders.add_model(&self.gpu.device, &self.gpu.queue, name, model)
+    }
+
+    // the base color texture `mesh` gets drawn with, instead of plain white
+    pub fn set_mesh_texture(&mut self, mesh: MeshId, texture: &ColorTexture) -> Result<()> {
+        self.vertex_shaders.set_texture(&self.gpu.device, mesh, texture)
+    }
+
+    // copies of the cube placed by hand, outside the scene graph. Changes get uploaded with the next frame.
+    pub fn instances(&mut self) -> &mut InstanceBuffer {
+        &mut self.vertex_shaders.meshes[VertexShaders::CUBE.0].instances
+    }
+
+    // pushes the CameraMatrix to the GPU if it changed
+    pub fn sync_camera(&mut self) {
+        self.camera_buffer.update(&self.gpu.queue, &self.camera);
+    }
+
+    pub fn set_camera_controller(&mut self, controller: impl CameraController + 'static) {
+        self.controller = Some(Box::new(controller));
+    }
+
+    // Once a frame, before render. The controller turns the input it collected since last frame into a new view matrix,
+    // with no input it leaves whatever view the app set alone.
+    pub fn update(&mut self, dt: f32) {
+        self.ui.update(dt);
+
+        // the controllers move a 3D eye around, a pixel space camera has nothing for them to move
+        if let Some(controller) = self.controller.as_mut()
+            && self.camera.mode == Projection::Perspective
+        {
+            controller.update(&mut self.camera, dt);
+        }
+        self.sync_camera();
+    }
+
+    pub fn handle_mouse_button(&mut self, button: MouseButton, is_pressed: bool) {
+        let handled = self.ui.pointer_button(button, is_pressed);
+        if is_pressed && handled {
+            self.ui_captured = true;
+        }
+        if self.ui_captured {
+            if !is_pressed {
+                self.ui_captured = false;
+            }
+            return;
+        }
+
+        if let Some(controller) = self.controller.as_mut() {
+            controller.process_mouse_button(button, is_pressed);
+        }
+    }
+
+    // x and y are physical pixels straight from winit
+    pub fn handle_cursor_moved(&mut self, x: f64, y: f64) {
+        let scale_factor = self.window.scale_factor();
+        self.ui.pointer_moved(Point::new((x / scale_factor) as f32, (y / scale_factor) as f32));
+
+        // winit gives the position, the controllers want how far it moved
+        if let (false, Some((last_x, last_y)), Some(controller)) =
+            (self.ui_captured, self.last_cursor, self.controller.as_mut())
+        {
+            controller.process_mouse_motion(x - last_x, y - last_y);
+        }
+        self.last_cursor = Some((x, y));
+    }
+
+    // so the jump when the cursor comes back in somewhere else doesn't count as a drag
+    pub fn handle_cursor_left(&mut self) {
+        self.last_cursor = None;
+        self.ui.pointer_left();
+    }
+
+    pub fn handle_scroll(&mut self, delta: MouseScrollDelta) {
+        let lines = match delta {
+            MouseScrollDelta::LineDelta(_, y) => y,
+            // trackpads send pixels, call 20 of them one line
+            MouseScrollDelta::PixelDelta(pos) => pos.y as f32 / 20.0,
+        };
+        if let Some(controller) = self.controller.as_mut() {
+            controller.process_scroll(lines);
         }
     }
+
+    // true when the key asks for this window to close (Escape). Only this window, the app exits once the last one is gone.
+    pub fn handle_key(&mut self, code: KeyCode, is_pressed: bool) -> bool {
+        // the focused widget gets first go at the keyboard
+        if self.ui.key(code, is_pressed) {
+            return false;
+        }
+
+        if let Some(controller) = self.controller.as_mut()
+            && controller.process_key(code, is_pressed)
+        {
+            return false;
+        }
+
+        code == KeyCode::Escape && is_pressed
+    }
+}
+
+// the surface is sized in physical pixels, widgets are laid out in logical ones
+pub fn logical_size(width: u32, height: u32, scale_factor: f32) -> Size {
+    let scale_factor = if scale_factor > 0.0 { scale_factor } else { 1.0 };
+    Size::new(width as f32 / scale_factor, height as f32 / scale_factor)
 }
 
 pub fn configure_surface(adapter: &wgpu::Adapter, window: &Window, surface: &wgpu::Surface<'_>) -> SurfaceConfiguration {
     let size = window.inner_size();
-    let surface_caps = surface.get_capabilities(&adapter);
+    let surface_caps = surface.get_capabilities(adapter);
     
     let surface_format = surface_caps.formats.iter()
         .find(|f| f.is_srgb())
         .copied()
         .unwrap_or(surface_caps.formats[0]);
 
-    let config = wgpu::SurfaceConfiguration {
-        usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+    // COPY_SRC where the platform has it so screenshots can copy the frame that's actually shown
+    wgpu::SurfaceConfiguration {
+        usage: wgpu::TextureUsages::RENDER_ATTACHMENT | (surface_caps.usages & wgpu::TextureUsages::COPY_SRC),
         format: surface_format,
         width: size.width,
         height: size.height,
@@ -172,8 +436,7 @@ pub fn configure_surface(adapter: &wgpu::Adapter, window: &Window, surface: &wgp
         alpha_mode: surface_caps.alpha_modes[0],
         view_formats: vec![],
         desired_maximum_frame_latency: 2,
-    };
-    config   
+    }
 }
 
 
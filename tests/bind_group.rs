@@ -0,0 +1,158 @@
+mod common;
+
+use common::gpu;
+use rs_gui_library::shaders::bind_group::BindGrouping;
+use rs_gui_library::shaders::reflect::ShaderReflection;
+use rs_gui_library::textures::textures::ColorTexture;
+use wgpu::ShaderStages;
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+
+const SHADER: &str = r#"
+struct Params { scale: f32, _pad0: f32, _pad1: f32, _pad2: f32 };
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var<uniform> offset: Params;
+@group(0) @binding(2) var<storage, read> input: array<f32>;
+@group(0) @binding(3) var<storage, read_write> output: array<f32>;
+@group(0) @binding(4) var image: texture_2d<f32>;
+@group(0) @binding(5) var image_sampler: sampler;
+@group(0) @binding(6) var target_image: texture_storage_2d<rgba8unorm, write>;
+
+@compute @workgroup_size(4)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let texel = textureSampleLevel(image, image_sampler, vec2<f32>(0.5, 0.5), 0.0);
+    output[id.x] = input[id.x] * params.scale + offset.scale + texel.r;
+    textureStore(target_image, vec2<i32>(i32(id.x), 0), vec4<f32>(1.0, 0.0, 0.0, 1.0));
+}
+"#;
+
+// Every kind of entry in one group, used by a compute shader so wgpu checks the layout against the WGSL
+#[test]
+fn every_entry_kind_matches_the_shader() {
+    let gpu = gpu();
+    let device = &gpu.device;
+
+    let uniform = |value: f32| {
+        device.create_buffer_init(&BufferInitDescriptor {
+            label: None,
+            contents: bytemuck::cast_slice(&[value, 0.0, 0.0, 0.0]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        })
+    };
+    let params = uniform(2.0);
+
+    // two Params 256 bytes apart, the dynamic offset picks the second one
+    let alignment = device.limits().min_uniform_buffer_offset_alignment as usize;
+    let mut offsets = vec![0.0f32; alignment / 4 + 4];
+    offsets[alignment / 4] = 10.0;
+    let offset = device.create_buffer_init(&BufferInitDescriptor {
+        label: None,
+        contents: bytemuck::cast_slice(&offsets),
+        usage: wgpu::BufferUsages::UNIFORM,
+    });
+
+    let input = device.create_buffer_init(&BufferInitDescriptor {
+        label: None,
+        contents: bytemuck::cast_slice(&[1.0f32, 2.0, 3.0, 4.0]),
+        usage: wgpu::BufferUsages::STORAGE,
+    });
+    let output = device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size: 16,
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+        mapped_at_creation: false,
+    });
+    let readback = device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size: 16,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    // black so the sample adds 0, it still has to go through the texture and sampler bindings
+    let image = ColorTexture::new(device, "black", 1, 1, wgpu::TextureFormat::Rgba8Unorm);
+    image.write_region(&gpu.queue, 0, 0, 1, 1, &[0, 0, 0, 255]);
+
+    let target = device.create_texture(&wgpu::TextureDescriptor {
+        label: None,
+        size: wgpu::Extent3d { width: 4, height: 1, depth_or_array_layers: 1 },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba8Unorm,
+        usage: wgpu::TextureUsages::STORAGE_BINDING,
+        view_formats: &[],
+    });
+    let target_view = target.create_view(&Default::default());
+
+    device.push_error_scope(wgpu::ErrorFilter::Validation);
+
+    let bg = BindGrouping::builder("everything")
+        .uniform(0, ShaderStages::COMPUTE, &params)
+        .uniform_dynamic(1, ShaderStages::COMPUTE, &offset, 16)
+        .storage(2, ShaderStages::COMPUTE, &input, true)
+        .storage(3, ShaderStages::COMPUTE, &output, false)
+        .color_texture(4, 5, ShaderStages::COMPUTE, device, &image)
+        .storage_texture(6, ShaderStages::COMPUTE, &target_view, wgpu::TextureFormat::Rgba8Unorm, wgpu::StorageTextureAccess::WriteOnly)
+        .build(device)
+        .unwrap();
+
+    let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: None,
+        source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+    });
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: None,
+        bind_group_layouts: &[&bg.bind_group.0],
+        push_constant_ranges: &[],
+    });
+    let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: None,
+        layout: Some(&layout),
+        module: &module,
+        entry_point: Some("main"),
+        compilation_options: Default::default(),
+        cache: None,
+    });
+
+    let mut encoder = device.create_command_encoder(&Default::default());
+    {
+        let mut pass = encoder.begin_compute_pass(&Default::default());
+        pass.set_pipeline(&pipeline);
+        pass.set_bind_group(0, Some(&bg.bind_group.1), &[alignment as u32]);
+        pass.dispatch_workgroups(1, 1, 1);
+    }
+    encoder.copy_buffer_to_buffer(&output, 0, &readback, 0, 16);
+    gpu.queue.submit(std::iter::once(encoder.finish()));
+
+    assert!(pollster::block_on(device.pop_error_scope()).is_none());
+
+    readback.slice(..).map_async(wgpu::MapMode::Read, |result| result.unwrap());
+    device.poll(wgpu::PollType::Wait).unwrap();
+    let values: Vec<f32> = bytemuck::cast_slice(&readback.slice(..).get_mapped_range()).to_vec();
+    assert_eq!(values, [12.0, 14.0, 16.0, 18.0]);
+}
+
+#[test]
+fn duplicate_binding_is_an_error() {
+    let gpu = gpu();
+    let device = &gpu.device;
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size: 16,
+        usage: wgpu::BufferUsages::UNIFORM,
+        mapped_at_creation: false,
+    });
+    let clash = || {
+        BindGrouping::builder("clash")
+            .uniform(0, ShaderStages::VERTEX, &buffer)
+            .uniform(0, ShaderStages::FRAGMENT, &buffer)
+    };
+
+    let err = clash().build(device).err().expect("build took a duplicate binding").to_string();
+    assert_eq!(err, "binding 0 is used twice in bind group 'clash'");
+
+    let reflection = ShaderReflection::from_wgsl("bind_group.wgsl", SHADER).unwrap();
+    let err = clash().build_checked(device, &reflection, 0).err().expect("build_checked took a duplicate binding").to_string();
+    assert_eq!(err, "binding 0 is used twice in bind group 'clash'");
+}
@@ -0,0 +1,177 @@
+mod common;
+
+use common::gpu;
+use rs_gui_library::gpu::gpu::GPUDevice;
+use rs_gui_library::shaders::bind_group::BindGrouping;
+use rs_gui_library::shaders::buffers::{BufferTypes, DynamicBuffer, UniformRing};
+use rs_gui_library::shaders::reflect::ShaderReflection;
+use wgpu::ShaderStages;
+
+fn read_back(gpu: &GPUDevice, buffer: &wgpu::Buffer, size: u64) -> Vec<u8> {
+    let readback = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+    let mut encoder = gpu.device.create_command_encoder(&Default::default());
+    encoder.copy_buffer_to_buffer(buffer, 0, &readback, 0, size);
+    gpu.queue.submit(std::iter::once(encoder.finish()));
+
+    readback.slice(..).map_async(wgpu::MapMode::Read, |result| result.unwrap());
+    gpu.device.poll(wgpu::PollType::Wait).unwrap();
+    readback.slice(..).get_mapped_range().to_vec()
+}
+
+#[test]
+fn dynamic_buffers_grow_only_when_they_have_to() {
+    let gpu = gpu();
+    let mut buffer = DynamicBuffer::new("stream", wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC);
+    assert!(buffer.buffer().is_none() && buffer.slice().is_none());
+
+    assert!(buffer.write(&gpu.device, &gpu.queue, bytemuck::cast_slice(&[1u32, 2, 3])));
+    assert_eq!((buffer.len(), buffer.capacity()), (12, 16));
+    let first = buffer.buffer().unwrap().clone();
+
+    // smaller and the same size again both stay in the buffer they have
+    assert!(!buffer.write(&gpu.device, &gpu.queue, bytemuck::cast_slice(&[4u32, 5])));
+    assert!(!buffer.write(&gpu.device, &gpu.queue, bytemuck::cast_slice(&[6u32, 7, 8, 9])));
+    assert_eq!(buffer.buffer(), Some(&first));
+    assert_eq!(bytemuck::cast_slice::<u8, u32>(&read_back(&gpu, &first, 16)), [6, 7, 8, 9]);
+
+    // partial writes never grow
+    assert!(buffer.write_at(&gpu.queue, 8, bytemuck::cast_slice(&[10u32])));
+    assert!(!buffer.write_at(&gpu.queue, 12, bytemuck::cast_slice(&[11u32, 12])));
+    assert_eq!(bytemuck::cast_slice::<u8, u32>(&read_back(&gpu, &first, 16)), [6, 7, 10, 9]);
+
+    assert!(buffer.write(&gpu.device, &gpu.queue, bytemuck::cast_slice(&[0u32; 5])));
+    assert_eq!((buffer.len(), buffer.capacity()), (20, 32));
+    assert_ne!(buffer.buffer(), Some(&first));
+
+    // three u16 are 6 bytes, write_buffer wants multiples of 4
+    let mut indices = BufferTypes::IndexBuffer(&[0u16, 1, 2]).dynamic("indices", &gpu.device, &gpu.queue);
+    assert_eq!(indices.len(), 6);
+    assert!(!indices.write(&gpu.device, &gpu.queue, bytemuck::cast_slice(&[2u16, 1, 0])));
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct Draw {
+    index: u32,
+    value: f32,
+    _pad: [f32; 2],
+}
+
+const SHADER: &str = r#"
+struct Draw { index: u32, value: f32, _pad0: f32, _pad1: f32 };
+
+@group(0) @binding(0) var<uniform> draw: Draw;
+@group(0) @binding(1) var<storage, read_write> output: array<f32>;
+
+@compute @workgroup_size(1)
+fn main() {
+    output[draw.index] = draw.value;
+}
+"#;
+
+// Every dispatch reads its own slot of the ring through a dynamic offset and writes it where it says
+#[test]
+fn uniform_ring_hands_out_aligned_offsets() {
+    let gpu = gpu();
+    let device = &gpu.device;
+    let alignment = device.limits().min_uniform_buffer_offset_alignment;
+
+    let mut ring = UniformRing::<Draw>::new(device, "draws", 1);
+    let output = DynamicBuffer::new("output", wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC).with_capacity(device, 32);
+    let output = output.buffer().unwrap();
+
+    let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: None,
+        source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+    });
+    let bind_group = |ring: &UniformRing<Draw>| {
+        BindGrouping::builder("draws")
+            .uniform_dynamic(0, ShaderStages::COMPUTE, ring.buffer(), ring.binding_size())
+            .storage(1, ShaderStages::COMPUTE, output, false)
+            .build(device)
+            .unwrap()
+    };
+    let mut bg = bind_group(&ring);
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: None,
+        bind_group_layouts: &[&bg.bind_group.0],
+        push_constant_ranges: &[],
+    });
+    let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: None,
+        layout: Some(&layout),
+        module: &module,
+        entry_point: Some("main"),
+        compilation_options: Default::default(),
+        cache: None,
+    });
+
+    let mut frame = |values: &[(u32, f32)]| -> (bool, Vec<f32>) {
+        ring.reset();
+        let offsets: Vec<u32> = values
+            .iter()
+            .map(|(index, value)| ring.push(&Draw { index: *index, value: *value, _pad: [0.0; 2] }))
+            .collect();
+        assert!(offsets.iter().enumerate().all(|(i, offset)| *offset == i as u32 * ring.stride() as u32));
+        assert_eq!(ring.len(), values.len());
+
+        let grew = ring.upload(device, &gpu.queue);
+        if grew {
+            bg = bind_group(&ring);
+        }
+
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let mut encoder = device.create_command_encoder(&Default::default());
+        {
+            let mut pass = encoder.begin_compute_pass(&Default::default());
+            pass.set_pipeline(&pipeline);
+            for offset in offsets {
+                pass.set_bind_group(0, Some(&bg.bind_group.1), &[offset]);
+                pass.dispatch_workgroups(1, 1, 1);
+            }
+        }
+        gpu.queue.submit(std::iter::once(encoder.finish()));
+        assert!(pollster::block_on(device.pop_error_scope()).is_none());
+
+        (grew, bytemuck::cast_slice(&read_back(&gpu, output, 32)).to_vec())
+    };
+
+    // room for one to start with, five make it grow
+    let (grew, values) = frame(&[(0, 1.0), (1, 2.0), (2, 3.0), (3, 4.0), (7, 8.0)]);
+    assert!(grew);
+    assert_eq!(values, [1.0, 2.0, 3.0, 4.0, 0.0, 0.0, 0.0, 8.0]);
+
+    // the next frame starts at the front and fits in what's there
+    let (grew, values) = frame(&[(4, 5.0), (0, -1.0)]);
+    assert!(!grew);
+    assert_eq!(values, [-1.0, 2.0, 3.0, 4.0, 5.0, 0.0, 0.0, 8.0]);
+    assert_eq!(ring.stride() % alignment as u64, 0);
+}
+
+// a vec3<f32> struct is 12 bytes in Rust and 16 in WGSL, the binding has to cover the WGSL size
+#[test]
+fn uniform_ring_binding_size_matches_wgsl() {
+    let gpu = gpu();
+    let device = &gpu.device;
+    let ring = UniformRing::<[f32; 3]>::new(device, "tints", 4);
+    assert_eq!(ring.binding_size(), 16);
+
+    let source = r#"
+struct Tint { rgb: vec3<f32> };
+@group(0) @binding(0) var<uniform> tint: Tint;
+@fragment
+fn main() -> @location(0) vec4<f32> {
+    return vec4<f32>(tint.rgb, 1.0);
+}
+"#;
+    let reflection = ShaderReflection::from_wgsl("tint.wgsl", source).unwrap();
+    BindGrouping::builder("tints")
+        .uniform_dynamic(0, ShaderStages::FRAGMENT, ring.buffer(), ring.binding_size())
+        .build_checked(device, &reflection, 0)
+        .unwrap();
+}
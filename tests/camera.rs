@@ -0,0 +1,97 @@
+use nalgebra::{Point3, Vector3};
+use rs_gui_library::camera::camera::CameraMatrix;
+use rs_gui_library::camera::controller::{CameraController, FlyController, OrbitController};
+use winit::keyboard::KeyCode;
+
+fn eye(camera: &CameraMatrix) -> Point3<f32> {
+    camera.cam.try_inverse().unwrap().transform_point(&Point3::origin())
+}
+
+fn view_set_by_code() -> CameraMatrix {
+    let mut camera = CameraMatrix::new(1.0);
+    camera.set_view(&Point3::new(3.0, 4.0, 2.0), &Point3::new(0.0, 1.0, 0.0), &Vector3::y());
+    camera
+}
+
+#[test]
+fn idle_controllers_keep_the_view_set_by_code() {
+    let controllers: [Box<dyn CameraController>; 2] = [Box::new(OrbitController::default()), Box::new(FlyController::default())];
+    for mut controller in controllers {
+        let mut camera = view_set_by_code();
+        let view = camera.cam;
+        for _ in 0..3 {
+            assert!(!controller.update(&mut camera, 1.0 / 60.0));
+        }
+        assert_eq!(camera.cam, view);
+    }
+}
+
+#[test]
+fn controllers_take_over_once_there_is_input() {
+    let mut orbit = OrbitController::default();
+    let mut camera = view_set_by_code();
+    let view = camera.cam;
+    orbit.process_scroll(1.0);
+    assert!(orbit.update(&mut camera, 1.0 / 60.0));
+    assert_ne!(camera.cam, view);
+    // the scroll was used up, the next frame leaves the camera alone again
+    let zoomed = camera.cam;
+    assert!(!orbit.update(&mut camera, 1.0 / 60.0));
+    assert_eq!(camera.cam, zoomed);
+
+    let mut fly = FlyController::default();
+    let mut camera = view_set_by_code();
+    assert!(fly.process_key(KeyCode::KeyW, true));
+    assert!(fly.update(&mut camera, 1.0 / 60.0));
+    assert_ne!(camera.cam, view);
+    // holding the key keeps moving
+    assert!(fly.update(&mut camera, 1.0 / 60.0));
+    fly.process_key(KeyCode::KeyW, false);
+    assert!(!fly.update(&mut camera, 1.0 / 60.0));
+}
+
+#[test]
+fn first_input_after_set_view_moves_one_step() {
+    let dt = 1.0 / 60.0;
+
+    // the fly controller still thinks it's at its default spot, 5 units back on z
+    let mut fly = FlyController::default();
+    let mut camera = view_set_by_code();
+    let start = eye(&camera);
+    fly.process_key(KeyCode::KeyW, true);
+    assert!(fly.update(&mut camera, dt));
+    let moved = (eye(&camera) - start).norm();
+    assert!((moved - fly.settings.speed * dt).abs() < 1e-4, "moved {}", moved);
+
+    // one frame of arrow key turns the orbit by key_rotate_speed * dt around a target in front of the camera
+    let mut orbit = OrbitController::default();
+    let mut camera = view_set_by_code();
+    orbit.process_key(KeyCode::ArrowLeft, true);
+    assert!(orbit.update(&mut camera, dt));
+    let moved = (eye(&camera) - start).norm();
+    let step = orbit.settings.key_rotate_speed * dt * orbit.distance;
+    assert!(moved <= step * 1.01, "moved {} for a step of {}", moved, step);
+
+    // the view set from code in between counts too, not just the first one
+    orbit.process_key(KeyCode::ArrowLeft, false);
+    camera.set_view(&Point3::new(-2.0, 0.0, 0.0), &Point3::origin(), &Vector3::y());
+    orbit.process_scroll(1.0);
+    assert!(orbit.update(&mut camera, dt));
+    let zoomed = eye(&camera);
+    assert!(zoomed.y.abs() < 1e-4 && zoomed.z.abs() < 1e-4 && zoomed.x < -1.0, "{}", zoomed);
+}
+
+#[test]
+fn zoom_keys_use_the_settings_speed() {
+    let mut orbit = OrbitController::default();
+    orbit.settings.key_zoom_speed = 0.0;
+    let mut camera = CameraMatrix::new(1.0);
+    orbit.process_key(KeyCode::Equal, true);
+    orbit.update(&mut camera, 1.0);
+    assert_eq!(orbit.distance, 5.0);
+
+    orbit.settings.key_zoom_speed = 2.0;
+    orbit.update(&mut camera, 1.0);
+    // two steps of zoom_speed (0.1) off the distance
+    assert!((orbit.distance - 4.0).abs() < 1e-5, "{}", orbit.distance);
+}
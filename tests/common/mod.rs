@@ -0,0 +1,141 @@
+// Helpers shared by the integration tests. Every tests/*.rs is its own crate and only uses some of these.
+#![allow(dead_code)]
+
+// Golden image harness. A scene gets rendered through HeadlessState, read back and compared
+// against tests/golden/<name>.png.
+//
+// UPDATE_GOLDEN=1 cargo test   rewrites the references from whatever renders now. Look at them before you commit them.
+//
+// When a frame does not match, the actual frame and a diff image (mismatched pixels in red, the rest dimmed)
+// are written to target/golden/ so you can see what moved.
+
+use image::{Rgba, RgbaImage};
+use rs_gui_library::gpu::gpu::GPUDevice;
+use rs_gui_library::render::render::RenderSettings;
+use rs_gui_library::window::headless::HeadlessState;
+use std::path::PathBuf;
+
+// a device without a window, on a software adapter when there's no GPU
+pub fn gpu() -> GPUDevice {
+    pollster::block_on(GPUDevice::new_headless(GPUDevice::headless_instance())).expect("no GPU adapter")
+}
+
+pub struct Tolerance {
+    // biggest difference allowed on any one channel before a pixel counts as mismatched
+    pub per_channel: u8,
+    // how many mismatched pixels are allowed. Different rasterizers don't agree on every edge pixel.
+    pub max_mismatched_pixels: usize,
+}
+
+impl Default for Tolerance {
+    fn default() -> Self {
+        Self {
+            per_channel: 2,
+            max_mismatched_pixels: 0,
+        }
+    }
+}
+
+pub fn render_headless(width: u32, height: u32) -> RgbaImage {
+    render_headless_with(width, height, |_| {})
+}
+
+// `setup` gets the state before the frame is drawn, to move the camera etc.
+pub fn render_headless_with(width: u32, height: u32, setup: impl FnOnce(&mut HeadlessState)) -> RgbaImage {
+    render_headless_with_settings(width, height, RenderSettings::default(), setup)
+}
+
+pub fn render_headless_with_settings(
+    width: u32,
+    height: u32,
+    settings: RenderSettings,
+    setup: impl FnOnce(&mut HeadlessState),
+) -> RgbaImage {
+    let mut state = pollster::block_on(HeadlessState::with_settings(
+        width,
+        height,
+        HeadlessState::DEFAULT_FORMAT,
+        settings,
+    ))
+        .expect("no wgpu adapter found, the golden tests need at least a software adapter (lavapipe / llvmpipe)");
+    setup(&mut state);
+    state.render();
+    state.capture_frame().expect("failed to read back the frame")
+}
+
+pub fn assert_golden(name: &str, actual: &RgbaImage, tolerance: Tolerance) {
+    let manifest = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let reference_path = manifest.join("tests").join("golden").join(format!("{}.png", name));
+
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        actual.save(&reference_path).expect("failed to write golden image");
+        eprintln!("updated {}", reference_path.display());
+        return;
+    }
+
+    let expected = match image::open(&reference_path) {
+        Ok(image) => image.to_rgba8(),
+        Err(e) => panic!(
+            "missing golden image {} ({}), run with UPDATE_GOLDEN=1 to create it",
+            reference_path.display(),
+            e
+        ),
+    };
+
+    let output_dir = manifest.join("target").join("golden");
+    let actual_path = output_dir.join(format!("{}.actual.png", name));
+    let diff_path = output_dir.join(format!("{}.diff.png", name));
+
+    if expected.dimensions() != actual.dimensions() {
+        std::fs::create_dir_all(&output_dir).unwrap();
+        actual.save(&actual_path).unwrap();
+        panic!(
+            "{}: size changed, expected {:?} got {:?} (actual frame in {})",
+            name,
+            expected.dimensions(),
+            actual.dimensions(),
+            actual_path.display()
+        );
+    }
+
+    let (diff, mismatched, worst) = diff_images(&expected, actual, tolerance.per_channel);
+    if mismatched > tolerance.max_mismatched_pixels {
+        std::fs::create_dir_all(&output_dir).unwrap();
+        actual.save(&actual_path).unwrap();
+        diff.save(&diff_path).unwrap();
+        panic!(
+            "{}: {} pixels differ by more than {} (worst channel difference {}, {} allowed). Actual: {} Diff: {}",
+            name,
+            mismatched,
+            tolerance.per_channel,
+            worst,
+            tolerance.max_mismatched_pixels,
+            actual_path.display(),
+            diff_path.display()
+        );
+    }
+}
+
+fn diff_images(expected: &RgbaImage, actual: &RgbaImage, per_channel: u8) -> (RgbaImage, usize, u8) {
+    let mut diff = RgbaImage::new(expected.width(), expected.height());
+    let mut mismatched = 0;
+    let mut worst = 0;
+
+    for (x, y, e) in expected.enumerate_pixels() {
+        let a = actual.get_pixel(x, y);
+        let delta = e.0.iter().zip(a.0.iter()).map(|(e, a)| e.abs_diff(*a)).max().unwrap_or(0);
+        worst = worst.max(delta);
+
+        if delta > per_channel {
+            mismatched += 1;
+            diff.put_pixel(x, y, Rgba([255, 0, 0, 255]));
+        } else {
+            // faded grayscale of the expected frame so the red pixels have something to line up with
+            let luma = (e[0] as u32 * 3 + e[1] as u32 * 6 + e[2] as u32) / 10;
+            let faded = (luma / 3) as u8;
+            diff.put_pixel(x, y, Rgba([faded, faded, faded, 255]));
+        }
+    }
+
+    (diff, mismatched, worst)
+}
@@ -0,0 +1,355 @@
+mod common;
+
+use common::{Tolerance, assert_golden, render_headless, render_headless_with, render_headless_with_settings};
+use std::cell::Cell;
+use std::rc::Rc;
+use rs_gui_library::geometry::rect::{Point, Rect, Size};
+use rs_gui_library::render::painter::{Color, Painter, Stroke};
+use rs_gui_library::layout::style::{Align, Style};
+use rs_gui_library::render::render::RenderSettings;
+use rs_gui_library::text::font::Font;
+use rs_gui_library::text::text::{TextAlign, TextStyle, VerticalAlign};
+use rs_gui_library::widgets::label::Label;
+use rs_gui_library::widgets::panel::Panel;
+use rs_gui_library::widgets::toggle::Toggle;
+use rs_gui_library::widgets::widget::{Widget, WidgetContext};
+use nalgebra::{Matrix4, Point3, UnitQuaternion, Vector3};
+use rs_gui_library::render::instance::Instance;
+use rs_gui_library::geometry::mesh::MeshData;
+use rs_gui_library::geometry::model::Model;
+use rs_gui_library::scene::light::Light;
+use rs_gui_library::scene::scene::{Attachment, Transform};
+use rs_gui_library::shaders::shader::VertexShaders;
+use rs_gui_library::window::headless::HeadlessState;
+use winit::event::MouseButton;
+use winit::keyboard::KeyCode;
+
+// The default scene: shader.wgsl, the CameraMatrix view/projection and the draw in render::draw_scene
+// all end up in these frames, so any change to them shows up as a diff.
+
+#[test]
+fn default_scene_square() {
+    let frame = render_headless(128, 128);
+    assert_golden("default_scene_square", &frame, Tolerance::default());
+}
+
+#[test]
+fn default_scene_wide() {
+    let frame = render_headless(200, 100);
+    assert_golden("default_scene_wide", &frame, Tolerance::default());
+}
+
+// the camera gets moved after the pipeline exists, so this only passes if the new matrices reach the GPU
+#[test]
+fn moved_camera() {
+    let frame = render_headless_with(128, 128, |state| {
+        state.camera.set_view(&Point3::new(2.0, 1.0, 4.0), &Point3::origin(), &Vector3::y());
+    });
+    assert_golden("moved_camera", &frame, Tolerance::default());
+}
+
+// 1600 small cubes in one draw. Some are removed and one is moved and recolored after they've been drawn once,
+// so the partial uploads of the instance buffer have to land in the right place.
+#[test]
+fn instanced_cubes() {
+    let frame = render_headless_with(128, 128, |state| {
+        state.camera.set_view(&Point3::new(0.0, 6.0, 6.0), &Point3::origin(), &Vector3::y());
+        // the default cubes are scene nodes, these are all hand placed
+        state.scene().clear();
+        state.instances().clear();
+
+        let mut ids = Vec::new();
+        for x in 0..40 {
+            for z in 0..40 {
+                let position = Vector3::new(x as f32 - 19.5, 0.0, z as f32 - 19.5) * 0.15;
+                let color = [x as f32 / 39.0, 1.0, z as f32 / 39.0, 1.0];
+                let model = Matrix4::new_translation(&position) * Matrix4::new_scaling(0.1);
+                ids.push(state.instances().add(Instance::new(model).with_color(color)));
+            }
+        }
+        // everything is on the GPU after this, what follows only rewrites part of the buffer
+        state.render();
+
+        let instances = state.instances();
+        // a hole in the middle
+        for x in 15..25 {
+            for z in 15..25 {
+                instances.remove(ids[x * 40 + z]);
+            }
+        }
+        let lifted = Matrix4::new_translation(&Vector3::new(0.0, 0.5, 0.0)) * Matrix4::new_scaling(0.5);
+        instances.update(ids[0], Instance::new(lifted).with_color([1.0, 0.2, 0.2, 1.0]));
+        assert_eq!(instances.len(), 1500);
+    });
+    assert_golden("instanced_cubes", &frame, Tolerance::default());
+}
+
+// A small hierarchy with its own camera and lights. It's drawn once, then the parent turns and a node goes
+// away, so the second frame only has the world matrices the change touched rewritten.
+#[test]
+fn scene_graph() {
+    let frame = render_headless_with(128, 128, |state| {
+        state.scene().clear();
+        let sphere = state.add_mesh("sphere", MeshData::uv_sphere(0.5, 24, 12)).unwrap();
+
+        let scene = state.scene();
+        let turntable = scene.add("turntable", Transform::default());
+        let left = scene.add_child(turntable, "left", Transform::at(Vector3::new(-1.0, 0.0, 0.0))).unwrap();
+        scene.attach(left, Attachment::mesh(VertexShaders::CUBE));
+        let right = scene.add_child(turntable, "right", Transform::at(Vector3::new(1.0, 0.0, 0.0))).unwrap();
+        scene.attach(right, Attachment::mesh(sphere));
+        let moon = scene.add_child(right, "moon", Transform::at(Vector3::new(0.0, 0.9, 0.0)).with_scale(Vector3::new(0.3, 0.3, 0.3))).unwrap();
+        scene.attach(moon, Attachment::Mesh { mesh: VertexShaders::CUBE, color: [1.0, 0.3, 0.3, 1.0] });
+        let extra = scene.add_child(left, "extra", Transform::at(Vector3::new(0.0, 1.0, 0.0))).unwrap();
+        scene.attach(extra, Attachment::mesh(sphere));
+
+        let camera = scene.add("camera", Transform::looking_at(Point3::new(0.0, 2.0, 4.0), Point3::origin(), Vector3::y()));
+        scene.attach(camera, Attachment::Camera);
+        let lamp = scene.add("lamp", Transform::at(Vector3::new(0.0, 1.5, 1.5)));
+        scene.attach(lamp, Attachment::Light(Light::point([1.0, 0.8, 0.6], 1.5, 5.0)));
+        let fill = scene.add("fill", Transform::looking_at(Point3::new(-1.0, 0.5, 0.0), Point3::origin(), Vector3::y()));
+        scene.attach(fill, Attachment::Light(Light::directional([0.3, 0.4, 1.0], 0.5)));
+        scene.ambient = [0.1, 0.1, 0.1];
+        state.render();
+
+        let scene = state.scene();
+        let turned = Transform::default().with_rotation(UnitQuaternion::from_axis_angle(&Vector3::y_axis(), 0.6));
+        scene.set_transform(turntable, turned);
+        scene.remove(extra);
+    });
+    assert_golden("scene_graph", &frame, Tolerance::default());
+}
+
+// The lights uniform is only rebuilt when a light node or the ambient changes, each of those has to show up
+// in the next frame and a frame where nothing changed has to look the same.
+#[test]
+fn lights_follow_their_nodes() {
+    let mut state = pollster::block_on(HeadlessState::new(64, 64)).expect("no wgpu adapter found");
+    let frame = |state: &mut HeadlessState| {
+        state.render();
+        state.capture_frame().unwrap()
+    };
+    let lamp = state.scene().add("lamp", Transform::looking_at(Point3::new(0.0, 1.0, 0.2), Point3::origin(), Vector3::y()));
+    state.scene().attach(lamp, Attachment::Light(Light::directional([1.0, 1.0, 1.0], 1.0)));
+    let from_above = frame(&mut state);
+    assert_eq!(frame(&mut state), from_above);
+
+    state.scene().set_transform(lamp, Transform::looking_at(Point3::new(0.0, 0.2, 1.0), Point3::origin(), Vector3::y()));
+    let from_front = frame(&mut state);
+    assert_ne!(from_front, from_above);
+
+    // back to the fallback light once the last one is gone
+    state.scene().remove(lamp);
+    let fallback = frame(&mut state);
+    assert_ne!(fallback, from_front);
+
+    state.scene().ambient = [0.6, 0.6, 0.6];
+    assert_ne!(frame(&mut state), fallback);
+}
+
+// quad.obj and quad.gltf put into the scene with add_model, both sample checker.png through the material texture.
+// The glTF one also has a node transform and a pink base color.
+#[test]
+fn textured_models() {
+    let frame = render_headless_with(128, 128, |state| {
+        state.camera.set_view(&Point3::new(0.0, 0.0, 7.0), &Point3::origin(), &Vector3::y());
+        state.scene().clear();
+        let obj = Model::load(model_asset("quad.obj")).unwrap();
+        let gltf = Model::load(model_asset("quad.gltf")).unwrap();
+
+        let left = state.add_model("obj", &obj).unwrap();
+        let right = state.add_model("gltf", &gltf).unwrap();
+        let scene = state.scene();
+        scene.set_transform(left, Transform::at(Vector3::new(-1.3, 0.0, 0.0)));
+        // the file scales it up to 4 units, the root brings it back to 2 like the OBJ
+        scene.set_transform(right, Transform::at(Vector3::new(0.8, 0.0, 0.0)).with_scale(Vector3::new(0.5, 0.5, 0.5)));
+    });
+    assert_golden("textured_models", &frame, Tolerance::default());
+}
+
+// A mirrored node has its winding flipped, it must still show its outside like the cube next to it. The
+// squashed sphere in the middle checks the normals go through the inverse transpose and stay lit like a
+// flattened ball instead of going dark along the rim.
+#[test]
+fn mirrored_and_squashed_nodes() {
+    let frame = render_headless_with(160, 96, |state| {
+        state.camera.set_view(&Point3::new(1.5, 2.0, 5.0), &Point3::origin(), &Vector3::y());
+        state.scene().clear();
+        let sphere = state.add_mesh("sphere", MeshData::uv_sphere(0.6, 24, 12)).unwrap();
+
+        let scene = state.scene();
+        let left = scene.add("left", Transform::at(Vector3::new(-1.6, 0.0, 0.0)));
+        scene.attach(left, Attachment::mesh(VertexShaders::CUBE));
+        let right = scene.add("mirrored", Transform::at(Vector3::new(1.6, 0.0, 0.0)).with_scale(Vector3::new(-1.0, 1.0, 1.0)));
+        scene.attach(right, Attachment::mesh(VertexShaders::CUBE));
+        let squashed = scene.add("squashed", Transform::default().with_scale(Vector3::new(1.2, 0.4, 1.2)));
+        scene.attach(squashed, Attachment::mesh(sphere));
+        let lamp = scene.add("lamp", Transform::looking_at(Point3::new(1.0, 3.0, 2.0), Point3::origin(), Vector3::y()));
+        scene.attach(lamp, Attachment::Light(Light::directional([1.0, 1.0, 1.0], 1.0)));
+        scene.ambient = [0.15, 0.15, 0.15];
+    });
+    assert_golden("mirrored_and_squashed_nodes", &frame, Tolerance::default());
+}
+
+fn model_asset(name: &str) -> String {
+    format!("{}/tests/assets/models/{}", env!("CARGO_MANIFEST_DIR"), name)
+}
+
+// goes through the multisampled texture + resolve, the quad edges come out smoothed
+#[test]
+fn default_scene_msaa() {
+    let settings = RenderSettings {
+        sample_count: 4,
+        ..Default::default()
+    };
+    let frame = render_headless_with_settings(128, 128, settings, |_| {});
+    assert_golden("default_scene_msaa", &frame, Tolerance::default());
+}
+
+// a fixed size colored box, just enough of a widget to see where the tree puts things
+struct Swatch {
+    size: Size,
+    color: Color,
+}
+
+impl Widget for Swatch {
+    fn measure(&mut self, _available: Size) -> Size {
+        self.size
+    }
+
+    fn paint(&self, ctx: &WidgetContext, painter: &mut Painter) {
+        painter.fill_rounded_rect(ctx.bounds, 6.0, self.color);
+    }
+}
+
+// the widget tree drawn over the scene through the pixel space camera
+#[test]
+fn widget_tree_overlay() {
+    let frame = render_headless_with(128, 128, |state| {
+        let root = state.ui.set_root(Panel::new());
+        state.ui.add_child(root, Swatch { size: Size::new(60.0, 30.0), color: Color::from_rgba8(230, 80, 60, 255) });
+        state.ui.add_child(root, Swatch { size: Size::new(90.0, 20.0), color: Color::from_rgba8(40, 200, 120, 180) });
+        let circle = state.ui.add_child(root, Panel::with_background(Color::WHITE)).unwrap();
+        state.ui.get_mut::<Panel>(circle).unwrap().corner_radius = 100.0;
+        // takes whatever height the swatches leave
+        state.ui.set_style(circle, Style::fill());
+    });
+    assert_golden("widget_tree_overlay", &frame, Tolerance::default());
+}
+
+// off, on, clicked from off to on, disabled, and one that has focus after a key press turned it off
+#[test]
+fn toggle_states() {
+    let clicked = Rc::new(Cell::new(false));
+    let changes = Rc::new(Cell::new(0));
+
+    let frame = render_headless_with(128, 160, |state| {
+        let root = state.ui.set_root(Panel::with_background(Color::from_rgba8(30, 30, 36, 255)));
+        state.ui.add_child(root, Toggle::new(false));
+        state.ui.add_child(root, Toggle::new(true));
+        let changes = changes.clone();
+        let click = state.ui.add_child(
+            root,
+            Toggle::default().bind(clicked.clone()).on_change(move |_| changes.set(changes.get() + 1)),
+        ).unwrap();
+        state.ui.add_child(root, Toggle::new(true).disabled(true));
+        let keyed = state.ui.add_child(root, Toggle::new(true)).unwrap();
+        state.ui.layout();
+
+        let center = state.ui.bounds(click).unwrap().center();
+        state.ui.pointer_moved(center);
+        state.ui.pointer_button(MouseButton::Left, true);
+        state.ui.pointer_button(MouseButton::Left, false);
+        state.ui.pointer_moved(Point::new(127.0, 159.0));
+
+        state.ui.set_focus(Some(keyed));
+        state.ui.key(KeyCode::Space, true);
+        state.ui.key(KeyCode::Space, false);
+
+        // run the knob animations to the end
+        while state.ui.update(0.05) {}
+    });
+
+    assert!(clicked.get());
+    assert_eq!(changes.get(), 1);
+    assert_golden("toggle_states", &frame, Tolerance::default());
+}
+
+// paints straight into the painter, covers every kind of shape the UI renderer batches
+struct Shapes;
+
+impl Widget for Shapes {
+    fn measure(&mut self, available: Size) -> Size {
+        available
+    }
+
+    fn paint(&self, _ctx: &WidgetContext, painter: &mut Painter) {
+        let red = Color::from_rgba8(230, 80, 60, 255);
+        let blue = Color::from_rgba8(60, 120, 230, 255);
+        let yellow = Color::from_rgba8(250, 210, 60, 255);
+
+        painter.fill_rect(Rect::new(0.0, 0.0, 128.0, 128.0), Color::from_rgba8(30, 30, 36, 255));
+        painter.fill_rect(Rect::new(8.0, 8.0, 30.0, 20.0), red);
+        painter.rounded_rect(Rect::new(48.0, 8.0, 72.0, 20.0), 10.0, blue, Stroke::new(3.0, Color::WHITE));
+        painter.stroke_rect(Rect::new(8.0, 36.0, 30.0, 30.0), Stroke::new(2.0, yellow));
+        painter.stroke_rounded_rect(Rect::new(48.0, 36.0, 30.0, 30.0), 8.0, Stroke::new(1.5, Color::WHITE));
+        painter.fill_circle(Point::new(104.0, 51.0), 15.0, yellow);
+        painter.fill_ellipse(Rect::new(8.0, 76.0, 60.0, 24.0), blue);
+        painter.ellipse(Rect::new(76.0, 72.0, 44.0, 44.0), red, Stroke::new(4.0, Color::BLACK));
+        painter.stroke_circle(Point::new(38.0, 112.0), 10.0, Stroke::new(2.0, red));
+        // half transparent on top of what's already there
+        painter.fill_rounded_rect(Rect::new(20.0, 20.0, 80.0, 70.0), 12.0, Color::WHITE.with_alpha(0.25));
+    }
+}
+
+#[test]
+fn shape_batch() {
+    let frame = render_headless_with(128, 128, |state| {
+        state.ui.set_root(Shapes);
+    });
+    assert_golden("shape_batch", &frame, Tolerance::default());
+}
+
+fn test_font() -> Font {
+    Font::from_file(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/fonts/DejaVuSans.ttf")).expect("test font")
+}
+
+// labels through the layout (left, centered, wrapped) plus text painted straight into a box
+#[test]
+fn text_labels() {
+    let frame = render_headless_with(160, 160, |state| {
+        let font = test_font();
+        let style = TextStyle::new(font.clone(), 14.0).with_color(Color::WHITE);
+
+        let root = state.ui.set_root(Panel::with_background(Color::from_rgba8(30, 30, 36, 255)));
+        state.ui.add_child(root, Label::new("Hello, world!", style.clone()));
+        let centered = state.ui.add_child(root, Label::new("centered", style.clone().with_align(TextAlign::Center))).unwrap();
+        state.ui.set_style(centered, Style { align_self: Some(Align::Stretch), ..Default::default() });
+        state.ui.add_child(
+            root,
+            Label::new(
+                "Wrapped text breaks between words to fit.",
+                TextStyle::new(font.clone(), 12.0).with_color(Color::from_rgba8(250, 210, 60, 255)).with_wrap(true),
+            ),
+        );
+        state.ui.add_child(root, TextBox { style: TextStyle::new(font, 18.0).with_color(Color::BLACK) });
+    });
+    assert_golden("text_labels", &frame, Tolerance::default());
+}
+
+// a light box with its text in the bottom right corner
+struct TextBox {
+    style: TextStyle,
+}
+
+impl Widget for TextBox {
+    fn measure(&mut self, available: Size) -> Size {
+        Size::new(available.width, 40.0)
+    }
+
+    fn paint(&self, ctx: &WidgetContext, painter: &mut Painter) {
+        painter.fill_rect(ctx.bounds, Color::from_rgba8(220, 220, 230, 255));
+        let style = self.style.clone().with_align(TextAlign::Right).with_vertical_align(VerticalAlign::Bottom);
+        painter.text(ctx.bounds.inset(4.0), "Ag 42", &style);
+    }
+}
@@ -0,0 +1,132 @@
+mod common;
+
+use common::gpu;
+use std::path::PathBuf;
+use rs_gui_library::geometry::geometry::Vertex;
+use rs_gui_library::gpu::gpu::GPUDevice;
+use rs_gui_library::shaders::pipeline::{PipelineBuilder, PipelineCache};
+use rs_gui_library::shaders::source::{ShaderFile, ShaderPipeline};
+
+fn shader(red: &str) -> String {
+    format!(
+        r#"
+@vertex
+fn vs_main(@location(0) position: vec3<f32>, @location(1) color: vec3<f32>) -> @builtin(position) vec4<f32> {{
+    return vec4<f32>(position, 1.0);
+}}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {{
+    return vec4<f32>({}, 0.0, 0.0, 1.0);
+}}
+"#,
+        red
+    )
+}
+
+// a file of its own in the temp directory, ShaderFile wants a 'static path
+fn temp_shader(name: &str, source: &str) -> ShaderFile {
+    let path = std::env::temp_dir().join(format!("rs-gui-{}-{}.wgsl", name, std::process::id()));
+    std::fs::write(&path, source).unwrap();
+    ShaderFile {
+        path: Box::leak(path.to_string_lossy().into_owned().into_boxed_str()),
+        embedded: "",
+    }
+}
+
+fn pipeline(gpu: &GPUDevice, pipelines: &mut PipelineCache, file: ShaderFile) -> ShaderPipeline {
+    let builder = PipelineBuilder::new(file.name(), file.read().unwrap())
+        .vertex_layout(Vertex::desc())
+        .target(wgpu::TextureFormat::Rgba8Unorm);
+    ShaderPipeline::new(&gpu.device, pipelines, file, builder).unwrap()
+}
+
+#[test]
+fn broken_edits_keep_the_last_good_pipeline() {
+    let gpu = gpu();
+    let mut pipelines = PipelineCache::new();
+    let file = temp_shader("reload", &shader("1.0"));
+    let mut shader_pipeline = pipeline(&gpu, &mut pipelines, file);
+    let first = shader_pipeline.pipeline.clone();
+
+    // the error says where, and nothing is swapped
+    let err = shader_pipeline
+        .reload(&gpu.device, &mut pipelines, shader("1.0 +"))
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains(&format!("{}:9:", file.name())), "{}", err);
+    assert_eq!(shader_pipeline.pipeline, first);
+    assert_eq!(shader_pipeline.builder.descriptor.shader, shader("1.0"));
+
+    // only files that changed are looked at
+    std::fs::write(file.path, shader("0.5")).unwrap();
+    assert!(!shader_pipeline.reload_changed(&gpu.device, &mut pipelines, &[PathBuf::from("other.wgsl")]));
+    assert!(shader_pipeline.reload_changed(&gpu.device, &mut pipelines, &[PathBuf::from(file.path)]));
+    assert_ne!(shader_pipeline.pipeline, first);
+    let second = shader_pipeline.pipeline.clone();
+
+    // half way through typing
+    std::fs::write(file.path, shader("0.")).unwrap();
+    std::fs::write(file.path, shader("0.5 * ")).unwrap();
+    assert!(!shader_pipeline.reload_changed(&gpu.device, &mut pipelines, &[PathBuf::from(file.path)]));
+    assert_eq!(shader_pipeline.pipeline, second);
+
+    std::fs::remove_file(file.path).unwrap();
+}
+
+#[cfg(feature = "hot-reload")]
+#[test]
+fn watcher_sees_saves() {
+    use std::time::{Duration, Instant};
+    use rs_gui_library::shaders::watcher::ShaderWatcher;
+
+    let file = temp_shader("watch", &shader("1.0"));
+    let other = temp_shader("not-watched", &shader("1.0"));
+    let watcher = ShaderWatcher::new(&[file]).unwrap();
+
+    std::fs::write(other.path, shader("0.0")).unwrap();
+    std::fs::write(file.path, shader("0.5")).unwrap();
+
+    let started = Instant::now();
+    let mut changed = Vec::new();
+    while changed.is_empty() && started.elapsed() < Duration::from_secs(5) {
+        std::thread::sleep(Duration::from_millis(20));
+        changed = watcher.poll();
+    }
+    assert_eq!(changed, [PathBuf::from(file.path)]);
+
+    std::fs::remove_file(file.path).unwrap();
+    std::fs::remove_file(other.path).unwrap();
+}
+
+// the file on disk is read at startup but doesn't build, the pipeline comes from the embedded copy instead
+#[cfg(feature = "hot-reload")]
+#[test]
+fn startup_falls_back_to_the_embedded_shader() {
+    let gpu = gpu();
+    let mut pipelines = PipelineCache::new();
+    let broken = temp_shader("startup", &shader("true"));
+    let file = ShaderFile {
+        embedded: Box::leak(shader("1.0").into_boxed_str()),
+        ..broken
+    };
+
+    let built = file
+        .build(|source| {
+            let builder = PipelineBuilder::new(file.name(), source)
+                .vertex_layout(Vertex::desc())
+                .target(wgpu::TextureFormat::Rgba8Unorm);
+            ShaderPipeline::new(&gpu.device, &mut pipelines, file, builder)
+        })
+        .unwrap();
+    assert_eq!(built.builder.descriptor.shader, file.embedded);
+
+    // an embedded copy that doesn't build either is still an error
+    let both_broken = ShaderFile {
+        embedded: Box::leak(shader("false").into_boxed_str()),
+        ..broken
+    };
+    assert!(both_broken.build(|source| pipelines.shader(&gpu.device, "broken", &source).map(|_| ())).is_err());
+
+    std::fs::remove_file(file.path).unwrap();
+}
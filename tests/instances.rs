@@ -0,0 +1,41 @@
+use nalgebra::Vector3;
+use rs_gui_library::render::instance::{Instance, InstanceBuffer};
+
+fn at(x: f32) -> Instance {
+    Instance::at(Vector3::new(x, 0.0, 0.0))
+}
+
+fn xs(instances: &InstanceBuffer) -> Vec<f32> {
+    instances.iter().map(|(_, instance)| instance.model[(0, 3)]).collect()
+}
+
+#[test]
+fn ids_survive_other_removals() {
+    let mut instances = InstanceBuffer::new();
+    let ids: Vec<_> = (0..5).map(|i| instances.add(at(i as f32))).collect();
+
+    // the last one fills the hole
+    assert_eq!(instances.remove(ids[1]), Some(at(1.0)));
+    assert_eq!(xs(&instances), [0.0, 4.0, 2.0, 3.0]);
+    assert_eq!(instances.get(ids[4]), Some(&at(4.0)));
+    assert_eq!(instances.get(ids[1]), None);
+    assert_eq!(instances.remove(ids[1]), None);
+
+    assert!(instances.update(ids[4], at(40.0)));
+    assert!(!instances.update(ids[1], at(10.0)));
+    assert_eq!(xs(&instances), [0.0, 40.0, 2.0, 3.0]);
+
+    // removing the last one doesn't move anything
+    instances.remove(ids[3]);
+    assert_eq!(xs(&instances), [0.0, 40.0, 2.0]);
+
+    // freed ids get reused and point at the new instance
+    let reused = instances.add(at(7.0));
+    assert!(reused == ids[1] || reused == ids[3]);
+    assert_eq!(instances.get(reused), Some(&at(7.0)));
+    assert_eq!(instances.len(), 4);
+
+    instances.clear();
+    assert!(instances.is_empty());
+    assert_eq!(instances.get(ids[0]), None);
+}
@@ -0,0 +1,91 @@
+mod common;
+
+use common::gpu;
+use nalgebra::Vector3;
+use rs_gui_library::geometry::mesh::{Mesh, MeshData, MeshVertex};
+
+fn generated() -> Vec<(&'static str, MeshData)> {
+    vec![
+        ("cube", MeshData::cube(2.0)),
+        ("plane", MeshData::plane(4.0, 2.0, 3)),
+        ("uv_sphere", MeshData::uv_sphere(1.5, 16, 8)),
+        ("cylinder", MeshData::cylinder(1.0, 2.0, 12)),
+        ("cone", MeshData::cone(1.0, 2.0, 12)),
+        ("torus", MeshData::torus(2.0, 0.5, 24, 12)),
+    ]
+}
+
+#[test]
+fn generated_meshes_are_valid() {
+    for (name, data) in generated().into_iter().chain([("grid", MeshData::grid(10.0, 10))]) {
+        data.validate().unwrap_or_else(|err| panic!("{}: {}", name, err));
+        for vertex in &data.vertices {
+            let length = Vector3::from(vertex.normal).norm();
+            assert!((length - 1.0).abs() < 1e-4, "{}: normal {:?} isn't unit length", name, vertex.normal);
+            assert!(vertex.uv.iter().all(|uv| (0.0..=1.0).contains(uv)), "{}: uv {:?}", name, vertex.uv);
+        }
+    }
+}
+
+#[test]
+fn triangles_face_the_way_their_normals_point() {
+    for (name, data) in generated() {
+        assert_eq!(data.topology, wgpu::PrimitiveTopology::TriangleList);
+        for triangle in data.indices.chunks(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| Vector3::from(data.vertices[triangle[i] as usize].position));
+            let face = (b - a).cross(&(c - a));
+            // the poles of the sphere and the tip of the cone have triangles squashed to a line
+            if face.norm() < 1e-6 {
+                continue;
+            }
+            let normal: Vector3<f32> = triangle.iter().map(|&i| Vector3::from(data.vertices[i as usize].normal)).sum();
+            assert!(face.dot(&normal) > 0.0, "{}: triangle {:?} winds clockwise", name, triangle);
+        }
+    }
+}
+
+#[test]
+fn sizes_and_counts() {
+    let cube = MeshData::cube(2.0);
+    assert_eq!((cube.vertices.len(), cube.indices.len()), (24, 36));
+    for vertex in &cube.vertices {
+        assert!(vertex.position.iter().all(|coordinate| coordinate.abs() == 1.0));
+    }
+
+    let plane = MeshData::plane(4.0, 2.0, 3);
+    assert_eq!((plane.vertices.len(), plane.indices.len()), (16, 54));
+    assert!(plane.vertices.iter().all(|vertex| vertex.position[0].abs() <= 2.0 && vertex.position[2].abs() <= 1.0));
+
+    let sphere = MeshData::uv_sphere(1.5, 16, 8);
+    assert!(sphere.vertices.iter().all(|vertex| (Vector3::from(vertex.position).norm() - 1.5).abs() < 1e-4));
+
+    let grid = MeshData::grid(10.0, 10);
+    assert_eq!(grid.topology, wgpu::PrimitiveTopology::LineList);
+    assert_eq!(grid.indices.len(), 11 * 2 * 2);
+}
+
+// what geometry::Cube used to be: four vertices and indices for eight
+#[test]
+fn out_of_range_indices_are_rejected() {
+    let vertex = MeshVertex::new(Vector3::zeros(), Vector3::z(), [0.0, 0.0]);
+    let err = MeshData::new(vec![vertex; 4], vec![0, 1, 2, 2, 3, 0, 4, 5, 6, 6, 7, 4], wgpu::PrimitiveTopology::TriangleList)
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("index 4 (at 6) is out of range, there are 4 vertices"), "{}", err);
+
+    let err = MeshData::new(vec![vertex; 4], vec![0, 1, 2, 3], wgpu::PrimitiveTopology::TriangleList)
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("4 indices"), "{}", err);
+
+    // fields are pub so MeshData can be changed after it's made, Mesh::new checks again
+    let gpu = gpu();
+    let mut data = MeshData::cube(1.0);
+    data.indices.push(24);
+    data.indices.extend([0, 1]);
+    assert!(Mesh::new(&gpu.device, "broken", data).is_err());
+
+    let mesh = Mesh::new(&gpu.device, "cube", MeshData::cube(1.0)).unwrap();
+    assert_eq!(mesh.num_indices, 36);
+    assert_eq!(mesh.vertex_buffer.size(), 24 * std::mem::size_of::<MeshVertex>() as u64);
+}
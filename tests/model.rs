@@ -0,0 +1,121 @@
+mod common;
+
+use common::gpu;
+use nalgebra::{Matrix4, Point3, Vector3, Vector4};
+use rs_gui_library::geometry::model::Model;
+
+fn asset(name: &str) -> String {
+    format!("{}/tests/assets/models/{}", env!("CARGO_MANIFEST_DIR"), name)
+}
+
+fn assert_close(actual: impl IntoIterator<Item = f32>, expected: impl IntoIterator<Item = f32>) {
+    let (actual, expected): (Vec<f32>, Vec<f32>) = (actual.into_iter().collect(), expected.into_iter().collect());
+    assert_eq!(actual.len(), expected.len(), "{:?} != {:?}", actual, expected);
+    assert!(actual.iter().zip(&expected).all(|(a, e)| (a - e).abs() < 1e-5), "{:?} != {:?}", actual, expected);
+}
+
+// checker.png is red, green / blue, white
+fn assert_checker(model: &Model, material: usize) {
+    let image = model.materials[material].base_color_texture.as_ref().expect("no texture");
+    assert_eq!(image.dimensions(), (2, 2));
+    assert_eq!(image.get_pixel(0, 0).0, [255, 0, 0, 255]);
+    assert_eq!(image.get_pixel(1, 1).0, [255, 255, 255, 255]);
+}
+
+#[test]
+fn obj_with_materials() {
+    let model = Model::load(asset("quad.obj")).unwrap();
+    assert_eq!(model.meshes.len(), 2);
+    assert_eq!(model.materials.len(), 2);
+
+    let quad = &model.meshes[0];
+    assert_eq!(quad.name, "quad");
+    assert_eq!((quad.data.vertices.len(), quad.data.indices.len()), (4, 6));
+    quad.data.validate().unwrap();
+    assert_eq!(quad.transform, Matrix4::identity());
+    let first = quad.data.vertices[0];
+    assert_close(first.position, [-1.0, -1.0, 0.0]);
+    assert_close(first.normal, [0.0, 0.0, 1.0]);
+    // flipped so the bottom of the quad samples the bottom of the image
+    assert_close(first.uv, [0.0, 1.0]);
+    assert_close(first.color, [1.0, 0.0, 0.0, 1.0]);
+
+    let material = model.material(quad).unwrap();
+    assert_eq!(material.name, "checker");
+    assert_checker(&model, quad.material.unwrap());
+
+    // no normals in the file, they're worked out from the winding
+    let triangle = &model.meshes[1];
+    for vertex in &triangle.data.vertices {
+        assert_close(vertex.normal, [-1.0, 0.0, 0.0]);
+    }
+    let red = model.material(triangle).unwrap();
+    assert_eq!(red.base_color, [1.0, 0.0, 0.0, 0.5]);
+    assert!(red.base_color_texture.is_none());
+    assert_eq!(model.instance(triangle, Matrix4::identity()).color, [1.0, 0.0, 0.0, 0.5]);
+}
+
+#[test]
+fn gltf_with_external_buffers_and_images() {
+    let model = Model::load(asset("quad.gltf")).unwrap();
+    assert_eq!(model.meshes.len(), 1);
+    let quad = &model.meshes[0];
+    assert_eq!(quad.data.indices, [0, 1, 2, 2, 3, 0]);
+    assert_close(quad.data.vertices[1].color, [0.0, 1.0, 0.0, 1.0]);
+    assert_close(quad.data.vertices[3].uv, [0.0, 0.0]);
+
+    // the child node scales by 2, its parent moves it along x
+    assert_close(quad.transform.transform_point(&Point3::new(1.0, 1.0, 0.0)).coords.iter().copied(), [3.0, 2.0, 0.0]);
+    let instance = model.instance(quad, Matrix4::new_translation(&Vector3::new(0.0, 0.0, 5.0)));
+    assert_close(instance.model.transform_point(&Point3::origin()).coords.iter().copied(), [1.0, 0.0, 5.0]);
+
+    let material = model.material(quad).unwrap();
+    assert_eq!(material.name, "checker");
+    assert_eq!(material.base_color, [1.0, 0.5, 1.0, 1.0]);
+    assert_checker(&model, 0);
+}
+
+#[test]
+fn glb_with_embedded_image_and_no_indices() {
+    let model = Model::load(asset("quad.glb")).unwrap();
+    let quad = &model.meshes[0];
+    assert_eq!(quad.data.indices, [0, 1, 2, 3, 4, 5]);
+    assert!(quad.data.vertices.iter().all(|vertex| vertex.color == [1.0; 4]));
+    for vertex in &quad.data.vertices {
+        assert_close(vertex.normal, [0.0, 0.0, 1.0]);
+    }
+    // a quarter turn around y
+    let facing = quad.transform * Vector4::new(0.0, 0.0, 1.0, 0.0);
+    assert_close(facing.iter().copied(), [1.0, 0.0, 0.0, 0.0]);
+    assert_checker(&model, 0);
+}
+
+#[test]
+fn gltf_uses_the_uv_set_the_texture_names() {
+    // TEXCOORD_0 is all zeros, the texture says texCoord 1
+    let model = Model::load(asset("triangle_uv1.gltf")).unwrap();
+    let uvs: Vec<f32> = model.meshes[0].data.vertices.iter().flat_map(|vertex| vertex.uv).collect();
+    assert_close(uvs, [0.0, 1.0, 1.0, 1.0, 1.0, 0.0]);
+}
+
+#[test]
+fn models_upload() {
+    let gpu = gpu();
+    let model = Model::load(asset("quad.glb")).unwrap();
+    let meshes = model.create_meshes(&gpu.device).unwrap();
+    assert_eq!(meshes[0].num_indices, 6);
+
+    let texture = model.materials[0].texture(&gpu.device, &gpu.queue).unwrap();
+    assert_eq!((texture.width, texture.height, texture.mip_level_count), (2, 2, 2));
+    let pixels = texture.read_rgba8(&gpu.device, &gpu.queue).unwrap();
+    assert_eq!(pixels.get_pixel(0, 1).0, [0, 0, 255, 255]);
+}
+
+#[test]
+fn bad_files_say_what_went_wrong() {
+    let err = Model::load("scene.fbx").unwrap_err().to_string();
+    assert!(err.contains(".fbx"), "{}", err);
+
+    let err = format!("{:#}", Model::load(asset("missing.gltf")).unwrap_err());
+    assert!(err.contains("missing.gltf"), "{}", err);
+}
@@ -0,0 +1,130 @@
+mod common;
+
+use common::gpu;
+use rs_gui_library::geometry::geometry::Vertex;
+use rs_gui_library::render::render::RenderSettings;
+use rs_gui_library::shaders::pipeline::{PipelineBuilder, PipelineCache};
+
+const SHADER: &str = r#"
+struct Camera { view_proj: mat4x4<f32> };
+@group(0) @binding(0) var<uniform> camera: Camera;
+
+struct VertexOut {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec3<f32>,
+};
+
+@vertex
+fn vs_main(@location(0) position: vec3<f32>, @location(1) color: vec3<f32>) -> VertexOut {
+    var out: VertexOut;
+    out.position = camera.view_proj * vec4<f32>(position, 1.0);
+    out.color = color;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOut) -> @location(0) vec4<f32> {
+    return vec4<f32>(in.color, 0.5);
+}
+"#;
+
+const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+fn scene() -> PipelineBuilder {
+    PipelineBuilder::new("scene", SHADER)
+        .vertex_layout(Vertex::desc())
+        .render_settings(&RenderSettings::default(), true, wgpu::CompareFunction::Less)
+        .target(FORMAT)
+}
+
+#[test]
+fn same_descriptor_comes_from_the_cache() {
+    let gpu = gpu();
+    let mut pipelines = PipelineCache::new();
+
+    let first = pipelines.get(&gpu.device, scene()).unwrap();
+    // the label isn't part of the key
+    let mut renamed = scene();
+    renamed.label = "another name".to_string();
+    let second = pipelines.get(&gpu.device, renamed).unwrap();
+
+    assert_eq!(first, second);
+    assert_eq!(pipelines.len(), 1);
+
+    pipelines.clear();
+    assert!(pipelines.is_empty());
+    assert_ne!(pipelines.get(&gpu.device, scene()).unwrap(), first);
+}
+
+// One shader, the variations a renderer keeps around side by side
+#[test]
+fn variants_are_separate_pipelines() {
+    let gpu = gpu();
+    let mut pipelines = PipelineCache::new();
+
+    let opaque = pipelines.get(&gpu.device, scene().cull_mode(Some(wgpu::Face::Back))).unwrap();
+    let blended = pipelines
+        .get(
+            &gpu.device,
+            scene()
+                .blend(Some(wgpu::BlendState::ALPHA_BLENDING))
+                .render_settings(&RenderSettings::default(), false, wgpu::CompareFunction::Less),
+        )
+        .unwrap();
+    let lines = pipelines.get(&gpu.device, scene().topology(wgpu::PrimitiveTopology::LineList)).unwrap();
+    let msaa = pipelines.get(&gpu.device, scene().sample_count(4)).unwrap();
+
+    assert_ne!(opaque, blended);
+    assert_ne!(blended, lines);
+    assert_eq!(pipelines.len(), 4);
+    drop(msaa);
+
+    let wireframe = pipelines.get(&gpu.device, scene().polygon_mode(wgpu::PolygonMode::Line));
+    if gpu.device.features().contains(wgpu::Features::POLYGON_MODE_LINE) {
+        wireframe.unwrap();
+        assert_eq!(pipelines.len(), 5);
+    } else {
+        let err = wireframe.unwrap_err().to_string();
+        assert!(err.contains("POLYGON_MODE_LINE"), "{}", err);
+        assert_eq!(pipelines.len(), 4);
+    }
+}
+
+#[test]
+fn mistakes_are_errors_not_panics() {
+    let gpu = gpu();
+    let mut pipelines = PipelineCache::new();
+
+    // the color attribute is missing
+    let attributes = wgpu::vertex_attr_array![0 => Float32x3];
+    let err = PipelineBuilder::new("scene", SHADER)
+        .vertex_layout(wgpu::VertexBufferLayout {
+            array_stride: 12,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &attributes,
+        })
+        .target(FORMAT)
+        .build(&gpu.device)
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("@location(1) `color`"), "{}", err);
+
+    // broken WGSL, with where it broke
+    let err = pipelines
+        .get(&gpu.device, PipelineBuilder::new("broken.wgsl", "@vertex\nfn vs_main( -> {}\n"))
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("broken.wgsl:2:"), "{}", err);
+
+    let err = pipelines.get(&gpu.device, scene().fragment_entry(Some("fs_missing"))).unwrap_err().to_string();
+    assert!(err.contains("fs_missing"), "{}", err);
+
+    // wgpu's own validation (a depth format that isn't one) comes back as an error too
+    let err = pipelines
+        .get(&gpu.device, scene().depth(wgpu::TextureFormat::Rgba8Unorm, true, wgpu::CompareFunction::Less))
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("couldn't create the pipeline"), "{}", err);
+
+    assert!(pipelines.is_empty());
+}
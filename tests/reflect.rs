@@ -0,0 +1,205 @@
+mod common;
+
+use common::gpu;
+use rs_gui_library::shaders::bind_group::BindGrouping;
+use rs_gui_library::shaders::reflect::ShaderReflection;
+use wgpu::ShaderStages;
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+
+const SHADER: &str = r#"
+struct Camera { view_proj: mat4x4<f32> };
+struct Material { tint: vec4<f32> };
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+@group(1) @binding(0) var<uniform> material: Material;
+@group(1) @binding(1) var albedo: texture_2d<f32>;
+@group(1) @binding(2) var albedo_sampler: sampler;
+
+struct VertexIn {
+    @location(0) position: vec3<f32>,
+    @location(1) uv: vec2<f32>,
+    @builtin(vertex_index) index: u32,
+};
+
+struct VertexOut {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn vs_main(in: VertexIn, @location(2) id: u32) -> VertexOut {
+    var out: VertexOut;
+    out.position = camera.view_proj * vec4<f32>(in.position + vec3<f32>(f32(id)), 1.0);
+    out.uv = in.uv;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOut) -> @location(0) vec4<f32> {
+    return textureSample(albedo, albedo_sampler, in.uv) * material.tint;
+}
+"#;
+
+fn buffer(device: &wgpu::Device, size: usize) -> wgpu::Buffer {
+    device.create_buffer_init(&BufferInitDescriptor {
+        label: None,
+        contents: &vec![0u8; size],
+        usage: wgpu::BufferUsages::UNIFORM,
+    })
+}
+
+#[test]
+fn reflects_bindings_and_vertex_inputs() {
+    let reflection = ShaderReflection::from_wgsl("test.wgsl", SHADER).unwrap();
+
+    assert_eq!(reflection.group_count(), 2);
+    let camera = &reflection.bindings[0];
+    assert_eq!((camera.group, camera.binding, camera.name.as_str()), (0, 0, "camera"));
+    assert_eq!(camera.visibility, ShaderStages::VERTEX);
+    assert_eq!(camera.size, Some(64));
+
+    let material: Vec<_> = reflection.group(1).map(|binding| (binding.name.as_str(), binding.visibility)).collect();
+    assert_eq!(
+        material,
+        [
+            ("material", ShaderStages::FRAGMENT),
+            ("albedo", ShaderStages::FRAGMENT),
+            ("albedo_sampler", ShaderStages::FRAGMENT),
+        ]
+    );
+
+    let inputs: Vec<_> = reflection.entry_point("vs_main").unwrap().vertex_inputs.iter().map(|input| (input.location, input.name.as_str(), input.components)).collect();
+    assert_eq!(inputs, [(0, "position", 3), (1, "uv", 2), (2, "id", 1)]);
+}
+
+#[test]
+fn parse_errors_point_at_the_line() {
+    let source = "struct A { x: f32 };\n\nfn broken() -> f32 {\n    return 1.0 +;\n}\n";
+    let err = ShaderReflection::from_wgsl("broken.wgsl", source).unwrap_err().to_string();
+    assert!(err.contains("broken.wgsl:4:"), "{}", err);
+
+    // validation errors too, not only syntax
+    let source = "@group(0) @binding(0) var<uniform> x: f32;\n\nfn f() -> u32 {\n    return x;\n}\n";
+    let err = ShaderReflection::from_wgsl("types.wgsl", source).unwrap_err().to_string();
+    assert!(err.contains("types.wgsl"), "{}", err);
+}
+
+#[test]
+fn mismatched_bind_groups_are_errors() {
+    let gpu = gpu();
+    let device = &gpu.device;
+    let reflection = ShaderReflection::from_wgsl("test.wgsl", SHADER).unwrap();
+
+    // fine
+    let camera = buffer(device, 64);
+    BindGrouping::builder("camera")
+        .uniform(0, ShaderStages::VERTEX, &camera)
+        .build_checked(device, &reflection, 0)
+        .unwrap();
+
+    // visible to the wrong stage
+    let err = BindGrouping::builder("camera")
+        .uniform(0, ShaderStages::FRAGMENT, &camera)
+        .build_checked(device, &reflection, 0)
+        .err()
+        .unwrap()
+        .to_string();
+    assert!(err.contains("@group(0) @binding(0) `camera`") && err.contains("VERTEX"), "{}", err);
+
+    // a mat4 needs 64 bytes
+    let small = buffer(device, 16);
+    let err = BindGrouping::builder("camera")
+        .uniform(0, ShaderStages::VERTEX, &small)
+        .build_checked(device, &reflection, 0)
+        .err()
+        .unwrap()
+        .to_string();
+    assert!(err.contains("needs 64 bytes") && err.contains("16 bytes"), "{}", err);
+
+    // texture and sampler left out, and a storage buffer where the uniform should be
+    let material = device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size: 16,
+        usage: wgpu::BufferUsages::STORAGE,
+        mapped_at_creation: false,
+    });
+    let err = BindGrouping::builder("material")
+        .storage(0, ShaderStages::FRAGMENT, &material, true)
+        .build_checked(device, &reflection, 1)
+        .err()
+        .unwrap()
+        .to_string();
+    assert!(err.contains("`material` is a uniform buffer in the shader but a read only storage buffer was bound"), "{}", err);
+    assert!(err.contains("`albedo`") && err.contains("`albedo_sampler`"), "{}", err);
+}
+
+#[test]
+fn mismatched_vertex_buffers_are_errors() {
+    let reflection = ShaderReflection::from_wgsl("test.wgsl", SHADER).unwrap();
+
+    let attributes = wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x2, 2 => Uint32];
+    let layout = |attributes| wgpu::VertexBufferLayout {
+        array_stride: 24,
+        step_mode: wgpu::VertexStepMode::Vertex,
+        attributes,
+    };
+    reflection.check_vertex_buffers("vs_main", &[layout(&attributes)]).unwrap();
+
+    // the uv is missing and the id comes in as a float
+    let attributes = wgpu::vertex_attr_array![0 => Float32x3, 2 => Float32];
+    let err = reflection.check_vertex_buffers("vs_main", &[layout(&attributes)]).unwrap_err().to_string();
+    assert!(err.contains("@location(1) `uv` (vec2<f32>)"), "{}", err);
+    assert!(err.contains("`id` as u32 but the attribute is Float32"), "{}", err);
+
+    assert!(reflection.check_vertex_buffers("main", &[]).is_err());
+}
+
+// Layouts made straight from the reflection are accepted by wgpu for a pipeline using the shader
+#[test]
+fn generated_layouts_build_a_pipeline() {
+    let gpu = gpu();
+    let device = &gpu.device;
+    let reflection = ShaderReflection::from_wgsl("test.wgsl", SHADER).unwrap();
+
+    let layouts = reflection.create_bind_group_layouts(device);
+    let layouts: Vec<_> = layouts.iter().collect();
+    let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: None,
+        source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+    });
+    let attributes = wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x2, 2 => Uint32];
+
+    device.push_error_scope(wgpu::ErrorFilter::Validation);
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: None,
+        bind_group_layouts: &layouts,
+        push_constant_ranges: &[],
+    });
+    let _pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: None,
+        layout: Some(&layout),
+        vertex: wgpu::VertexState {
+            module: &module,
+            entry_point: Some("vs_main"),
+            buffers: &[wgpu::VertexBufferLayout {
+                array_stride: 24,
+                step_mode: wgpu::VertexStepMode::Vertex,
+                attributes: &attributes,
+            }],
+            compilation_options: Default::default(),
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &module,
+            entry_point: Some("fs_main"),
+            targets: &[Some(wgpu::TextureFormat::Rgba8Unorm.into())],
+            compilation_options: Default::default(),
+        }),
+        primitive: Default::default(),
+        depth_stencil: None,
+        multisample: Default::default(),
+        multiview: None,
+        cache: None,
+    });
+    let error = pollster::block_on(device.pop_error_scope());
+    assert!(error.is_none(), "{:?}", error);
+}
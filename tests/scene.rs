@@ -0,0 +1,146 @@
+use std::f32::consts::FRAC_PI_2;
+use nalgebra::{Matrix4, Point3, UnitQuaternion, Vector3};
+use rs_gui_library::scene::light::{Light, LightsUniform};
+use rs_gui_library::scene::scene::{Attachment, SceneGraph, Transform};
+
+fn origin(world: &Matrix4<f32>) -> Point3<f32> {
+    world.transform_point(&Point3::origin())
+}
+
+fn assert_near(actual: Point3<f32>, expected: Point3<f32>) {
+    assert!((actual - expected).norm() < 1e-5, "{} != {}", actual, expected);
+}
+
+#[test]
+fn world_matrices_follow_parents() {
+    let mut scene = SceneGraph::new();
+    let root = scene.add("root", Transform::at(Vector3::new(1.0, 0.0, 0.0)));
+    let arm = scene
+        .add_child(root, "arm", Transform::at(Vector3::new(0.0, 0.0, -2.0)).with_rotation(UnitQuaternion::from_axis_angle(&Vector3::y_axis(), FRAC_PI_2)))
+        .unwrap();
+    let hand = scene.add_child(arm, "hand", Transform::at(Vector3::new(0.0, 0.0, 1.0)).with_scale(Vector3::new(2.0, 2.0, 2.0))).unwrap();
+
+    scene.update();
+    assert_near(origin(scene.world(arm).unwrap()), Point3::new(1.0, 0.0, -2.0));
+    // the arm's quarter turn swings the hand's +z round to +x
+    assert_near(origin(scene.world(hand).unwrap()), Point3::new(2.0, 0.0, -2.0));
+    assert_eq!(scene.take_changed(), [root, arm, hand]);
+    assert_eq!(scene.take_changed(), []);
+
+    // only the moved node and what's under it get worked out again
+    scene.set_transform(arm, Transform::at(Vector3::new(0.0, 3.0, 0.0)));
+    assert_near(origin(scene.world(hand).unwrap()), Point3::new(2.0, 0.0, -2.0));
+    scene.update();
+    assert_eq!(scene.take_changed(), [arm, hand]);
+    assert_near(origin(scene.world(hand).unwrap()), Point3::new(1.0, 3.0, 1.0));
+
+    // nothing moved, nothing changed
+    scene.update();
+    assert_eq!(scene.take_changed(), []);
+}
+
+#[test]
+fn reparenting_and_removing() {
+    let mut scene = SceneGraph::new();
+    let a = scene.add("a", Transform::at(Vector3::new(5.0, 0.0, 0.0)));
+    let b = scene.add("b", Transform::default());
+    let child = scene.add_child(a, "child", Transform::at(Vector3::new(0.0, 1.0, 0.0))).unwrap();
+    let grandchild = scene.add_child(child, "grandchild", Transform::default()).unwrap();
+
+    // no loops
+    let err = scene.set_parent(a, Some(grandchild)).unwrap_err().to_string();
+    assert!(err.contains("its own children"), "{}", err);
+    assert_eq!(scene.get(a).unwrap().parent(), None);
+
+    // the local transform stays, the world one follows the new parent
+    scene.set_parent(child, Some(b)).unwrap();
+    scene.update();
+    assert_eq!(scene.get(a).unwrap().children(), []);
+    assert_eq!(scene.get(b).unwrap().children(), [child]);
+    assert_near(origin(scene.world(grandchild).unwrap()), Point3::new(0.0, 1.0, 0.0));
+
+    // children go with their parent
+    scene.take_changed();
+    assert!(scene.remove(child));
+    assert!(!scene.remove(child));
+    assert_eq!(scene.take_removed(), [child, grandchild]);
+    assert!(scene.get(grandchild).is_none());
+    assert!(scene.add_child(grandchild, "orphan", Transform::default()).is_none());
+    assert_eq!(scene.len(), 2);
+
+    scene.clear();
+    assert!(scene.is_empty());
+    assert_eq!(scene.take_removed().len(), 2);
+    assert_eq!(scene.roots(), []);
+}
+
+#[test]
+fn removed_ids_stay_dead_when_the_slot_is_reused() {
+    let mut scene = SceneGraph::new();
+    let old = scene.add("old", Transform::default());
+    scene.remove(old);
+    let new = scene.add("new", Transform::at(Vector3::new(1.0, 0.0, 0.0)));
+    assert_ne!(old, new);
+
+    assert!(scene.get(old).is_none());
+    assert!(!scene.set_transform(old, Transform::default()));
+    assert!(!scene.attach(old, Attachment::Camera));
+    assert!(!scene.remove(old));
+    assert_eq!(scene.get(new).unwrap().name, "new");
+
+    scene.update();
+    assert_eq!(scene.take_removed(), [old]);
+    assert_eq!(scene.take_changed(), [new]);
+}
+
+#[test]
+fn cameras_and_lights_come_from_their_nodes() {
+    let mut scene = SceneGraph::new();
+    assert!(scene.view().is_none());
+
+    let rig = scene.add("rig", Transform::at(Vector3::new(0.0, 2.0, 0.0)));
+    let eye = Transform::looking_at(Point3::new(0.0, 0.0, 5.0), Point3::new(0.0, -2.0, 0.0), Vector3::y());
+    let camera = scene.add_child(rig, "camera", eye).unwrap();
+    scene.attach(camera, Attachment::Camera);
+    let sun = scene.add("sun", Transform::looking_at(Point3::new(0.0, 1.0, 0.0), Point3::origin(), Vector3::z()));
+    scene.attach(sun, Attachment::Light(Light::directional([1.0, 1.0, 1.0], 2.0)));
+    let lamp = scene.add_child(rig, "lamp", Transform::at(Vector3::new(1.0, 0.0, 0.0))).unwrap();
+    scene.attach(lamp, Attachment::Light(Light::point([1.0, 0.5, 0.0], 1.0, 4.0)));
+    scene.update();
+
+    // the same view a CameraMatrix looking from the camera's world position would have
+    assert_eq!(scene.camera(), Some(camera));
+    let expected = Matrix4::look_at_rh(&Point3::new(0.0, 2.0, 5.0), &Point3::origin(), &Vector3::y());
+    assert!((scene.view().unwrap() - expected).abs().max() < 1e-5);
+
+    let uniform = LightsUniform::new(scene.ambient, scene.lights());
+    assert_eq!(uniform.count, 2);
+    // rig's lamp comes first, depth first from the roots
+    assert_eq!(uniform.lights[0].position, [1.0, 2.0, 0.0, 1.0]);
+    assert_eq!(uniform.lights[0].color, [1.0, 0.5, 0.0, 4.0]);
+    let to_sun = uniform.lights[1].position;
+    assert!((to_sun[1] - 1.0).abs() < 1e-5 && to_sun[3] == 0.0, "{:?}", to_sun);
+    assert_eq!(uniform.lights[1].color, [2.0, 2.0, 2.0, 0.0]);
+
+    // attaching something else counts as a change even without moving
+    scene.take_changed();
+    assert_eq!(scene.detach(camera), Some(Attachment::Camera));
+    assert_eq!(scene.take_changed(), [camera]);
+    assert!(scene.view().is_none());
+}
+
+#[test]
+fn transform_from_matrix_round_trips() {
+    let transform = Transform::at(Vector3::new(1.0, -2.0, 3.0))
+        .with_rotation(UnitQuaternion::from_euler_angles(0.3, -1.1, 0.7))
+        .with_scale(Vector3::new(2.0, 0.5, 1.5));
+    let back = Transform::from_matrix(&transform.matrix());
+    assert!((back.translation - transform.translation).norm() < 1e-5);
+    assert!((back.scale - transform.scale).norm() < 1e-5);
+    assert!(back.rotation.angle_to(&transform.rotation) < 1e-4);
+
+    // a mirror keeps its handedness in the scale
+    let mirrored = Matrix4::new_nonuniform_scaling(&Vector3::new(-1.0, 1.0, 1.0));
+    let back = Transform::from_matrix(&mirrored);
+    assert!((back.matrix() - mirrored).norm() < 1e-5);
+}
@@ -0,0 +1,95 @@
+mod common;
+
+use common::gpu;
+use std::io::Cursor;
+use image::{ImageFormat, Rgba, RgbaImage};
+use rs_gui_library::shaders::bind_group::BindGrouping;
+use rs_gui_library::textures::loader::{TextureOptions, downsample, mip_level_count};
+use rs_gui_library::textures::textures::{ColorTexture, SamplerOptions};
+
+fn encode(image: &RgbaImage, format: ImageFormat) -> Vec<u8> {
+    let mut bytes = Cursor::new(Vec::new());
+    match format {
+        // JPEG has no alpha channel
+        ImageFormat::Jpeg => image::DynamicImage::ImageRgba8(image.clone()).to_rgb8().write_to(&mut bytes, format),
+        _ => image.write_to(&mut bytes, format),
+    }
+    .unwrap();
+    bytes.into_inner()
+}
+
+// PNG is lossless so what comes back off the GPU has to be exactly what went in
+#[test]
+fn png_round_trip() {
+    let gpu = gpu();
+    let image = RgbaImage::from_fn(5, 3, |x, y| Rgba([x as u8 * 50, y as u8 * 100, 7, 255 - x as u8]));
+
+    let texture = ColorTexture::from_bytes(&gpu.device, &gpu.queue, &encode(&image, ImageFormat::Png), &TextureOptions::default()).unwrap();
+
+    assert_eq!((texture.width, texture.height), (5, 3));
+    assert_eq!(texture.format, wgpu::TextureFormat::Rgba8UnormSrgb);
+    assert_eq!(texture.mip_level_count, 1);
+    assert_eq!(texture.read_rgba8(&gpu.device, &gpu.queue).unwrap(), image);
+}
+
+#[test]
+fn jpeg_as_linear_data() {
+    let gpu = gpu();
+    let image = RgbaImage::from_pixel(16, 16, Rgba([200, 100, 50, 255]));
+    let options = TextureOptions::default().linear();
+
+    let texture = ColorTexture::from_bytes(&gpu.device, &gpu.queue, &encode(&image, ImageFormat::Jpeg), &options).unwrap();
+
+    assert_eq!(texture.format, wgpu::TextureFormat::Rgba8Unorm);
+    let pixels = texture.read_rgba8(&gpu.device, &gpu.queue).unwrap();
+    for (actual, expected) in pixels.get_pixel(8, 8).0.iter().zip([200, 100, 50, 255]) {
+        assert!(actual.abs_diff(expected) <= 3, "{:?}", pixels.get_pixel(8, 8));
+    }
+}
+
+#[test]
+fn mipmaps_and_sampler_options() {
+    let gpu = gpu();
+    let image = RgbaImage::from_fn(64, 16, |x, _| if x % 2 == 0 { Rgba([255, 255, 255, 255]) } else { Rgba([0, 0, 0, 255]) });
+    let options = TextureOptions::default().with_mipmaps().with_sampler(SamplerOptions {
+        anisotropy: 8,
+        ..SamplerOptions::repeat()
+    });
+
+    let texture = ColorTexture::from_image(&gpu.device, &gpu.queue, &image, "checker", &options);
+    assert_eq!(texture.mip_level_count, 7);
+
+    // it has to go into a bind group without validation errors
+    gpu.device.push_error_scope(wgpu::ErrorFilter::Validation);
+    let _bind_group = BindGrouping::texture(&gpu.device, &texture).unwrap();
+    assert!(pollster::block_on(gpu.device.pop_error_scope()).is_none());
+}
+
+#[test]
+fn mip_level_counts() {
+    assert_eq!(mip_level_count(1, 1), 1);
+    assert_eq!(mip_level_count(2, 1), 2);
+    assert_eq!(mip_level_count(256, 64), 9);
+    assert_eq!(mip_level_count(300, 20), 9);
+}
+
+// colors ignore what's under transparent texels, data keeps every channel as it is
+#[test]
+fn only_srgb_mips_are_weighted_by_alpha() {
+    let image = RgbaImage::from_fn(2, 2, |x, _| if x == 0 { Rgba([255, 0, 0, 255]) } else { Rgba([0, 0, 255, 0]) });
+
+    let color = downsample(&image, true);
+    assert_eq!(color.get_pixel(0, 0).0, [255, 0, 0, 128]);
+
+    // a packed mask with something else than coverage in alpha
+    let data = downsample(&image, false);
+    assert_eq!(data.get_pixel(0, 0).0, [128, 0, 128, 128]);
+}
+
+#[test]
+fn missing_file_and_garbage_bytes_are_errors() {
+    let gpu = gpu();
+    let options = TextureOptions::default();
+    assert!(ColorTexture::from_path(&gpu.device, &gpu.queue, "does/not/exist.png", &options).is_err());
+    assert!(ColorTexture::from_bytes(&gpu.device, &gpu.queue, b"not an image", &options).is_err());
+}
@@ -0,0 +1,83 @@
+use std::cell::Cell;
+use std::rc::Rc;
+use rs_gui_library::widgets::panel::Panel;
+use rs_gui_library::widgets::toggle::Toggle;
+use rs_gui_library::widgets::tree::WidgetTree;
+use winit::keyboard::KeyCode;
+
+// a focused toggle bound to `on`, and how many times on_change fired
+fn focused_toggle() -> (WidgetTree, Rc<Cell<bool>>, Rc<Cell<u32>>) {
+    let on = Rc::new(Cell::new(false));
+    let changes = Rc::new(Cell::new(0));
+    let mut tree = WidgetTree::new();
+    let root = tree.set_root(Panel::default());
+    let counter = changes.clone();
+    let toggle = tree
+        .add_child(root, Toggle::new(false).bind(on.clone()).on_change(move |_| counter.set(counter.get() + 1)))
+        .unwrap();
+    tree.set_focus(Some(toggle));
+    (tree, on, changes)
+}
+
+#[test]
+fn held_key_repeats_flip_once() {
+    let (mut tree, on, changes) = focused_toggle();
+
+    // the OS keeps sending presses while the key is held
+    for _ in 0..5 {
+        assert!(tree.key(KeyCode::Space, true));
+    }
+    assert!(!on.get());
+
+    assert!(tree.key(KeyCode::Space, false));
+    assert!(on.get());
+    assert_eq!(changes.get(), 1);
+
+    tree.key(KeyCode::Enter, true);
+    tree.key(KeyCode::Enter, true);
+    tree.key(KeyCode::Enter, false);
+    assert!(!on.get());
+    assert_eq!(changes.get(), 2);
+}
+
+#[test]
+fn release_without_a_press_does_nothing() {
+    // focus landing on the toggle while Space is already down
+    let (mut tree, on, changes) = focused_toggle();
+    tree.key(KeyCode::Space, false);
+    assert!(!on.get());
+    assert_eq!(changes.get(), 0);
+}
+
+#[test]
+fn press_is_forgotten_when_focus_leaves() {
+    let (mut tree, on, changes) = focused_toggle();
+    let toggle = tree.focused().unwrap();
+    // somewhere for Tab to go
+    let root = tree.root().unwrap();
+    tree.add_child(root, Toggle::new(false)).unwrap();
+
+    tree.key(KeyCode::Space, true);
+    // Tab away while Space is still down, the release lands on the other widget
+    tree.key(KeyCode::Tab, true);
+    assert_ne!(tree.focused(), Some(toggle));
+    tree.key(KeyCode::Space, false);
+
+    tree.set_focus(Some(toggle));
+    tree.key(KeyCode::Space, false);
+    assert!(!on.get());
+    assert_eq!(changes.get(), 0);
+}
+
+#[test]
+fn press_is_forgotten_when_disabled() {
+    let (mut tree, on, changes) = focused_toggle();
+    let toggle = tree.focused().unwrap();
+
+    tree.key(KeyCode::Space, true);
+    tree.get_mut::<Toggle>(toggle).unwrap().set_disabled(true);
+    tree.get_mut::<Toggle>(toggle).unwrap().set_disabled(false);
+    tree.key(KeyCode::Space, false);
+    assert!(!on.get());
+    assert_eq!(changes.get(), 0);
+}